@@ -63,7 +63,31 @@ const translations = {
         "source_tauron_short": "Tauron",
         "source_water_short": "MPWiK",
         "source_fortum_short": "Fortum",
-        "lbl_other_alerts_divider": "Other alerts"
+        "lbl_other_alerts_divider": "Other alerts",
+        "msg_settings_recovered": "⚠️ Your settings file was damaged and has been restored from a backup.",
+        "msg_settings_reset": "⚠️ Your settings file was damaged. Please set your location again.",
+        "settings_transfer": "Export / Import",
+        "settings_import_mode": "When importing",
+        "import_mode_merge": "Add to my settings",
+        "import_mode_replace": "Replace my settings",
+        "settings_export": "Export to file",
+        "settings_import": "Import from file",
+        "msg_exported": "✅ Exported.",
+        "msg_imported": "✅ Imported.",
+        "msg_import_confirm": "Import these changes?",
+        "msg_import_no_changes": "Nothing would change.",
+        "lbl_acknowledgements_added": "Acknowledged alerts added",
+        "settings_sync_folder": "Sync folder",
+        "settings_sync_folder_placeholder": "e.g. a Syncthing or Nextcloud folder",
+        "settings_sync_apply": "Use sync folder",
+        "msg_sync_on": "✅ Settings are synced through this folder.",
+        "msg_sync_off": "✅ Folder sync turned off.",
+        "badge_new": "New",
+        "badge_changed": "Changed",
+        "badge_rescheduled": "Rescheduled",
+        "badge_cancelled": "Cancelled",
+        "badge_overrunning": "Running late",
+        "lbl_previously": "Previously"
     },
     pl: {
         "title": "Awaria",
@@ -126,7 +150,31 @@ const translations = {
         "source_tauron_short": "Tauron",
         "source_water_short": "MPWiK",
         "source_fortum_short": "Fortum",
-        "lbl_other_alerts_divider": "Inne alerty"
+        "lbl_other_alerts_divider": "Inne alerty",
+        "msg_settings_recovered": "⚠️ Plik ustawień był uszkodzony i został przywrócony z kopii zapasowej.",
+        "msg_settings_reset": "⚠️ Plik ustawień był uszkodzony. Ustaw ponownie swoją lokalizację.",
+        "settings_transfer": "Eksport / import",
+        "settings_import_mode": "Przy imporcie",
+        "import_mode_merge": "Dodaj do moich ustawień",
+        "import_mode_replace": "Zastąp moje ustawienia",
+        "settings_export": "Eksportuj do pliku",
+        "settings_import": "Importuj z pliku",
+        "msg_exported": "✅ Wyeksportowano.",
+        "msg_imported": "✅ Zaimportowano.",
+        "msg_import_confirm": "Zaimportować te zmiany?",
+        "msg_import_no_changes": "Nic się nie zmieni.",
+        "lbl_acknowledgements_added": "Dodane potwierdzone alerty",
+        "settings_sync_folder": "Folder synchronizacji",
+        "settings_sync_folder_placeholder": "np. folder Syncthing lub Nextcloud",
+        "settings_sync_apply": "Użyj folderu synchronizacji",
+        "msg_sync_on": "✅ Ustawienia są synchronizowane przez ten folder.",
+        "msg_sync_off": "✅ Synchronizacja przez folder wyłączona.",
+        "badge_new": "Nowe",
+        "badge_changed": "Zmienione",
+        "badge_rescheduled": "Nowy termin",
+        "badge_cancelled": "Odwołane",
+        "badge_overrunning": "Przedłuża się",
+        "lbl_previously": "Wcześniej"
     }
 };
 
@@ -3,15 +3,79 @@ if (typeof document !== 'undefined') {
         initSettings();
         initPullToRefresh();
         initRefreshButton();
+        initBackendEvents();
         loadSettingsAndFetch();
     });
 }
 
+// ── Backend events ────────────────────────────────────────
+
+function initBackendEvents() {
+    if (!window.__TAURI__ || !window.__TAURI__.event) return;
+
+    // settings.json was changed by another process (widget config, sync tool)
+    window.__TAURI__.event.listen('settings-changed', () => {
+        loadSettingsAndFetch();
+    });
+
+    // The backend polls the sources in the background
+    window.__TAURI__.event.listen('alerts-updated', (event) => {
+        lastAlerts = event.payload.alerts;
+        previousWindows = {};
+        (event.payload.rescheduled || []).forEach(r => {
+            previousWindows[r.alertId] = r;
+        });
+        cancelledAlerts = event.payload.cancelled || [];
+        overrunningIds = new Set(event.payload.overrunning || []);
+        const fetched = event.payload.sources
+            .map(status => status.lastFetchAt)
+            .filter(Boolean)
+            .sort();
+        if (fetched.length > 0) updateLastUpdated(new Date(fetched[fetched.length - 1]));
+        renderAlerts(lastAlerts, document.getElementById('outages-container'), currentSettings);
+    });
+
+    window.__TAURI__.event.listen('alerts-changed', () => {
+        refreshAlertChanges();
+    });
+
+    // The window was brought up from a notification
+    window.__TAURI__.event.listen('focus-alert', (event) => {
+        focusAlert(event.payload);
+    });
+
+    // Platforms that report notification clicks name the alert that was clicked
+    const notification = window.__TAURI__.notification;
+    if (notification && notification.onAction) {
+        notification.onAction((action) => {
+            const alertId = action.notification && action.notification.extra && action.notification.extra.alertId;
+            if (alertId) window.__TAURI__.core.invoke('open_notified_alert', { alertId });
+        }).catch(() => {
+            // Desktop notifications don't report clicks; window focus covers them
+        });
+    }
+
+    // Badges mark what is new since the user last looked at the window
+    const markSeen = () => {
+        window.__TAURI__.core.invoke('mark_alerts_seen').catch(error => {
+            console.error('Failed to mark alerts seen:', error);
+        });
+    };
+    window.addEventListener('blur', markSeen);
+    document.addEventListener('visibilitychange', () => {
+        if (document.visibilityState === 'hidden') markSeen();
+    });
+}
+
 // ── Settings ──────────────────────────────────────────────
 
 let currentSettings = null;
 let lastAlerts = [];
 let lastFetchDate = null;
+let alertChanges = {}; // alert id -> change kind, for "new"/"changed" badges
+let previousWindows = {}; // alert id -> window before it was rescheduled
+let cancelledAlerts = []; // cancelled before they started, shown crossed out
+let overrunningIds = new Set(); // still listed after their end
 
 function initSettings() {
     const btn = document.getElementById('settings-btn');
@@ -86,6 +150,20 @@ function initSettings() {
         document.getElementById('location-settings-collapsible').classList.toggle('collapsed');
     });
 
+    const transferTrigger = document.querySelector('#transfer-settings-collapsible .collapsible-trigger');
+    transferTrigger.addEventListener('click', () => {
+        document.getElementById('transfer-settings-collapsible').classList.toggle('collapsed');
+    });
+
+    const importInput = document.getElementById('import-file-input');
+    document.getElementById('export-settings-btn').addEventListener('click', exportSettings);
+    document.getElementById('import-settings-btn').addEventListener('click', () => importInput.click());
+    importInput.addEventListener('change', () => {
+        if (importInput.files.length > 0) importSettings(importInput.files[0]);
+        importInput.value = '';
+    });
+    document.getElementById('sync-folder-btn').addEventListener('click', setSyncFolder);
+
     ['source-tauron-check', 'source-water-check', 'source-fortum-check'].forEach(id => {
         const checkbox = document.getElementById(id);
         checkbox.addEventListener('change', () => {
@@ -134,6 +212,7 @@ async function loadSettingsAndFetch() {
             document.getElementById('city-input').value = settings.cityName;
             document.getElementById('street-input').value = settings.streetName;
             document.getElementById('house-input').value = settings.houseNo;
+            document.getElementById('sync-folder-input').value = settings.syncFolder || '';
             if (settings.theme) {
                 document.getElementById('theme-select').value = settings.theme;
             }
@@ -150,10 +229,12 @@ async function loadSettingsAndFetch() {
                 document.getElementById('location-settings-collapsible').classList.add('collapsed');
             }
 
+            checkSettingsRecovery();
             fetchOutages();
         } else {
             initLanguage('system');
             applyTranslations();
+            checkSettingsRecovery();
             // No settings yet — show setup prompt
             const container = document.getElementById('outages-container');
             container.innerHTML = `<div class="no-outages">${typeof t !== 'undefined' ? t('setup_prompt') : 'Tap ⚙️ to configure your location.'}</div>`;
@@ -168,6 +249,22 @@ async function loadSettingsAndFetch() {
     }
 }
 
+// Tell the user once if the backend had to replace a corrupt settings file
+async function checkSettingsRecovery() {
+    try {
+        const recovery = await window.__TAURI__.core.invoke('settings_recovery_status');
+        if (!recovery) return;
+        const status = document.getElementById('settings-status');
+        const key = recovery.restoredBackup ? 'msg_settings_recovered' : 'msg_settings_reset';
+        status.textContent = typeof t !== 'undefined' ? t(key) : '⚠️ Settings were recovered.';
+        status.className = 'settings-status error';
+        document.getElementById('settings-panel').classList.remove('hidden');
+        await window.__TAURI__.core.invoke('dismiss_settings_recovery');
+    } catch (error) {
+        console.error('Failed to check settings recovery:', error);
+    }
+}
+
 async function saveSettings() {
     const cityName = document.getElementById('city-input').value.trim();
     const streetName = document.getElementById('street-input').value.trim();
@@ -191,7 +288,9 @@ async function saveSettings() {
         status.className = 'settings-status';
         const cities = await window.__TAURI__.core.invoke('lookup_city', { cityName });
 
-        const city = cities.find(c => c.Name === cityName);
+        // Results come ranked from the backend; accept a match typed without diacritics
+        const city = cities.find(c => c.Name === cityName)
+            || cities.find(c => foldDiacritics(c.Name) === foldDiacritics(cityName));
         if (!city) {
             const available = cities.map(c => c.Name).join(', ');
             status.textContent = (typeof t !== 'undefined' ? t('err_city_not_found') : `❌ City not found. Did you mean: `) + `${available || 'none'}`;
@@ -207,7 +306,8 @@ async function saveSettings() {
             cityGaid: city.GAID
         });
 
-        const street = streets.find(s => s.Name === streetName);
+        const street = streets.find(s => s.Name === streetName)
+            || streets.find(s => foldDiacritics(s.Name) === foldDiacritics(streetName));
         if (!street) {
             const available = streets.map(s => s.Name).join(', ');
             status.textContent = (typeof t !== 'undefined' ? t('err_street_not_found') : `❌ Street not found. Did you mean: `) + `${available || 'none'}`;
@@ -219,9 +319,11 @@ async function saveSettings() {
         // Step 3: Save settings
         status.textContent = typeof t !== 'undefined' ? t('msg_saving') : '💾 Saving...';
 
+        // Keep fields this form doesn't edit (saved locations etc.)
         const newSettings = {
-            cityName,
-            streetName,
+            ...(currentSettings || {}),
+            cityName: city.Name,
+            streetName: street.Name,
             houseNo,
             cityGAID: city.GAID,
             streetGAID: street.GAID,
@@ -265,6 +367,88 @@ async function saveSettings() {
     }
 }
 
+// ── Export / import ───────────────────────────────────────
+
+async function exportSettings() {
+    const status = document.getElementById('transfer-status');
+    try {
+        const bundle = await window.__TAURI__.core.invoke('export_settings');
+        const link = document.createElement('a');
+        link.href = URL.createObjectURL(new Blob([bundle], { type: 'application/json' }));
+        link.download = `awaria-settings-${new Date().toISOString().slice(0, 10)}.json`;
+        link.click();
+        URL.revokeObjectURL(link.href);
+        status.textContent = typeof t !== 'undefined' ? t('msg_exported') : '✅ Exported.';
+        status.className = 'settings-status success';
+    } catch (error) {
+        status.textContent = `❌ ${error}`;
+        status.className = 'settings-status error';
+    }
+}
+
+function describeImportPreview(preview) {
+    const tr = (key, fallback) => typeof t !== 'undefined' ? t(key) : fallback;
+    const lines = [];
+    preview.changes.forEach(change => {
+        lines.push(`${change.field}: ${JSON.stringify(change.current)} → ${JSON.stringify(change.incoming)}`);
+    });
+    preview.locationsAdded.forEach(loc => lines.push(`+ ${loc.label}`));
+    preview.locationsRemoved.forEach(loc => lines.push(`− ${loc.label}`));
+    if (preview.acknowledgementsAdded > 0) {
+        lines.push(`${tr('lbl_acknowledgements_added', 'Acknowledged alerts added')}: ${preview.acknowledgementsAdded}`);
+    }
+    return lines.length > 0 ? lines.join('\n') : tr('msg_import_no_changes', 'Nothing would change.');
+}
+
+async function importSettings(file) {
+    const status = document.getElementById('transfer-status');
+    const mode = document.getElementById('import-mode-select').value;
+    try {
+        const bundle = await file.text();
+        const preview = await window.__TAURI__.core.invoke('import_settings', { bundle, mode, apply: false });
+        if (!preview.valid) {
+            status.textContent = `❌ ${preview.errors.join(' ')}`;
+            status.className = 'settings-status error';
+            return;
+        }
+
+        const question = typeof t !== 'undefined' ? t('msg_import_confirm') : 'Import these changes?';
+        if (!confirm(`${question}\n\n${describeImportPreview(preview)}`)) return;
+
+        await window.__TAURI__.core.invoke('import_settings', { bundle, mode, apply: true });
+        status.textContent = typeof t !== 'undefined' ? t('msg_imported') : '✅ Imported.';
+        status.className = 'settings-status success';
+        await loadSettingsAndFetch();
+    } catch (error) {
+        status.textContent = `❌ ${error}`;
+        status.className = 'settings-status error';
+    }
+}
+
+// An empty folder turns sync off
+async function setSyncFolder() {
+    const status = document.getElementById('transfer-status');
+    const folder = document.getElementById('sync-folder-input').value.trim();
+    try {
+        const sync = await window.__TAURI__.core.invoke('set_sync_folder', { folder: folder || null });
+        if (sync.lastError) {
+            status.textContent = `❌ ${sync.lastError}`;
+            status.className = 'settings-status error';
+            return;
+        }
+        status.textContent = typeof t !== 'undefined' ? t(folder ? 'msg_sync_on' : 'msg_sync_off') : '✅ Saved.';
+        status.className = 'settings-status success';
+        await loadSettingsAndFetch();
+    } catch (error) {
+        status.textContent = `❌ ${error}`;
+        status.className = 'settings-status error';
+    }
+}
+
+function foldDiacritics(text) {
+    return text.toLowerCase().normalize('NFD').replace(/[\u0300-\u036f]/g, '').replace(/ł/g, 'l');
+}
+
 function applyTheme(theme) {
     const root = document.documentElement;
     if (!theme || theme === 'system') {
@@ -344,6 +528,7 @@ async function fetchOutages() {
         lastAlerts = alerts; // Cache for instant re-rendering
         updateLastUpdated(new Date());
         renderAlerts(alerts, container, currentSettings);
+        refreshAlertChanges();
     } catch (error) {
         console.error('Error fetching data:', error);
         container.innerHTML = `<div class="error">${typeof t !== 'undefined' ? t('err_load_failed') : 'Failed to load alert data. Error: '}${error}</div>`;
@@ -368,6 +553,15 @@ function updateLastUpdated(date) {
     el.textContent = `${label}: ${lastFetchDate.toLocaleTimeString(localeStr)}`;
 }
 
+// Scroll to an alert card and highlight it briefly
+function focusAlert(alertId) {
+    const card = document.querySelector(`[data-alert-id="${CSS.escape(alertId)}"]`);
+    if (!card) return;
+    card.scrollIntoView({ behavior: 'smooth', block: 'center' });
+    card.classList.add('alert-focus');
+    setTimeout(() => card.classList.remove('alert-focus'), 3000);
+}
+
 function filterAlerts(alerts, streetName) {
     if (!alerts || !streetName) return [];
 
@@ -428,12 +622,13 @@ function renderAlerts(alerts, container, settings) {
 
     // Filter by enabled sources and finished status
     const enabledSources = (settings && settings.enabledSources) ? settings.enabledSources : ['tauron', 'water', 'fortum'];
-    const activeAlerts = alerts.filter(item => {
+    const cancelled = cancelledAlerts.map(item => ({ ...item, cancelled: true }));
+    const activeAlerts = alerts.concat(cancelled).filter(item => {
         // Source filter
         if (!enabledSources.includes(item.source)) return false;
 
-        // Date filter
-        if (!item.endDate) return true;
+        // Date filter; a provider still listing an alert after its end means it runs late
+        if (!item.endDate || overrunningIds.has(item.id)) return true;
         const end = new Date(item.endDate);
         return isNaN(end.getTime()) || end > now;
     });
@@ -551,6 +746,45 @@ function renderAlerts(alerts, container, settings) {
     }
 }
 
+async function refreshAlertChanges() {
+    try {
+        const changes = await window.__TAURI__.core.invoke('get_alert_changes');
+        alertChanges = {};
+        changes.forEach(change => {
+            if (change.kind !== 'removed' && change.kind !== 'cancelled') alertChanges[change.alert.id] = change.kind;
+        });
+        renderAlerts(lastAlerts || [], document.getElementById('outages-container'), currentSettings);
+    } catch (error) {
+        console.error('Failed to load alert changes:', error);
+    }
+}
+
+function alertBadge(item) {
+    if (item.cancelled) {
+        const label = typeof t !== 'undefined' ? t('badge_cancelled') : 'Cancelled';
+        return `<span class="alert-badge badge-cancelled">${label}</span>`;
+    }
+    if (overrunningIds.has(item.id)) {
+        const label = typeof t !== 'undefined' ? t('badge_overrunning') : 'Running late';
+        return `<span class="alert-badge badge-overrunning">${label}</span>`;
+    }
+    const kind = alertChanges[item.id];
+    if (!kind) return '';
+    const label = kind === 'added'
+        ? (typeof t !== 'undefined' ? t('badge_new') : 'New')
+        : kind === 'time_changed'
+        ? (typeof t !== 'undefined' ? t('badge_rescheduled') : 'Rescheduled')
+        : (typeof t !== 'undefined' ? t('badge_changed') : 'Changed');
+    return `<span class="alert-badge badge-${kind}">${label}</span>`;
+}
+
+function previousWindow(item) {
+    const previous = previousWindows[item.id];
+    if (!previous) return '';
+    const lbl = typeof t !== 'undefined' ? t('lbl_previously') : 'Previously';
+    return `<div class="outage-previous">${lbl}: ${formatDate(previous.previousStartDate)} – ${formatDate(previous.previousEndDate)}</div>`;
+}
+
 function renderCards(alerts, source) {
     const sourceLabel = source === 'water'
         ? (typeof t !== 'undefined' ? t('source_water') : '💧 Water Outage')
@@ -559,11 +793,13 @@ function renderCards(alerts, source) {
         : (typeof t !== 'undefined' ? t('source_tauron') : '⚡ Power Outage');
 
     return alerts.map(item => `
-        <div class="card source-${source}">
+        <div class="card source-${source}${item.cancelled ? ' cancelled' : ''}" data-alert-id="${item.id || ''}">
+            ${alertBadge(item)}
             <span class="outage-type">${sourceLabel}</span>
             <div class="outage-time">
                 ${formatDate(item.startDate)} – ${formatDate(item.endDate)}
             </div>
+            ${previousWindow(item)}
             ${item.description ? `<div class="outage-reason">${item.description}</div>` : ''}
             ${item.message ? `<div class="outage-message">${item.message}</div>` : ''}
         </div>
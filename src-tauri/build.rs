@@ -1,3 +1,50 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[path = "build/pna.rs"]
+mod pna;
+#[path = "build/teryt.rs"]
+mod teryt;
+
 fn main() {
+  println!("cargo:rustc-check-cfg=cfg(teryt_full)");
+  println!("cargo:rustc-check-cfg=cfg(postal_full)");
+  if env::var_os("CARGO_FEATURE_TERYT").is_some() {
+    bundle_index("teryt.tsv.gz", "TERYT_DIR", "teryt_full", teryt::generate);
+  }
+  if env::var_os("CARGO_FEATURE_POSTAL").is_some() {
+    bundle_index("pna.tsv.gz", "PNA_CSV", "postal_full", pna::generate);
+  }
   tauri_build::build()
 }
+
+/// Put `name` into OUT_DIR for `include_bytes!`. It is generated from the registers
+/// `var` points at and sets `cfg`; without them the build gets the seed from `data/`.
+fn bundle_index(name: &str, var: &str, cfg: &str, generate: fn(&Path) -> Result<String, String>) {
+  println!("cargo:rerun-if-env-changed={}", var);
+  let out = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR")).join(name);
+  match env::var_os(var) {
+    Some(source) => {
+      let text = generate(Path::new(&source)).unwrap_or_else(|e| panic!("{}: {}", var, e));
+      let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+      encoder.write_all(text.as_bytes()).expect("gzip");
+      fs::write(&out, encoder.finish().expect("gzip")).expect("write index");
+      println!("cargo:rustc-cfg={}", cfg);
+    }
+    None => {
+      if env::var("PROFILE").as_deref() == Ok("release") {
+        println!(
+          "cargo:warning={} is not set, bundling the small seed {} index (see README)",
+          var, name
+        );
+      }
+      let seed = Path::new("data").join(name);
+      println!("cargo:rerun-if-changed={}", seed.display());
+      fs::copy(&seed, &out).expect("copy seed index");
+    }
+  }
+}
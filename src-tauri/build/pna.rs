@@ -0,0 +1,50 @@
+use std::fs;
+use std::path::Path;
+
+/// Index text from the Poczta Polska PNA register: a semicolon-separated UTF-8 CSV
+/// with "PNA", "Miejscowość", "Ulica", "Numery" and "Województwo" columns.
+pub fn generate(path: &Path) -> Result<String, String> {
+    println!("cargo:rerun-if-changed={}", path.display());
+    let data = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut lines = data.trim_start_matches('\u{feff}').lines();
+
+    let header: Vec<String> = lines
+        .next()
+        .unwrap_or_default()
+        .split(';')
+        .map(|h| h.trim().to_lowercase())
+        .collect();
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h == name)
+            .ok_or_else(|| format!("missing column '{}' in {}", name, path.display()))
+    };
+    let (pna, locality, street, numbers, voivodeship) = (
+        column("pna")?,
+        column("miejscowość")?,
+        column("ulica")?,
+        column("numery")?,
+        column("województwo")?,
+    );
+
+    let mut out = String::new();
+    out.push_str("# Generated from the Poczta Polska PNA register\n");
+    for line in lines {
+        let fields: Vec<&str> = line.split(';').map(str::trim).collect();
+        if fields.len() < header.len() {
+            continue;
+        }
+        // "Wrocław (Krzyki)" -> "Wrocław"
+        let locality_name = fields[locality].split(" (").next().unwrap_or_default();
+        out.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\n",
+            fields[pna],
+            locality_name,
+            fields[street],
+            fields[numbers],
+            fields[voivodeship].to_lowercase()
+        ));
+    }
+    Ok(out)
+}
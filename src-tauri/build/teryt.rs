@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Index text from the GUS TERYT registers in `dir`: the "urzędowy" CSV exports
+/// whose file names start with TERC, SIMC and ULIC.
+pub fn generate(dir: &Path) -> Result<String, String> {
+    let terc = read_csv(&register(dir, "TERC")?)?;
+    let simc = read_csv(&register(dir, "SIMC")?)?;
+    let ulic = read_csv(&register(dir, "ULIC")?)?;
+
+    let mut out = String::new();
+    out.push_str("# Generated from the GUS TERYT registers\n");
+
+    let units = unit_names(&terc);
+    for row in &simc {
+        // SIMC: WOJ;POW;GMI;RODZ_GMI;RM;MZ;NAZWA;SYM;SYMPOD;STAN_NA
+        if row.len() < 9 || row[7] != row[8] {
+            continue; // parts of a locality share their parent's streets
+        }
+        let unit = |key: String| units.get(&key).cloned().unwrap_or_default();
+        out.push_str(&format!(
+            "C\t{}\t{}\t{}\t{}\t{}\n",
+            row[7],
+            row[6],
+            unit(row[0].clone()).to_lowercase(),
+            unit(format!("{}{}", row[0], row[1])),
+            unit(format!("{}{}{}{}", row[0], row[1], row[2], row[3])),
+        ));
+    }
+
+    for row in &ulic {
+        // ULIC: WOJ;POW;GMI;RODZ_GMI;SYM;SYM_UL;CECHA;NAZWA_1;NAZWA_2;STAN_NA
+        if row.len() < 9 {
+            continue;
+        }
+        let name = if row[8].is_empty() {
+            row[7].clone()
+        } else {
+            format!("{} {}", row[8], row[7])
+        };
+        out.push_str(&format!(
+            "S\t{}\t{}\t{}\t{}\n",
+            row[4], row[5], row[6], name
+        ));
+    }
+    Ok(out)
+}
+
+/// The CSV in `dir` whose name starts with `prefix`, e.g. "SIMC_Urzedowy_2026-01-01.csv".
+fn register(dir: &Path, prefix: &str) -> Result<PathBuf, String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .find(|path| {
+            let name = path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_uppercase();
+            name.starts_with(prefix) && name.ends_with(".CSV")
+        })
+        .ok_or_else(|| format!("no {} CSV in {}", prefix, dir.display()))
+}
+
+/// Voivodeship, district and commune names keyed by their concatenated TERC codes.
+fn unit_names(terc: &[Vec<String>]) -> HashMap<String, String> {
+    // TERC: WOJ;POW;GMI;RODZ;NAZWA;NAZWA_DOD;STAN_NA
+    terc.iter()
+        .filter(|row| row.len() >= 5)
+        .map(|row| {
+            (
+                format!("{}{}{}{}", row[0], row[1], row[2], row[3]),
+                row[4].clone(),
+            )
+        })
+        .collect()
+}
+
+fn read_csv(path: &Path) -> Result<Vec<Vec<String>>, String> {
+    println!("cargo:rerun-if-changed={}", path.display());
+    let data = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(data
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .skip(1)
+        .map(|line| line.split(';').map(|f| f.trim().to_string()).collect())
+        .collect())
+}
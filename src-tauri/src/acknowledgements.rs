@@ -0,0 +1,136 @@
+use crate::settings_store::write_atomic;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Stored next to `settings.json`.
+const ACKNOWLEDGEMENTS_FILE: &str = "acknowledgements.json";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Acknowledgement {
+    /// `UnifiedAlert.id` of the acknowledged alert.
+    pub alert_id: String,
+    pub acknowledged_at: String,
+}
+
+pub fn acknowledgements_path(settings_path: &Path) -> PathBuf {
+    settings_path.with_file_name(ACKNOWLEDGEMENTS_FILE)
+}
+
+pub fn load(path: &Path) -> Result<Vec<Acknowledgement>, String> {
+    match fs::read_to_string(path) {
+        Ok(data) if data.trim().is_empty() => Ok(Vec::new()),
+        Ok(data) => {
+            serde_json::from_str(&data).map_err(|e| format!("Acknowledgements parse error: {}", e))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+pub fn save(path: &Path, acknowledgements: &[Acknowledgement]) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(acknowledgements).map_err(|e| e.to_string())?;
+    write_atomic(path, json.as_bytes())
+}
+
+/// Mark `alert_id` as acknowledged now; acknowledging twice keeps the first time.
+pub fn acknowledge(path: &Path, alert_id: &str) -> Result<Vec<Acknowledgement>, String> {
+    let mut acknowledgements = load(path)?;
+    if !acknowledgements.iter().any(|a| a.alert_id == alert_id) {
+        acknowledgements.push(Acknowledgement {
+            alert_id: alert_id.to_string(),
+            acknowledged_at: Utc::now().to_rfc3339(),
+        });
+        save(path, &acknowledgements)?;
+    }
+    Ok(acknowledgements)
+}
+
+/// Whether `a` was acknowledged before `b`. Bundles may carry any UTC offset, so the
+/// instants are compared; a time that doesn't parse never wins.
+fn earlier(a: &str, b: &str) -> bool {
+    match (
+        DateTime::parse_from_rfc3339(a),
+        DateTime::parse_from_rfc3339(b),
+    ) {
+        (Ok(a), Ok(b)) => a < b,
+        (Ok(_), Err(_)) => true,
+        (Err(_), _) => false,
+    }
+}
+
+/// Union of both lists by alert id, keeping the earlier acknowledgement time.
+pub fn merge(current: &[Acknowledgement], incoming: &[Acknowledgement]) -> Vec<Acknowledgement> {
+    let mut merged = current.to_vec();
+    for ack in incoming {
+        match merged.iter_mut().find(|a| a.alert_id == ack.alert_id) {
+            Some(existing) if earlier(&ack.acknowledged_at, &existing.acknowledged_at) => {
+                existing.acknowledged_at = ack.acknowledged_at.clone();
+            }
+            Some(_) => {}
+            None => merged.push(ack.clone()),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    fn ack(id: &str, at: &str) -> Acknowledgement {
+        Acknowledgement {
+            alert_id: id.to_string(),
+            acknowledged_at: at.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_acknowledge_is_idempotent() {
+        let dir = temp_dir("acks");
+        let path = acknowledgements_path(&dir.join("settings.json"));
+
+        assert!(load(&path).unwrap().is_empty());
+        let first = acknowledge(&path, "tauron-1").unwrap();
+        let second = acknowledge(&path, "tauron-1").unwrap();
+        assert_eq!(first, second);
+        assert_eq!(load(&path).unwrap().len(), 1);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_merge_keeps_earliest() {
+        let current = vec![
+            ack("a", "2026-03-02T10:00:00+00:00"),
+            ack("b", "2026-03-01T10:00:00+00:00"),
+        ];
+        let incoming = vec![
+            ack("a", "2026-03-01T08:00:00+00:00"),
+            ack("c", "2026-03-03T10:00:00+00:00"),
+        ];
+        let merged = merge(&current, &incoming);
+        assert_eq!(
+            merged,
+            vec![
+                ack("a", "2026-03-01T08:00:00+00:00"),
+                ack("b", "2026-03-01T10:00:00+00:00"),
+                ack("c", "2026-03-03T10:00:00+00:00"),
+            ]
+        );
+
+        // 09:00+02:00 is 07:00 UTC, earlier though it sorts later as text; 09:30-02:00
+        // is 11:30 UTC, later though it sorts earlier
+        let incoming = vec![
+            ack("a", "2026-03-01T09:00:00+02:00"),
+            ack("b", "2026-03-01T09:30:00-02:00"),
+        ];
+        let merged = merge(&merged, &incoming);
+        assert_eq!(merged[0], ack("a", "2026-03-01T09:00:00+02:00"));
+        assert_eq!(merged[1], ack("b", "2026-03-01T10:00:00+00:00"));
+        assert_eq!(merge(&merged, &[ack("a", "yesterday")])[0], merged[0]);
+    }
+}
@@ -0,0 +1,338 @@
+use crate::api_logic::GeoItem;
+use crate::geo_search::{self, match_score, street_match_score, STREET_PREFIXES};
+use serde::{Deserialize, Serialize};
+
+/// Candidates below this confidence are not worth offering.
+const MIN_CONFIDENCE: f64 = 0.3;
+
+/// Markers separating the building number from the flat number ("25 m. 4", "25 lok. 4").
+const FLAT_MARKERS: &[&str] = &["m.", "m", "lok.", "lok", "mieszk."];
+
+/// A free-text address split into the parts the geo lookups need.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ParsedAddress {
+    pub street: String,
+    pub house_no: String,
+    pub flat: Option<String>,
+    pub city: String,
+    pub postal_code: Option<String>,
+}
+
+/// A resolved location, shaped like the location fields of `Settings`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[allow(non_snake_case)]
+pub struct AddressCandidate {
+    pub cityName: String,
+    pub streetName: String,
+    pub houseNo: String,
+    pub cityGAID: u64,
+    pub streetGAID: u64,
+    pub confidence: f64,
+}
+
+fn is_postal_code(token: &str) -> bool {
+    let bytes = token.as_bytes();
+    bytes.len() == 6
+        && bytes[2] == b'-'
+        && bytes
+            .iter()
+            .enumerate()
+            .all(|(i, b)| i == 2 || b.is_ascii_digit())
+}
+
+fn is_house_number(token: &str) -> bool {
+    token.chars().next().is_some_and(|c| c.is_ascii_digit())
+}
+
+fn is_flat_marker(token: &str) -> bool {
+    FLAT_MARKERS.iter().any(|m| token.eq_ignore_ascii_case(m))
+}
+
+fn is_street_prefix(token: &str) -> bool {
+    STREET_PREFIXES
+        .iter()
+        .any(|p| token.eq_ignore_ascii_case(p))
+}
+
+fn strip_street_prefix(street: &str) -> String {
+    let street = street.split_whitespace().collect::<Vec<_>>().join(" ");
+    geo_search::strip_street_prefix(&street).to_string()
+}
+
+/// Split "25/3", "25 m. 4" style numbers into building and flat.
+fn split_house_number(tokens: &[&str]) -> (String, Option<String>) {
+    let mut house = tokens[0].to_string();
+    let mut flat = None;
+
+    if let Some((building, rest)) = house.split_once('/') {
+        flat = Some(rest.to_string()).filter(|f| !f.is_empty());
+        house = building.to_string();
+    }
+
+    // "25 A" -> "25A"
+    let mut rest = &tokens[1..];
+    if let Some(letter) = rest.first() {
+        if letter.len() == 1
+            && letter.chars().all(|c| c.is_ascii_alphabetic())
+            && !is_flat_marker(letter)
+        {
+            house.push_str(&letter.to_uppercase());
+            rest = &rest[1..];
+        }
+    }
+
+    if let [marker, number, ..] = rest {
+        if is_flat_marker(marker) {
+            flat = Some(number.to_string());
+        }
+    } else if let [marker] = rest {
+        // "m.4" written without a space
+        for m in FLAT_MARKERS {
+            if let Some(number) = marker.strip_prefix(m).filter(|n| is_house_number(n)) {
+                flat = Some(number.to_string());
+                break;
+            }
+        }
+    }
+
+    (house.to_uppercase(), flat)
+}
+
+/// Normalize a house number as typed into the building number Tauron expects and
+/// the flat number, if any: "25 a" -> ("25A", None), "25/3" and "25 m. 3" -> ("25", Some("3")).
+/// Returns `None` when it doesn't start with a digit.
+pub fn normalize_house_no(input: &str) -> Option<(String, Option<String>)> {
+    let tokens: Vec<&str> = input.split_whitespace().collect();
+    if !tokens.first().is_some_and(|t| is_house_number(t)) {
+        return None;
+    }
+    Some(split_house_number(&tokens))
+}
+
+/// Whether an alert text mentions the street, the way the alert list picks "your
+/// location" alerts: the name as given, or any word of three or more letters of it
+/// (without the "ul." style prefix) as a whole word.
+pub fn mentions_street(message: &str, street_name: &str) -> bool {
+    let street = strip_street_prefix(street_name);
+    if street.is_empty() {
+        return false;
+    }
+    if message.contains(street_name.trim()) {
+        return true;
+    }
+    street
+        .split_whitespace()
+        .filter(|word| word.chars().count() >= 3)
+        .any(|word| contains_word(message, word))
+}
+
+fn contains_word(text: &str, word: &str) -> bool {
+    text.match_indices(word).any(|(at, _)| {
+        let before = text[..at].chars().next_back();
+        let after = text[at + word.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    })
+}
+
+/// Parse addresses such as "Kuźnicza 25, Wrocław", "ul. Kuźnicza 25/3, 50-138 Wrocław"
+/// or "Wrocław, al. Jana Pawła II 7 m. 4".
+pub fn parse_address(input: &str) -> Result<ParsedAddress, String> {
+    let mut parsed = ParsedAddress::default();
+    let mut street_part: Option<Vec<&str>> = None;
+    let mut city_parts: Vec<String> = Vec::new();
+
+    for segment in input.split([',', ';', '\n']) {
+        let mut tokens: Vec<&str> = segment.split_whitespace().collect();
+        if let Some(pos) = tokens.iter().position(|t| is_postal_code(t)) {
+            parsed.postal_code = Some(tokens.remove(pos).to_string());
+        }
+        if tokens.is_empty() {
+            continue;
+        }
+
+        if street_part.is_none() {
+            // The street takes at least one word after its prefix: "ul. 3 Maja 5"
+            let first = tokens.iter().take_while(|t| is_street_prefix(t)).count() + 1;
+            if let Some(pos) = tokens.iter().skip(first).position(|t| is_house_number(t)) {
+                let pos = pos + first;
+                let (house, flat) = split_house_number(&tokens[pos..]);
+                parsed.house_no = house;
+                parsed.flat = flat;
+
+                // Whatever follows the number in the same segment ("Kuźnicza 25 Wrocław")
+                let trailing: Vec<&str> = tokens[pos + 1..]
+                    .iter()
+                    .copied()
+                    .skip_while(|t| t.len() == 1 || is_flat_marker(t) || is_house_number(t))
+                    .collect();
+                if !trailing.is_empty() {
+                    city_parts.push(trailing.join(" "));
+                }
+                street_part = Some(tokens[..pos].to_vec());
+                continue;
+            }
+        }
+        city_parts.push(tokens.join(" "));
+    }
+
+    let street_tokens =
+        street_part.ok_or_else(|| "No house number found in address".to_string())?;
+    parsed.street = strip_street_prefix(&street_tokens.join(" "));
+    parsed.city = city_parts
+        .into_iter()
+        .next()
+        .ok_or_else(|| "No city found in address".to_string())?;
+
+    if parsed.street.is_empty() {
+        return Err("No street found in address".to_string());
+    }
+    Ok(parsed)
+}
+
+/// Combine city and street lookup results into candidates, best first.
+/// `lookups` pairs each candidate city with the streets found in it.
+pub fn build_candidates(
+    parsed: &ParsedAddress,
+    lookups: Vec<(GeoItem, Vec<GeoItem>)>,
+) -> Vec<AddressCandidate> {
+    let mut candidates: Vec<AddressCandidate> = lookups
+        .into_iter()
+        .flat_map(|(city, streets)| {
+            let city_score = match_score(&city.Name, &parsed.city);
+            streets.into_iter().map(move |street| {
+                let street_score = street_match_score(&street.Name, &parsed.street);
+                AddressCandidate {
+                    cityName: city.Name.clone(),
+                    streetName: street.Name,
+                    houseNo: parsed.house_no.clone(),
+                    cityGAID: city.GAID,
+                    streetGAID: street.GAID,
+                    confidence: (city_score * street_score * 100.0).round() / 100.0,
+                }
+            })
+        })
+        .filter(|c| c.confidence >= MIN_CONFIDENCE)
+        .collect();
+
+    candidates.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_simple_address() {
+        let parsed = parse_address("Kuźnicza 25, Wrocław").unwrap();
+        assert_eq!(parsed.street, "Kuźnicza");
+        assert_eq!(parsed.house_no, "25");
+        assert_eq!(parsed.flat, None);
+        assert_eq!(parsed.city, "Wrocław");
+    }
+
+    #[test]
+    fn test_parse_prefix_postal_code_and_flat() {
+        let parsed = parse_address("ul. Kuźnicza 25/3, 50-138 Wrocław").unwrap();
+        assert_eq!(parsed.street, "Kuźnicza");
+        assert_eq!(parsed.house_no, "25");
+        assert_eq!(parsed.flat.as_deref(), Some("3"));
+        assert_eq!(parsed.city, "Wrocław");
+        assert_eq!(parsed.postal_code.as_deref(), Some("50-138"));
+    }
+
+    #[test]
+    fn test_parse_city_first_with_flat_suffix() {
+        let parsed = parse_address("Wrocław, al. Jana Pawła II 7a m. 4").unwrap();
+        assert_eq!(parsed.street, "Jana Pawła II");
+        assert_eq!(parsed.house_no, "7A");
+        assert_eq!(parsed.flat.as_deref(), Some("4"));
+        assert_eq!(parsed.city, "Wrocław");
+
+        let parsed = parse_address("Opole, Ozimska 12 m.8").unwrap();
+        assert_eq!(parsed.house_no, "12");
+        assert_eq!(parsed.flat.as_deref(), Some("8"));
+    }
+
+    #[test]
+    fn test_parse_without_commas() {
+        let parsed = parse_address("ulica Legnicka 5 B Wrocław").unwrap();
+        assert_eq!(parsed.street, "Legnicka");
+        assert_eq!(parsed.house_no, "5B");
+        assert_eq!(parsed.city, "Wrocław");
+    }
+
+    #[test]
+    fn test_parse_street_starting_with_number() {
+        let parsed = parse_address("ul. 3 Maja 5, Kraków").unwrap();
+        assert_eq!(parsed.street, "3 Maja");
+        assert_eq!(parsed.house_no, "5");
+        assert_eq!(parsed.city, "Kraków");
+
+        let parsed = parse_address("3 Maja 5/2 Kraków").unwrap();
+        assert_eq!(parsed.street, "3 Maja");
+        assert_eq!(parsed.house_no, "5");
+        assert_eq!(parsed.flat.as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn test_build_candidates_orders_by_confidence() {
+        let parsed = parse_address("Kuznicza 25, Wroclaw").unwrap();
+        let geo = |gaid, name: &str| GeoItem {
+            GAID: gaid,
+            Name: name.to_string(),
+        };
+        let candidates = build_candidates(
+            &parsed,
+            vec![
+                (geo(2, "Wrocławice"), vec![geo(20, "Kuźnicza")]),
+                (
+                    geo(1, "Wrocław"),
+                    vec![geo(11, "ul. Nowa Kuźnicza"), geo(10, "ul. Kuźnicza")],
+                ),
+            ],
+        );
+        assert_eq!(candidates[0].cityGAID, 1);
+        assert_eq!(candidates[0].streetGAID, 10);
+        assert_eq!(candidates[0].houseNo, "25");
+        assert_eq!(candidates[0].confidence, 0.9);
+        assert_eq!(candidates[1].cityGAID, 2);
+        assert_eq!(candidates.len(), 3);
+    }
+
+    #[test]
+    fn test_normalize_house_no() {
+        assert_eq!(normalize_house_no("25"), Some(("25".to_string(), None)));
+        assert_eq!(normalize_house_no(" 25a "), Some(("25A".to_string(), None)));
+        assert_eq!(normalize_house_no("25 A"), Some(("25A".to_string(), None)));
+        assert_eq!(
+            normalize_house_no("25/3"),
+            Some(("25".to_string(), Some("3".to_string())))
+        );
+        assert_eq!(
+            normalize_house_no("25 m. 4"),
+            Some(("25".to_string(), Some("4".to_string())))
+        );
+        assert_eq!(normalize_house_no("m. 4"), None);
+        assert_eq!(normalize_house_no(""), None);
+    }
+
+    #[test]
+    fn test_mentions_street() {
+        assert!(mentions_street("Kuźnicza 1-10, Ruska 5", "Kuźnicza"));
+        assert!(mentions_street("Wyłączenie: Kuźnicza 25", "ul. Kuźnicza"));
+        assert!(mentions_street("Jana Pawła II 7-9", "al. Jana Pawła II"));
+        // Whole words only, Polish letters included
+        assert!(!mentions_street("Kuźniczaka 3", "ul. Kuźnicza"));
+        assert!(!mentions_street("Ruskaś 3", "ul. Ruska"));
+        assert!(!mentions_street("Ruska 5", "ul."));
+        assert!(!mentions_street("Ruska 5", ""));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse_address("Kuźnicza, Wrocław").is_err());
+        assert!(parse_address("Kuźnicza 25").is_err());
+    }
+}
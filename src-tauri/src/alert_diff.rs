@@ -0,0 +1,346 @@
+//! What changed between two fetches of the same source, and which changes the user
+//! hasn't looked at yet (the "new" badges).
+//!
+//! Fortum alerts have a real id, so changes to them are found by id. Tauron and MPWiK
+//! ids are derived from the alert's contents (`api_logic::alert_id`), so a moved or
+//! reworded alert gets a new id; those are paired up by identical text (time window
+//! changed) or identical window (text changed). An alert that disappears before its
+//! start was cancelled rather than over, see `mark_cancelled`, and one still listed
+//! after its end is running late, see `overrunning`.
+
+use crate::api_logic::UnifiedAlert;
+use chrono::{Duration, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    TimeChanged,
+    TextChanged,
+    Removed,
+    /// Removed before it started.
+    Cancelled,
+    /// Still listed after its end.
+    Overrunning,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertChange {
+    pub kind: ChangeKind,
+    /// The alert as it is now; for `Removed` and `Cancelled`, as it was last seen.
+    pub alert: UnifiedAlert,
+    /// The earlier version, for `TimeChanged` and `TextChanged`.
+    pub previous: Option<UnifiedAlert>,
+}
+
+impl AlertChange {
+    fn new(kind: ChangeKind, alert: &UnifiedAlert, previous: Option<&UnifiedAlert>) -> Self {
+        AlertChange {
+            kind,
+            alert: alert.clone(),
+            previous: previous.cloned(),
+        }
+    }
+}
+
+fn same_window(a: &UnifiedAlert, b: &UnifiedAlert) -> bool {
+    a.startDate == b.startDate && a.endDate == b.endDate
+}
+
+fn same_text(a: &UnifiedAlert, b: &UnifiedAlert) -> bool {
+    a.message == b.message && a.description == b.description
+}
+
+/// Compare two versions of the same alert, `None` if nothing relevant changed.
+fn compare(previous: &UnifiedAlert, current: &UnifiedAlert) -> Option<AlertChange> {
+    if !same_window(previous, current) {
+        Some(AlertChange::new(
+            ChangeKind::TimeChanged,
+            current,
+            Some(previous),
+        ))
+    } else if !same_text(previous, current) {
+        Some(AlertChange::new(
+            ChangeKind::TextChanged,
+            current,
+            Some(previous),
+        ))
+    } else {
+        None
+    }
+}
+
+/// Changes from `previous` to `current`, in the order of `current` with removals last.
+pub fn diff(previous: &[UnifiedAlert], current: &[UnifiedAlert]) -> Vec<AlertChange> {
+    let mut changes = Vec::new();
+    let mut unmatched_prev: Vec<&UnifiedAlert> = previous
+        .iter()
+        .filter(|p| !current.iter().any(|c| c.id == p.id))
+        .collect();
+
+    for alert in current {
+        if let Some(prev) = previous.iter().find(|p| p.id == alert.id) {
+            changes.extend(compare(prev, alert));
+            continue;
+        }
+        let text_match = unmatched_prev.iter().position(|p| {
+            p.source == alert.source
+                && same_text(p, alert)
+                && (p.message.is_some() || p.description.is_some())
+        });
+        let pair = text_match.or_else(|| {
+            unmatched_prev.iter().position(|p| {
+                p.source == alert.source && same_window(p, alert) && p.startDate.is_some()
+            })
+        });
+        match pair {
+            Some(idx) => {
+                let prev = unmatched_prev.remove(idx);
+                changes.extend(compare(prev, alert));
+            }
+            None => changes.push(AlertChange::new(ChangeKind::Added, alert, None)),
+        }
+    }
+
+    changes.extend(
+        unmatched_prev
+            .into_iter()
+            .map(|p| AlertChange::new(ChangeKind::Removed, p, None)),
+    );
+    changes
+}
+
+/// Turn removals of alerts that hadn't started by `now` into cancellations.
+pub fn mark_cancelled(changes: &mut [AlertChange], now: NaiveDateTime) {
+    for change in changes.iter_mut().filter(|c| c.kind == ChangeKind::Removed) {
+        if change.alert.start_time().is_some_and(|start| start > now) {
+            change.kind = ChangeKind::Cancelled;
+        }
+    }
+}
+
+/// When `alert` should be over: its end, or `open_ended_after` past the start for an
+/// alert without an end. `None` when its dates can't be read.
+pub fn due_end(alert: &UnifiedAlert, open_ended_after: Duration) -> Option<NaiveDateTime> {
+    match alert.endDate {
+        Some(_) => alert.end_time(),
+        None => alert.start_time().map(|start| start + open_ended_after),
+    }
+}
+
+/// Alerts in `current` past their `due_end` at `now` that aren't in `flagged` yet.
+pub fn overrunning(
+    current: &[UnifiedAlert],
+    flagged: &[String],
+    now: NaiveDateTime,
+    open_ended_after: Duration,
+) -> Vec<AlertChange> {
+    current
+        .iter()
+        .filter(|a| !flagged.contains(&a.id))
+        .filter(|a| due_end(a, open_ended_after).is_some_and(|end| end <= now))
+        .map(|a| AlertChange::new(ChangeKind::Overrunning, a, None))
+        .collect()
+}
+
+/// Fold `changes` into the changes the user hasn't seen yet, one entry per alert:
+/// an alert added and changed again is still "added", and one added and removed
+/// before anybody looked disappears without a trace.
+pub fn accumulate(unseen: &mut Vec<AlertChange>, changes: &[AlertChange]) {
+    for change in changes {
+        let key = change.previous.as_ref().unwrap_or(&change.alert).id.clone();
+        let existing = unseen.iter().position(|u| u.alert.id == key);
+        let earlier = existing.map(|idx| unseen.remove(idx));
+
+        let merged = match (earlier, change.kind) {
+            (Some(earlier), ChangeKind::Removed | ChangeKind::Cancelled)
+                if earlier.kind == ChangeKind::Added =>
+            {
+                None
+            }
+            (Some(earlier), _) if earlier.kind == ChangeKind::Added => {
+                Some(AlertChange::new(ChangeKind::Added, &change.alert, None))
+            }
+            // Keep the version the user last saw as `previous`
+            (Some(earlier), ChangeKind::TimeChanged | ChangeKind::TextChanged) => {
+                Some(AlertChange {
+                    previous: earlier.previous.or(change.previous.clone()),
+                    ..change.clone()
+                })
+            }
+            _ => Some(change.clone()),
+        };
+        unseen.extend(merged);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_logic::{alert_id, parse_alert_time, AlertSource};
+    use std::slice;
+
+    fn tauron(start: &str, message: &str) -> UnifiedAlert {
+        let end = "2026-03-12T16:00:00";
+        UnifiedAlert {
+            id: alert_id(
+                &AlertSource::Tauron,
+                &[Some(start), Some(end), Some(message)],
+            ),
+            source: AlertSource::Tauron,
+            startDate: Some(start.to_string()),
+            endDate: Some(end.to_string()),
+            message: Some(message.to_string()),
+            description: None,
+        }
+    }
+
+    fn fortum(id: &str, start: &str, message: &str) -> UnifiedAlert {
+        UnifiedAlert {
+            id: format!("fortum-{}", id),
+            source: AlertSource::Fortum,
+            startDate: Some(start.to_string()),
+            endDate: None,
+            message: Some(message.to_string()),
+            description: None,
+        }
+    }
+
+    fn kinds(changes: &[AlertChange]) -> Vec<ChangeKind> {
+        changes.iter().map(|c| c.kind).collect()
+    }
+
+    #[test]
+    fn test_diff_added_and_removed() {
+        let a = tauron("2026-03-12T08:00:00", "Kuźnicza 1-10");
+        let b = tauron("2026-03-13T08:00:00", "Ruska 5");
+        let c = tauron("2026-03-14T09:00:00", "Legnicka 2");
+        assert!(diff(&[a.clone(), b.clone()], &[a.clone(), b.clone()]).is_empty());
+
+        let changes = diff(&[a.clone(), b.clone()], &[a.clone(), c.clone()]);
+        assert_eq!(
+            kinds(&changes),
+            vec![ChangeKind::Added, ChangeKind::Removed]
+        );
+        assert_eq!(changes[0].alert, c);
+        assert_eq!(changes[1].alert, b);
+    }
+
+    #[test]
+    fn test_diff_pairs_changed_hash_ids() {
+        let before = tauron("2026-03-12T08:00:00", "Kuźnicza 1-10");
+        let moved = tauron("2026-03-12T10:00:00", "Kuźnicza 1-10");
+        let changes = diff(slice::from_ref(&before), slice::from_ref(&moved));
+        assert_eq!(kinds(&changes), vec![ChangeKind::TimeChanged]);
+        assert_eq!(changes[0].previous.as_ref(), Some(&before));
+
+        let reworded = tauron("2026-03-12T08:00:00", "Kuźnicza 1-12");
+        let changes = diff(slice::from_ref(&before), &[reworded]);
+        assert_eq!(kinds(&changes), vec![ChangeKind::TextChanged]);
+    }
+
+    #[test]
+    fn test_diff_by_real_id() {
+        let before = fortum("7", "2026-03-12T08:00:00", "Sieć energetyczna");
+        let after = fortum("7", "2026-03-12T08:00:00", "Sieć energetyczna, ul. Ruska");
+        assert_eq!(
+            kinds(&diff(slice::from_ref(&before), &[after])),
+            vec![ChangeKind::TextChanged]
+        );
+
+        let moved = fortum("7", "2026-03-13T08:00:00", "Inny tekst");
+        assert_eq!(
+            kinds(&diff(&[before], &[moved])),
+            vec![ChangeKind::TimeChanged]
+        );
+    }
+
+    #[test]
+    fn test_accumulate_unseen() {
+        let a = tauron("2026-03-12T08:00:00", "Kuźnicza 1-10");
+        let a_moved = tauron("2026-03-12T10:00:00", "Kuźnicza 1-10");
+        let b = tauron("2026-03-13T08:00:00", "Ruska 5");
+
+        let mut unseen = Vec::new();
+        accumulate(&mut unseen, &diff(&[], slice::from_ref(&a)));
+        // Moved before anyone looked: still just new
+        accumulate(
+            &mut unseen,
+            &diff(slice::from_ref(&a), slice::from_ref(&a_moved)),
+        );
+        assert_eq!(kinds(&unseen), vec![ChangeKind::Added]);
+        assert_eq!(unseen[0].alert, a_moved);
+
+        // Gone before anyone looked: nothing to show
+        accumulate(&mut unseen, &diff(slice::from_ref(&a_moved), &[]));
+        assert!(unseen.is_empty());
+
+        // A seen alert changing twice keeps the version the user saw
+        let b_moved = tauron("2026-03-13T09:00:00", "Ruska 5");
+        let b_moved_again = tauron("2026-03-13T10:00:00", "Ruska 5");
+        accumulate(
+            &mut unseen,
+            &diff(slice::from_ref(&b), slice::from_ref(&b_moved)),
+        );
+        accumulate(
+            &mut unseen,
+            &diff(&[b_moved], slice::from_ref(&b_moved_again)),
+        );
+        assert_eq!(kinds(&unseen), vec![ChangeKind::TimeChanged]);
+        assert_eq!(unseen[0].alert, b_moved_again);
+        assert_eq!(unseen[0].previous.as_ref(), Some(&b));
+    }
+
+    #[test]
+    fn test_mark_cancelled() {
+        let a = tauron("2026-03-12T08:00:00", "Kuźnicza 1-10");
+        let b = tauron("2026-03-11T08:00:00", "Ruska 5");
+        let mut changes = diff(&[a.clone(), b], &[]);
+        mark_cancelled(
+            &mut changes,
+            parse_alert_time("2026-03-11T12:00:00").unwrap(),
+        );
+        assert_eq!(
+            kinds(&changes),
+            vec![ChangeKind::Cancelled, ChangeKind::Removed]
+        );
+
+        // Cancelled before anyone looked: nothing to show
+        let mut unseen = Vec::new();
+        accumulate(&mut unseen, &diff(&[], slice::from_ref(&a)));
+        accumulate(&mut unseen, &changes[..1]);
+        assert!(unseen.is_empty());
+    }
+
+    #[test]
+    fn test_overrunning() {
+        let planned = tauron("2026-03-12T08:00:00", "Kuźnicza 1-10");
+        let failure = fortum("7", "2026-03-12T09:00:00", "Awaria sieci");
+        let current = [planned.clone(), failure.clone()];
+        let hours = |h| parse_alert_time(&format!("2026-03-12T{:02}:00:00", h)).unwrap();
+        let open_ended = Duration::hours(8);
+
+        assert!(overrunning(&current, &[], hours(14), open_ended).is_empty());
+        let late = overrunning(&current, &[], hours(16), open_ended);
+        assert_eq!(kinds(&late), vec![ChangeKind::Overrunning]);
+        assert_eq!(late[0].alert, planned);
+
+        // No end: flagged once it has run for `open_ended_after`
+        let late = overrunning(
+            &current,
+            slice::from_ref(&planned.id),
+            hours(17),
+            open_ended,
+        );
+        assert_eq!(late[0].alert, failure);
+        assert!(overrunning(
+            &current,
+            &[planned.id.clone(), failure.id.clone()],
+            hours(20),
+            open_ended
+        )
+        .is_empty());
+    }
+}
@@ -0,0 +1,435 @@
+//! The local API's `/ws` WebSocket. A client gets a `snapshot` of the alerts on
+//! connect, then a `change` for every alert added, rescheduled, reworded, removed or
+//! cancelled and a `status` when one starts, ends or is over. Both take the `/alerts`
+//! filters as query parameters, and a client can swap them later by sending
+//! `{"type": "subscribe", ...}` with the same fields, answered by a new snapshot.
+
+use crate::alert_diff::{AlertChange, ChangeKind};
+use crate::api_logic::{Settings, UnifiedAlert};
+use crate::local_api::{
+    alert_status, alerts, api_alert, error, generated_at, AlertFilter, AlertQuery, AlertStatus,
+    ApiAlert, ApiState,
+};
+use crate::scheduler::AlertSnapshot;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::Response;
+use chrono::{Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamMessage {
+    /// The alerts matching the client's filter, as `/alerts` lists them.
+    #[serde(rename_all = "camelCase")]
+    Snapshot {
+        generated_at: String,
+        alerts: Vec<ApiAlert>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Change {
+        kind: ChangeKind,
+        alert: ApiAlert,
+        /// The earlier version, for `time_changed` and `text_changed`.
+        previous: Option<UnifiedAlert>,
+    },
+    /// An alert started or ended; cancelling and overrunning come as a `change`.
+    #[serde(rename_all = "camelCase")]
+    Status {
+        alert: ApiAlert,
+        previous_status: AlertStatus,
+    },
+    Error {
+        error: String,
+    },
+}
+
+impl StreamMessage {
+    fn matches(&self, filter: &AlertFilter) -> bool {
+        match self {
+            StreamMessage::Change { alert, .. } | StreamMessage::Status { alert, .. } => {
+                filter.matches(alert)
+            }
+            StreamMessage::Snapshot { .. } | StreamMessage::Error { .. } => true,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Subscribe(AlertQuery),
+}
+
+/// `changes` from the latest poll as the clients get them.
+pub fn change_messages(
+    settings: &Settings,
+    snapshot: &AlertSnapshot,
+    changes: &[AlertChange],
+    now: NaiveDateTime,
+) -> Vec<StreamMessage> {
+    let locations = settings.identified_locations();
+    changes
+        .iter()
+        .map(|change| {
+            let status = match change.kind {
+                ChangeKind::Cancelled => AlertStatus::Cancelled,
+                ChangeKind::Removed => AlertStatus::Over,
+                _ => alert_status(&change.alert, snapshot, now),
+            };
+            StreamMessage::Change {
+                kind: change.kind,
+                alert: api_alert(&change.alert, status, &locations, snapshot),
+                previous: change.previous.clone(),
+            }
+        })
+        .collect()
+}
+
+/// Compare `current` with the `known` statuses and remember it. Alerts seen for the
+/// first time, and ones turning cancelled or overrunning, have a change of their own.
+pub fn status_transitions(
+    known: &mut BTreeMap<String, AlertStatus>,
+    current: &[ApiAlert],
+) -> Vec<StreamMessage> {
+    let messages = current
+        .iter()
+        .filter_map(|alert| {
+            let previous = *known.get(&alert.alert.id)?;
+            let announced = matches!(
+                alert.status,
+                AlertStatus::Cancelled | AlertStatus::Overrunning
+            );
+            (previous != alert.status && !announced).then(|| StreamMessage::Status {
+                alert: alert.clone(),
+                previous_status: previous,
+            })
+        })
+        .collect();
+    *known = current
+        .iter()
+        .map(|a| (a.alert.id.clone(), a.status))
+        .collect();
+    messages
+}
+
+fn snapshot(state: &ApiState, filter: &AlertFilter) -> StreamMessage {
+    let (settings, snapshot) = (state.current)();
+    StreamMessage::Snapshot {
+        generated_at: generated_at(),
+        alerts: alerts(&settings, &snapshot, Local::now().naive_local())
+            .into_iter()
+            .filter(|a| filter.matches(a))
+            .collect(),
+    }
+}
+
+pub async fn handler(
+    ws: WebSocketUpgrade,
+    State(state): State<ApiState>,
+    Query(query): Query<AlertQuery>,
+) -> Response {
+    let (settings, _) = (state.current)();
+    let filter = match AlertFilter::parse(&query, &settings) {
+        Ok(filter) => filter,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e),
+    };
+    // Subscribe before the snapshot so nothing falls between the two
+    let events = state.events.subscribe();
+    ws.on_upgrade(move |socket| stream(socket, state, filter, events))
+}
+
+async fn send(socket: &mut WebSocket, message: &StreamMessage) -> Result<(), String> {
+    let text = serde_json::to_string(message).map_err(|e| e.to_string())?;
+    socket
+        .send(Message::Text(text.into()))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+fn subscribe(state: &ApiState, filter: &mut AlertFilter, text: &str) -> StreamMessage {
+    let query = match serde_json::from_str::<ClientMessage>(text) {
+        Ok(ClientMessage::Subscribe(query)) => query,
+        Err(e) => {
+            return StreamMessage::Error {
+                error: format!("Invalid message: {}", e),
+            }
+        }
+    };
+    let (settings, _) = (state.current)();
+    match AlertFilter::parse(&query, &settings) {
+        Ok(parsed) => {
+            *filter = parsed;
+            snapshot(state, filter)
+        }
+        Err(error) => StreamMessage::Error { error },
+    }
+}
+
+async fn stream(
+    mut socket: WebSocket,
+    state: ApiState,
+    mut filter: AlertFilter,
+    mut events: broadcast::Receiver<StreamMessage>,
+) {
+    if send(&mut socket, &snapshot(&state, &filter)).await.is_err() {
+        return;
+    }
+    loop {
+        let reply = tokio::select! {
+            event = events.recv() => match event {
+                Ok(message) if message.matches(&filter) => message,
+                Ok(_) => continue,
+                // Missed some, start over
+                Err(RecvError::Lagged(_)) => snapshot(&state, &filter),
+                // The server is stopping
+                Err(RecvError::Closed) => {
+                    socket.send(Message::Close(None)).await.ok();
+                    return;
+                }
+            },
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Text(text))) => subscribe(&state, &mut filter, text.as_str()),
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                // Pings are answered by axum
+                Some(Ok(_)) => continue,
+            },
+        };
+        if let Err(e) = send(&mut socket, &reply).await {
+            log::debug!("WebSocket client gone: {}", e);
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_logic::{parse_alert_time, AlertSource};
+    use crate::local_api::serve;
+    use crate::test_support::{alert, settings};
+    use futures_util::{SinkExt, StreamExt};
+    use std::sync::Arc;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_tungstenite::tungstenite;
+    use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+    type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    fn listing(alerts: Vec<UnifiedAlert>) -> AlertSnapshot {
+        AlertSnapshot {
+            alerts,
+            sources: Vec::new(),
+            rescheduled: Vec::new(),
+            cancelled: Vec::new(),
+            overrunning: Vec::new(),
+        }
+    }
+
+    fn listed(id: &str, status: AlertStatus) -> ApiAlert {
+        ApiAlert {
+            alert: alert(
+                id,
+                AlertSource::Water,
+                "2026-03-12T08:00:00",
+                "2026-03-12T12:00:00",
+                "Ruska 5",
+            ),
+            status,
+            locations: Vec::new(),
+            previous_start_date: None,
+            previous_end_date: None,
+        }
+    }
+
+    async fn next(client: &mut Client) -> serde_json::Value {
+        loop {
+            if let tungstenite::Message::Text(text) = client.next().await.unwrap().unwrap() {
+                return serde_json::from_str(text.as_str()).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn change_messages_carry_status_and_locations() {
+        let now = parse_alert_time("2026-03-11T09:00:00").unwrap();
+        let before = alert(
+            "water-a",
+            AlertSource::Water,
+            "2026-03-12T08:00:00",
+            "2026-03-12T12:00:00",
+            "Ruska 5",
+        );
+        let after = alert(
+            "water-a",
+            AlertSource::Water,
+            "2026-03-13T08:00:00",
+            "2026-03-13T12:00:00",
+            "Ruska 5",
+        );
+        let changes = vec![
+            AlertChange {
+                kind: ChangeKind::TimeChanged,
+                alert: after,
+                previous: Some(before.clone()),
+            },
+            AlertChange {
+                kind: ChangeKind::Cancelled,
+                alert: alert(
+                    "tauron-b",
+                    AlertSource::Tauron,
+                    "2026-03-12T08:00:00",
+                    "2026-03-12T16:00:00",
+                    "Kuźnicza 1-30",
+                ),
+                previous: None,
+            },
+        ];
+        let messages = change_messages(&settings(), &listing(Vec::new()), &changes, now);
+
+        let StreamMessage::Change {
+            kind,
+            alert,
+            previous,
+        } = &messages[0]
+        else {
+            panic!("Not a change: {:?}", messages[0]);
+        };
+        assert_eq!(*kind, ChangeKind::TimeChanged);
+        assert_eq!(alert.status, AlertStatus::Upcoming);
+        assert_eq!(alert.locations, vec!["dzialka".to_string()]);
+        assert_eq!(previous.as_ref(), Some(&before));
+        let json = serde_json::to_value(&messages[1]).unwrap();
+        assert_eq!(json["type"], "change");
+        assert_eq!(json["kind"], "cancelled");
+        assert_eq!(json["alert"]["id"], "tauron-b");
+        assert_eq!(json["alert"]["status"], "cancelled");
+    }
+
+    #[test]
+    fn status_transitions_skip_new_and_announced_alerts() {
+        let mut known = BTreeMap::new();
+        let first = [
+            listed("a", AlertStatus::Upcoming),
+            listed("b", AlertStatus::Ongoing),
+        ];
+        assert!(status_transitions(&mut known, &first).is_empty());
+
+        let second = [
+            listed("a", AlertStatus::Ongoing),
+            listed("b", AlertStatus::Overrunning),
+            listed("c", AlertStatus::Upcoming),
+        ];
+        assert_eq!(
+            status_transitions(&mut known, &second),
+            vec![StreamMessage::Status {
+                alert: listed("a", AlertStatus::Ongoing),
+                previous_status: AlertStatus::Upcoming,
+            }]
+        );
+        assert_eq!(known.get("c"), Some(&AlertStatus::Upcoming));
+    }
+
+    #[tokio::test]
+    async fn streams_filtered_changes_and_resubscribes() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let snapshot = listing(vec![
+            alert(
+                "water-a",
+                AlertSource::Water,
+                "2026-03-12T08:00:00",
+                "2026-03-12T12:00:00",
+                "Kuźnicza 25",
+            ),
+            alert(
+                "tauron-b",
+                AlertSource::Tauron,
+                "2026-03-12T08:00:00",
+                "2026-03-12T16:00:00",
+                "Ruska 5",
+            ),
+        ]);
+        let (events, _) = broadcast::channel(16);
+        let state = ApiState {
+            token: Some("secret".to_string()),
+            current: Arc::new(move || (settings(), snapshot.clone())),
+            events: events.clone(),
+            metrics: false,
+        };
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(serve(listener, state, async {
+            stopped.await.ok();
+        }));
+
+        let refused = tokio_tungstenite::connect_async(format!("ws://{}/ws", address)).await;
+        assert!(matches!(refused, Err(tungstenite::Error::Http(r)) if r.status() == 401));
+
+        let url = format!("ws://{}/ws?source=water&token=secret", address);
+        let (mut client, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        let first = next(&mut client).await;
+        assert_eq!(first["type"], "snapshot");
+        assert_eq!(first["alerts"].as_array().unwrap().len(), 1);
+        assert_eq!(first["alerts"][0]["id"], "water-a");
+
+        let changes = [
+            AlertChange {
+                kind: ChangeKind::Added,
+                alert: alert(
+                    "tauron-c",
+                    AlertSource::Tauron,
+                    "2026-03-14T08:00:00",
+                    "2026-03-14T12:00:00",
+                    "Ruska",
+                ),
+                previous: None,
+            },
+            AlertChange {
+                kind: ChangeKind::Added,
+                alert: alert(
+                    "water-d",
+                    AlertSource::Water,
+                    "2026-03-14T08:00:00",
+                    "2026-03-14T12:00:00",
+                    "Kuźnicza",
+                ),
+                previous: None,
+            },
+        ];
+        let now = parse_alert_time("2026-03-11T09:00:00").unwrap();
+        for message in change_messages(&settings(), &listing(Vec::new()), &changes, now) {
+            events.send(message).unwrap();
+        }
+        let change = next(&mut client).await;
+        assert_eq!(change["type"], "change");
+        assert_eq!(change["kind"], "added");
+        assert_eq!(change["alert"]["id"], "water-d");
+
+        let subscribe = r#"{"type": "subscribe", "location": "Działka"}"#;
+        client
+            .send(tungstenite::Message::Text(subscribe.into()))
+            .await
+            .unwrap();
+        let resubscribed = next(&mut client).await;
+        assert_eq!(resubscribed["type"], "snapshot");
+        assert_eq!(resubscribed["alerts"].as_array().unwrap().len(), 1);
+        assert_eq!(resubscribed["alerts"][0]["id"], "tauron-b");
+
+        let unknown = r#"{"type": "subscribe", "source": "gas"}"#;
+        client
+            .send(tungstenite::Message::Text(unknown.into()))
+            .await
+            .unwrap();
+        assert_eq!(
+            next(&mut client).await,
+            serde_json::json!({ "type": "error", "error": "Unknown source: gas" })
+        );
+
+        drop(events);
+        stop.send(()).ok();
+        server.await.unwrap().unwrap();
+    }
+}
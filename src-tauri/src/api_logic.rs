@@ -1,4 +1,14 @@
+use crate::email::EmailSettings;
+use crate::geo_search::fold_diacritics;
+use crate::local_api::LocalApiSettings;
+use crate::matrix::MatrixSettings;
+use crate::mqtt::MqttSettings;
+use crate::rules::{NotificationRule, QuietHours};
+use crate::telegram::TelegramSettings;
+use crate::webhook::Webhook;
+use chrono::{DateTime, Local, NaiveDateTime};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 pub const BASE_URL: &str = "https://www.tauron-dystrybucja.pl/waapi";
 pub const MPWIK_URL: &str = "https://www.mpwik.wroc.pl/wp-admin/admin-ajax.php";
@@ -8,7 +18,7 @@ pub const FORTUM_REGION_ID: u32 = 3;
 
 // ── Alert source abstraction ──────────────────────────────
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum AlertSource {
     Tauron,
@@ -19,6 +29,9 @@ pub enum AlertSource {
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[allow(non_snake_case)]
 pub struct UnifiedAlert {
+    /// Stable identifier, e.g. for acknowledgements. See `alert_id`.
+    #[serde(default)]
+    pub id: String,
     pub source: AlertSource,
     pub startDate: Option<String>,
     pub endDate: Option<String>,
@@ -26,6 +39,53 @@ pub struct UnifiedAlert {
     pub description: Option<String>,
 }
 
+impl AlertSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertSource::Tauron => "tauron",
+            AlertSource::Water => "water",
+            AlertSource::Fortum => "fortum",
+        }
+    }
+}
+
+/// Id for alerts whose source has none: FNV-1a over the fields that identify them.
+/// It has to stay the same across app versions, so no `DefaultHasher`.
+pub fn alert_id(source: &AlertSource, parts: &[Option<&str>]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in parts {
+        for byte in part.unwrap_or_default().bytes().chain([0x1f]) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    format!("{}-{:016x}", source.as_str(), hash)
+}
+
+/// An alert's `startDate`/`endDate` as local wall-clock time. Providers send either
+/// local "2026-03-12T08:00:00" or RFC 3339 with an offset.
+pub fn parse_alert_time(value: &str) -> Option<NaiveDateTime> {
+    if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+        return Some(at.with_timezone(&Local).naive_local());
+    }
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f").ok()
+}
+
+impl UnifiedAlert {
+    pub fn start_time(&self) -> Option<NaiveDateTime> {
+        self.startDate.as_deref().and_then(parse_alert_time)
+    }
+
+    pub fn end_time(&self) -> Option<NaiveDateTime> {
+        self.endDate.as_deref().and_then(parse_alert_time)
+    }
+
+    /// Over once the end has passed; without a readable end it's treated as ongoing.
+    pub fn is_over(&self, now: NaiveDateTime) -> bool {
+        self.end_time().is_some_and(|end| end <= now)
+    }
+}
+
 // ── MPWiK (water) types ───────────────────────────────────
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -76,6 +136,14 @@ pub fn parse_mpwik_date(date_str: &str) -> Option<String> {
 impl MpwikFailureItem {
     pub fn to_unified(&self) -> UnifiedAlert {
         UnifiedAlert {
+            id: alert_id(
+                &AlertSource::Water,
+                &[
+                    self.date_start.as_deref(),
+                    self.date_end.as_deref(),
+                    self.content.as_deref(),
+                ],
+            ),
             source: AlertSource::Water,
             startDate: self.date_start.as_deref().and_then(parse_mpwik_date),
             endDate: self.date_end.as_deref().and_then(parse_mpwik_date),
@@ -88,6 +156,7 @@ impl MpwikFailureItem {
 impl FortumPoint {
     pub fn to_unified(&self) -> UnifiedAlert {
         UnifiedAlert {
+            id: format!("fortum-{}", self.switch_off_id),
             source: AlertSource::Fortum,
             startDate: self.start_date.clone(),
             endDate: self.end_date.clone(),
@@ -100,6 +169,14 @@ impl FortumPoint {
 impl OutageItem {
     pub fn to_unified(&self) -> UnifiedAlert {
         UnifiedAlert {
+            id: alert_id(
+                &AlertSource::Tauron,
+                &[
+                    self.StartDate.as_deref(),
+                    self.EndDate.as_deref(),
+                    self.Message.as_deref(),
+                ],
+            ),
             source: AlertSource::Tauron,
             startDate: self.StartDate.clone(),
             endDate: self.EndDate.clone(),
@@ -133,9 +210,13 @@ pub struct OutageResponse {
     pub debug_query: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
 #[allow(non_snake_case)]
 pub struct Settings {
+    /// Layout version, see `settings_store::CURRENT_SCHEMA_VERSION`. Missing in files
+    /// written before versioning, which reads as 0.
+    #[serde(default)]
+    pub schemaVersion: u32,
     pub cityName: String,
     pub streetName: String,
     pub houseNo: String,
@@ -147,6 +228,159 @@ pub struct Settings {
     pub language: Option<String>,
     #[serde(default)]
     pub enabledSources: Option<Vec<String>>,
+    /// Further addresses besides the primary one above.
+    #[serde(default)]
+    pub locations: Vec<SavedLocation>,
+    /// Folder to mirror settings into for sync between devices; this device only.
+    #[serde(default)]
+    pub syncFolder: Option<String>,
+    /// Background polling interval in minutes per source ("tauron", "water", "fortum");
+    /// missing sources use the defaults in `scheduler`.
+    #[serde(default)]
+    pub pollIntervals: Option<BTreeMap<String, u32>>,
+    /// Reminder lead times in minutes before an alert starts, per source, e.g.
+    /// `{"tauron": [1440, 120]}`; sources without an entry get no reminders.
+    #[serde(default)]
+    pub reminderLeadMinutes: Option<BTreeMap<String, Vec<u32>>>,
+    /// Evaluated in order, see `rules`.
+    #[serde(default)]
+    pub notificationRules: Vec<NotificationRule>,
+    #[serde(default)]
+    pub quietHours: Option<QuietHours>,
+    /// Hours after its start an alert without an end counts as overrunning; default
+    /// `scheduler::DEFAULT_OVERRUN_HOURS`.
+    #[serde(default)]
+    pub overrunAfterHours: Option<u32>,
+    /// Time of day for the daily digest, "HH:MM"; no digest when unset.
+    #[serde(default)]
+    pub digestTime: Option<String>,
+    /// Further channels every notification goes to, see `webhook`.
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>,
+    /// SMTP channel, see `email`.
+    #[serde(default)]
+    pub email: Option<EmailSettings>,
+    /// Chat channels, see `chat`.
+    #[serde(default)]
+    pub matrix: Option<MatrixSettings>,
+    #[serde(default)]
+    pub telegram: Option<TelegramSettings>,
+    /// Home Assistant sensors over MQTT, see `mqtt`.
+    #[serde(default)]
+    pub mqtt: Option<MqttSettings>,
+    /// HTTP API for the local network, see `local_api`.
+    #[serde(default)]
+    pub localApi: Option<LocalApiSettings>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[allow(non_snake_case)]
+pub struct SavedLocation {
+    pub label: String,
+    pub cityName: String,
+    pub streetName: String,
+    pub houseNo: String,
+    pub cityGAID: u64,
+    pub streetGAID: u64,
+}
+
+impl SavedLocation {
+    /// Two entries are the same place when the GAIDs and house number agree.
+    pub fn same_place(&self, other: &SavedLocation) -> bool {
+        self.cityGAID == other.cityGAID
+            && self.streetGAID == other.streetGAID
+            && self.houseNo.eq_ignore_ascii_case(&other.houseNo)
+    }
+}
+
+impl Settings {
+    /// The primary address as a `SavedLocation`.
+    pub fn primary_location(&self) -> SavedLocation {
+        SavedLocation {
+            label: format!("{} {}, {}", self.streetName, self.houseNo, self.cityName),
+            cityName: self.cityName.clone(),
+            streetName: self.streetName.clone(),
+            houseNo: self.houseNo.clone(),
+            cityGAID: self.cityGAID,
+            streetGAID: self.streetGAID,
+        }
+    }
+
+    /// The primary address (when set) and the saved locations, each with an id made
+    /// from its label by `location_id`, numbered when labels collide.
+    pub fn identified_locations(&self) -> Vec<(String, SavedLocation)> {
+        let primary = (!self.streetName.trim().is_empty()).then(|| self.primary_location());
+        let mut locations: Vec<(String, SavedLocation)> = Vec::new();
+        for location in primary.into_iter().chain(self.locations.iter().cloned()) {
+            let mut id = location_id(&location.label);
+            if id.is_empty() {
+                id = "location".to_string();
+            }
+            let base = id.clone();
+            for n in 2.. {
+                if !locations.iter().any(|(taken, _)| *taken == id) {
+                    break;
+                }
+                id = format!("{}_{}", base, n);
+            }
+            locations.push((id, location));
+        }
+        locations
+    }
+
+    /// Without the passwords and tokens of the channels and the local API. Those stay
+    /// on this device: bundles and the sync folder only ever get this.
+    pub fn without_secrets(&self) -> Settings {
+        let mut settings = self.clone();
+        settings.keep_secrets(&Settings::default());
+        settings
+    }
+
+    /// Take the passwords and tokens from `local`, matching webhooks by URL; channels
+    /// `local` doesn't have get none.
+    pub fn keep_secrets(&mut self, local: &Settings) {
+        for webhook in &mut self.webhooks {
+            let ours = local.webhooks.iter().find(|w| w.url == webhook.url);
+            webhook.token = ours.and_then(|w| w.token.clone());
+            webhook.hmacSecret = ours.and_then(|w| w.hmacSecret.clone());
+        }
+        if let Some(email) = &mut self.email {
+            email.password = local.email.as_ref().and_then(|e| e.password.clone());
+        }
+        if let Some(matrix) = &mut self.matrix {
+            matrix.accessToken = local
+                .matrix
+                .as_ref()
+                .map(|m| m.accessToken.clone())
+                .unwrap_or_default();
+        }
+        if let Some(telegram) = &mut self.telegram {
+            telegram.botToken = local
+                .telegram
+                .as_ref()
+                .map(|t| t.botToken.clone())
+                .unwrap_or_default();
+        }
+        if let Some(mqtt) = &mut self.mqtt {
+            mqtt.password = local.mqtt.as_ref().and_then(|m| m.password.clone());
+        }
+        if let Some(local_api) = &mut self.localApi {
+            local_api.token = local.localApi.as_ref().and_then(|l| l.token.clone());
+        }
+    }
+}
+
+/// "ul. Kuźnicza 25, Wrocław" → "ul_kuznicza_25_wroclaw".
+pub fn location_id(label: &str) -> String {
+    let mut id = String::new();
+    for c in fold_diacritics(label).chars() {
+        if c.is_ascii_alphanumeric() {
+            id.push(c);
+        } else if !id.is_empty() && !id.ends_with('_') {
+            id.push('_');
+        }
+    }
+    id.trim_end_matches('_').to_string()
 }
 
 pub fn get_cities_query(city_name: &str, cache_bust: &str) -> Vec<(&'static str, String)> {
@@ -186,25 +420,6 @@ pub fn get_outages_query(
     ]
 }
 
-pub fn save_settings_to_path(path: &std::path::Path, settings: &Settings) -> Result<(), String> {
-    let json = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
-    std::fs::write(path, json).map_err(|e| e.to_string())?;
-    Ok(())
-}
-
-pub fn load_settings_from_path(path: &std::path::Path) -> Result<Option<Settings>, String> {
-    if !path.exists() {
-        return Ok(None);
-    }
-    let data = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
-    if data.trim().is_empty() {
-        return Ok(None);
-    }
-    let settings: Settings = serde_json::from_str(&data)
-        .map_err(|e| format!("Settings parse error (might be empty/corrupt): {}", e))?;
-    Ok(Some(settings))
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -219,6 +434,7 @@ mod tests {
             streetGAID: 456,
             theme: Some("dark".to_string()),
             language: Some("pl".to_string()),
+            ..Default::default()
         };
         let json = serde_json::to_string(&settings).unwrap();
         let deserialized: Settings = serde_json::from_str(&json).unwrap();
@@ -251,76 +467,6 @@ mod tests {
         assert_eq!(query[2], ("houseNo", "5".to_string()));
     }
 
-    #[test]
-    fn test_settings_persistence() {
-        let temp_dir = std::env::temp_dir();
-        let test_path = temp_dir.join("test_settings.json");
-
-        let settings = Settings {
-            cityName: "TestCity".to_string(),
-            streetName: "TestStreet".to_string(),
-            houseNo: "10".to_string(),
-            cityGAID: 111,
-            streetGAID: 222,
-            theme: Some("light".to_string()),
-            language: Some("en".to_string()),
-        };
-
-        // Save
-        save_settings_to_path(&test_path, &settings).expect("Failed to save settings");
-
-        // Load
-        let loaded = load_settings_from_path(&test_path).expect("Failed to load settings");
-        assert_eq!(Some(settings), loaded);
-
-        // Cleanup
-        std::fs::remove_file(test_path).ok();
-    }
-
-    #[test]
-    fn test_load_non_existent_settings() {
-        let test_path = std::path::Path::new("non_existent_settings.json");
-        let loaded = load_settings_from_path(test_path).expect("Failed to load settings");
-        assert_eq!(None, loaded);
-    }
-
-    #[test]
-    fn test_load_corrupt_settings() {
-        let temp_dir = std::env::temp_dir();
-        let test_path = temp_dir.join("corrupt_settings.json");
-        std::fs::write(&test_path, "{ invalid json }").unwrap();
-
-        let result = load_settings_from_path(&test_path);
-        assert!(result.is_err());
-        // Just verify it's an error, exact message can vary
-
-        std::fs::remove_file(test_path).ok();
-    }
-
-    #[test]
-    fn test_load_legacy_settings_missing_fields() {
-        let temp_dir = std::env::temp_dir();
-        let test_path = temp_dir.join("legacy_settings.json");
-        // Theme is optional, but let's see if we missing other fields how it behaves
-        let legacy_json = r#"{
-            "cityName": "Legacy",
-            "streetName": "Old St",
-            "houseNo": "1",
-            "cityGAID": 1,
-            "streetGAID": 2
-        }"#;
-        std::fs::write(&test_path, legacy_json).unwrap();
-
-        let loaded =
-            load_settings_from_path(&test_path).expect("Should handle missing optional fields");
-        assert!(loaded.is_some());
-        let s = loaded.unwrap();
-        assert_eq!(s.cityName, "Legacy");
-        assert_eq!(s.theme, None); // Should default to None
-
-        std::fs::remove_file(test_path).ok();
-    }
-
     #[test]
     fn test_parse_outage_response() {
         let json = r#"{
@@ -397,5 +543,45 @@ mod tests {
         assert_eq!(unified.source, AlertSource::Tauron);
         assert_eq!(unified.message, Some("Test power outage".to_string()));
         assert_eq!(unified.description, Some("Testing".to_string()));
+        assert!(unified.id.starts_with("tauron-"));
+    }
+
+    #[test]
+    fn test_alert_id_is_stable() {
+        let a = alert_id(&AlertSource::Water, &[Some("a"), None, Some("b")]);
+        assert_eq!(
+            a,
+            alert_id(&AlertSource::Water, &[Some("a"), None, Some("b")])
+        );
+        assert_eq!(a, "water-3ec5d838ed60b56d");
+        // Field boundaries matter
+        assert_ne!(
+            a,
+            alert_id(&AlertSource::Water, &[Some("a"), Some("b"), None])
+        );
+        assert_ne!(
+            a,
+            alert_id(&AlertSource::Tauron, &[Some("a"), None, Some("b")])
+        );
+    }
+
+    #[test]
+    fn test_parse_alert_time() {
+        let local = parse_alert_time("2026-03-12T08:30:00").unwrap();
+        assert_eq!(local.to_string(), "2026-03-12 08:30:00");
+        assert!(parse_alert_time("2026-03-12T08:30:00.000").is_some());
+        let zoned = parse_alert_time("2026-03-12T08:30:00Z").unwrap();
+        let expected = DateTime::parse_from_rfc3339("2026-03-12T08:30:00Z").unwrap();
+        assert_eq!(zoned, expected.with_timezone(&Local).naive_local());
+        assert_eq!(parse_alert_time("12-03-2026 08:30"), None);
+
+        let alert = MpwikFailureItem {
+            date_start: None,
+            date_end: Some("12-03-2026 16:00".to_string()),
+            content: None,
+        }
+        .to_unified();
+        assert!(!alert.is_over(local));
+        assert!(alert.is_over(parse_alert_time("2026-03-12T16:00:00").unwrap()));
     }
 }
@@ -0,0 +1,452 @@
+//! Export/import bundle: settings (with saved locations) and alert
+//! acknowledgements in one versioned JSON file, for moving a setup between devices.
+
+use crate::acknowledgements::{self, Acknowledgement};
+use crate::api_logic::{SavedLocation, Settings};
+use crate::settings_store;
+use crate::validation::{self, Severity};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Marks a JSON file as one of our bundles.
+pub const BUNDLE_FORMAT: &str = "awaria-bundle";
+
+/// Bump when the bundle layout itself changes; the settings inside carry their own
+/// `schemaVersion` and are migrated like `settings.json`.
+pub const BUNDLE_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Bundle {
+    pub format: String,
+    pub bundle_version: u32,
+    pub exported_at: String,
+    pub app_version: String,
+    /// Kept as raw JSON so older settings layouts can be migrated on import.
+    pub settings: Value,
+    #[serde(default)]
+    pub acknowledgements: Vec<Acknowledgement>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// Keep the current setup and add the bundle's locations, notification rules and
+    /// acknowledgements.
+    Merge,
+    /// Make the bundle the current setup.
+    Replace,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldChange {
+    pub field: String,
+    pub current: Value,
+    pub incoming: Value,
+}
+
+/// What an import would do. Nothing is written unless `valid` is true.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportPreview {
+    pub mode: ImportMode,
+    pub valid: bool,
+    pub errors: Vec<String>,
+    /// Settings fields that change, other than locations.
+    pub changes: Vec<FieldChange>,
+    pub locations_added: Vec<SavedLocation>,
+    pub locations_removed: Vec<SavedLocation>,
+    pub acknowledgements_added: usize,
+    /// The resulting state, `None` when the bundle is invalid.
+    pub settings: Option<Settings>,
+    pub acknowledgements: Vec<Acknowledgement>,
+}
+
+pub fn export(
+    settings: &Settings,
+    acknowledgements: Vec<Acknowledgement>,
+) -> Result<Bundle, String> {
+    let mut settings = settings.without_secrets();
+    settings.schemaVersion = settings_store::CURRENT_SCHEMA_VERSION;
+    // Paths on this device mean nothing elsewhere
+    settings.syncFolder = None;
+    Ok(Bundle {
+        format: BUNDLE_FORMAT.to_string(),
+        bundle_version: BUNDLE_VERSION,
+        exported_at: Utc::now().to_rfc3339(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        settings: serde_json::to_value(&settings).map_err(|e| e.to_string())?,
+        acknowledgements,
+    })
+}
+
+/// Parse and check a bundle, collecting every problem rather than stopping at the first.
+fn parse_bundle(data: &str) -> Result<(Settings, Vec<Acknowledgement>), Vec<String>> {
+    let bundle: Bundle =
+        serde_json::from_str(data).map_err(|e| vec![format!("Not a settings bundle: {}", e)])?;
+    if bundle.format != BUNDLE_FORMAT {
+        return Err(vec![format!(
+            "Not a settings bundle (format \"{}\")",
+            bundle.format
+        )]);
+    }
+    if bundle.bundle_version > BUNDLE_VERSION {
+        return Err(vec![format!(
+            "The bundle was exported by a newer version of the app ({}). Please update the app.",
+            bundle.app_version
+        )]);
+    }
+
+    let mut value = bundle.settings;
+    if !value.is_object() {
+        return Err(vec!["Bundle settings are not a JSON object".to_string()]);
+    }
+    settings_store::migrate(&mut value).map_err(|e| vec![e])?;
+    let settings: Settings = serde_json::from_value(value)
+        .map_err(|e| vec![format!("Bundle settings are invalid: {}", e)])?;
+
+    let mut errors: Vec<String> = validation::normalize(&settings)
+        .1
+        .into_iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .map(|issue| issue.message)
+        .collect();
+    for location in &settings.locations {
+        if location.cityGAID == 0 || location.streetGAID == 0 || location.houseNo.trim().is_empty()
+        {
+            errors.push(format!("Location \"{}\" is incomplete", location.label));
+        }
+    }
+    for ack in &bundle.acknowledgements {
+        if ack.alert_id.is_empty() || DateTime::parse_from_rfc3339(&ack.acknowledged_at).is_err() {
+            errors.push(format!(
+                "Invalid acknowledgement for alert \"{}\"",
+                ack.alert_id
+            ));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok((settings, bundle.acknowledgements))
+    } else {
+        Err(errors)
+    }
+}
+
+/// Primary address followed by the saved locations.
+fn all_locations(settings: &Settings) -> Vec<SavedLocation> {
+    let mut locations = vec![settings.primary_location()];
+    locations.extend(settings.locations.iter().cloned());
+    locations
+}
+
+fn merge_settings(current: &Settings, incoming: &Settings) -> Settings {
+    let mut merged = current.clone();
+    for location in all_locations(incoming) {
+        if !all_locations(&merged)
+            .iter()
+            .any(|l| l.same_place(&location))
+        {
+            merged.locations.push(location);
+        }
+    }
+    if merged.theme.is_none() {
+        merged.theme = incoming.theme.clone();
+    }
+    if merged.language.is_none() {
+        merged.language = incoming.language.clone();
+    }
+    if merged.enabledSources.is_none() {
+        merged.enabledSources = incoming.enabledSources.clone();
+    }
+    for rule in &incoming.notificationRules {
+        if !merged.notificationRules.iter().any(|r| r.name == rule.name) {
+            merged.notificationRules.push(rule.clone());
+        }
+    }
+    if merged.quietHours.is_none() {
+        merged.quietHours = incoming.quietHours.clone();
+    }
+    merged
+}
+
+fn field_changes(current: &Settings, result: &Settings) -> Vec<FieldChange> {
+    let (Ok(Value::Object(before)), Ok(Value::Object(after))) =
+        (serde_json::to_value(current), serde_json::to_value(result))
+    else {
+        return Vec::new();
+    };
+    after
+        .into_iter()
+        .filter(|(field, _)| field != "schemaVersion" && field != "locations")
+        .filter_map(|(field, incoming)| {
+            let current = before.get(&field).cloned().unwrap_or(Value::Null);
+            (current != incoming).then_some(FieldChange {
+                field,
+                current,
+                incoming,
+            })
+        })
+        .collect()
+}
+
+/// Work out what importing `data` would do to the current state.
+pub fn preview(
+    data: &str,
+    mode: ImportMode,
+    current: Option<&Settings>,
+    current_acknowledgements: &[Acknowledgement],
+) -> ImportPreview {
+    let (incoming, incoming_acks) = match parse_bundle(data) {
+        Ok(parsed) => parsed,
+        Err(errors) => {
+            return ImportPreview {
+                mode,
+                valid: false,
+                errors,
+                changes: Vec::new(),
+                locations_added: Vec::new(),
+                locations_removed: Vec::new(),
+                acknowledgements_added: 0,
+                settings: None,
+                acknowledgements: current_acknowledgements.to_vec(),
+            }
+        }
+    };
+
+    let (mut settings, acknowledgements) = match (mode, current) {
+        (ImportMode::Merge, Some(current)) => (
+            merge_settings(current, &incoming),
+            acknowledgements::merge(current_acknowledgements, &incoming_acks),
+        ),
+        _ => (incoming, incoming_acks),
+    };
+    let empty = Settings::default();
+    let before = current.unwrap_or(&empty);
+    settings.syncFolder = before.syncFolder.clone();
+    settings.keep_secrets(before);
+
+    let before_locations = if current.is_some() {
+        all_locations(before)
+    } else {
+        Vec::new()
+    };
+    let after_locations = all_locations(&settings);
+    let locations_added = after_locations
+        .iter()
+        .filter(|l| !before_locations.iter().any(|b| b.same_place(l)))
+        .cloned()
+        .collect();
+    let locations_removed = before_locations
+        .iter()
+        .filter(|b| !after_locations.iter().any(|l| l.same_place(b)))
+        .cloned()
+        .collect();
+    let acknowledgements_added = acknowledgements
+        .iter()
+        .filter(|a| {
+            !current_acknowledgements
+                .iter()
+                .any(|c| c.alert_id == a.alert_id)
+        })
+        .count();
+
+    ImportPreview {
+        mode,
+        valid: true,
+        errors: Vec::new(),
+        changes: field_changes(before, &settings),
+        locations_added,
+        locations_removed,
+        acknowledgements_added,
+        settings: Some(settings),
+        acknowledgements,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::local_api::LocalApiSettings;
+    use crate::rules::{NotificationRule, RuleAction};
+    use crate::telegram::TelegramSettings;
+    use crate::webhook::Webhook;
+
+    fn settings(street: &str, street_gaid: u64) -> Settings {
+        Settings {
+            cityName: "Wrocław".to_string(),
+            streetName: street.to_string(),
+            houseNo: "25".to_string(),
+            cityGAID: 1,
+            streetGAID: street_gaid,
+            ..Default::default()
+        }
+    }
+
+    fn ack(id: &str) -> Acknowledgement {
+        Acknowledgement {
+            alert_id: id.to_string(),
+            acknowledged_at: "2026-03-01T10:00:00+00:00".to_string(),
+        }
+    }
+
+    fn bundle_json(settings: &Settings, acks: Vec<Acknowledgement>) -> String {
+        serde_json::to_string(&export(settings, acks).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_export_import_roundtrip() {
+        let mut exported = settings("Kuźnicza", 10);
+        exported.theme = Some("dark".to_string());
+        let data = bundle_json(&exported, vec![ack("tauron-1")]);
+
+        let preview = preview(&data, ImportMode::Replace, None, &[]);
+        assert!(preview.valid, "{:?}", preview.errors);
+        let imported = preview.settings.unwrap();
+        assert_eq!(imported.streetName, "Kuźnicza");
+        assert_eq!(imported.theme.as_deref(), Some("dark"));
+        assert_eq!(preview.acknowledgements, vec![ack("tauron-1")]);
+        assert_eq!(preview.locations_added.len(), 1);
+    }
+
+    #[test]
+    fn test_merge_adds_locations_and_keeps_current() {
+        let mut current = settings("Kuźnicza", 10);
+        current.theme = Some("light".to_string());
+        let mut incoming = settings("Ruska", 11);
+        incoming.theme = Some("dark".to_string());
+        incoming.locations.push(current.primary_location());
+        incoming.notificationRules.push(NotificationRule {
+            name: "quiet water".to_string(),
+            when: Default::default(),
+            action: RuleAction::DigestOnly,
+        });
+        let data = bundle_json(&incoming, vec![ack("water-1")]);
+
+        let preview = preview(&data, ImportMode::Merge, Some(&current), &[ack("tauron-1")]);
+        assert!(preview.valid);
+        let merged = preview.settings.unwrap();
+        assert_eq!(merged.streetName, "Kuźnicza");
+        assert_eq!(merged.theme.as_deref(), Some("light"));
+        // The bundle's copy of our own address is not added twice
+        assert_eq!(merged.locations.len(), 1);
+        assert_eq!(merged.locations[0].streetGAID, 11);
+        assert_eq!(merged.notificationRules.len(), 1);
+        assert_eq!(preview.changes.len(), 1);
+        assert!(preview.locations_removed.is_empty());
+        assert_eq!(preview.acknowledgements_added, 1);
+        assert_eq!(preview.acknowledgements.len(), 2);
+    }
+
+    #[test]
+    fn test_replace_reports_changes() {
+        let mut current = settings("Kuźnicza", 10);
+        current.syncFolder = Some("/home/me/Sync".to_string());
+        let data = bundle_json(&settings("Ruska", 11), Vec::new());
+
+        let preview = preview(
+            &data,
+            ImportMode::Replace,
+            Some(&current),
+            &[ack("tauron-1")],
+        );
+        assert!(preview.valid);
+        let fields: Vec<&str> = preview.changes.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(fields, vec!["streetGAID", "streetName"]);
+        assert_eq!(preview.locations_removed.len(), 1);
+        assert!(preview.acknowledgements.is_empty());
+        // The sync folder belongs to this device and is kept
+        assert_eq!(preview.settings.unwrap().syncFolder, current.syncFolder);
+    }
+
+    #[test]
+    fn test_secrets_stay_on_device() {
+        let mut current = settings("Kuźnicza", 10);
+        current.webhooks.push(Webhook {
+            name: "ntfy".to_string(),
+            url: "https://ntfy.example.com/awaria".to_string(),
+            token: Some("tk_ntfy".to_string()),
+            hmacSecret: Some("hmac-secret".to_string()),
+            ..Default::default()
+        });
+        current.telegram = Some(TelegramSettings {
+            apiUrl: None,
+            botToken: "123:bot".to_string(),
+            chatId: "42".to_string(),
+        });
+        current.localApi = Some(LocalApiSettings {
+            token: Some("api-secret".to_string()),
+            ..Default::default()
+        });
+        let data = bundle_json(&current, Vec::new());
+        for secret in ["tk_ntfy", "hmac-secret", "123:bot", "api-secret"] {
+            assert!(!data.contains(secret), "{} was exported", secret);
+        }
+
+        // Another device's setup keeps its own secrets; nothing comes from the bundle
+        let mut other = current.clone();
+        other.telegram = None;
+        other.webhooks[0].token = Some("tk_other".to_string());
+        let imported = preview(&data, ImportMode::Replace, Some(&other), &[])
+            .settings
+            .unwrap();
+        assert_eq!(imported.webhooks[0].token.as_deref(), Some("tk_other"));
+        assert_eq!(imported.telegram.unwrap().botToken, "");
+        assert_eq!(
+            imported.localApi.unwrap().token.as_deref(),
+            Some("api-secret")
+        );
+    }
+
+    #[test]
+    fn test_invalid_bundles_are_rejected() {
+        let current = settings("Kuźnicza", 10);
+        let check = |data: &str| {
+            let preview = preview(data, ImportMode::Replace, Some(&current), &[]);
+            assert!(!preview.valid);
+            assert!(preview.settings.is_none());
+            preview.errors
+        };
+
+        check("not json");
+        check(
+            r#"{"format":"other","bundleVersion":1,"exportedAt":"","appVersion":"","settings":{}}"#,
+        );
+
+        let mut bundle = export(&current, Vec::new()).unwrap();
+        bundle.bundle_version = BUNDLE_VERSION + 1;
+        check(&serde_json::to_string(&bundle).unwrap());
+
+        let mut broken = settings("Kuźnicza", 0);
+        broken.houseNo = String::new();
+        let errors = check(&bundle_json(&broken, vec![ack("")]));
+        assert_eq!(errors.len(), 3);
+    }
+
+    #[test]
+    fn test_old_settings_layout_is_migrated() {
+        let data = r#"{
+            "format": "awaria-bundle",
+            "bundleVersion": 1,
+            "exportedAt": "2026-03-01T10:00:00+00:00",
+            "appVersion": "1.0.14",
+            "settings": {
+                "cityName": "Wrocław",
+                "streetName": "Kuźnicza",
+                "houseNo": "25",
+                "cityGAID": 1,
+                "streetGAID": 10
+            }
+        }"#;
+        let preview = preview(data, ImportMode::Replace, None, &[]);
+        assert!(preview.valid, "{:?}", preview.errors);
+        let settings = preview.settings.unwrap();
+        assert_eq!(
+            settings.schemaVersion,
+            settings_store::CURRENT_SCHEMA_VERSION
+        );
+        assert!(settings.locations.is_empty());
+    }
+}
@@ -0,0 +1,406 @@
+//! Chat notification channels: a Matrix room (`Settings.matrix`) and a Telegram chat
+//! (`Settings.telegram`). Every notification is posted as its own message, and the
+//! message posted for an alert is remembered in `chat-messages.json`. When the alert
+//! is rescheduled, that message is edited in place; when it is cancelled, the
+//! cancellation is posted as a reply to it.
+
+use crate::api_logic::Settings;
+use crate::matrix::{self, MatrixSettings};
+use crate::notifier::Outgoing;
+use crate::rules::EventKind;
+use crate::settings_store::{read_json, store_json};
+use crate::telegram::{self, TelegramSettings};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+pub const CHAT_MESSAGES_FILE: &str = "chat-messages.json";
+
+/// Messages are forgotten after this long; by then the alert is long over.
+const KEEP_DAYS: i64 = 30;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatService {
+    Matrix,
+    Telegram,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChatChannel {
+    Matrix(MatrixSettings),
+    Telegram(TelegramSettings),
+}
+
+impl ChatChannel {
+    /// The channels set up in `settings`.
+    pub fn configured(settings: &Settings) -> Vec<ChatChannel> {
+        let matrix = settings.matrix.clone().map(ChatChannel::Matrix);
+        let telegram = settings.telegram.clone().map(ChatChannel::Telegram);
+        matrix.into_iter().chain(telegram).collect()
+    }
+
+    pub fn service(&self) -> ChatService {
+        match self {
+            ChatChannel::Matrix(_) => ChatService::Matrix,
+            ChatChannel::Telegram(_) => ChatService::Telegram,
+        }
+    }
+
+    /// Post `outgoing`, as a reply to message `reply_to` when given; returns the new
+    /// message's id.
+    pub async fn send(
+        &self,
+        client: &reqwest::Client,
+        outgoing: &Outgoing,
+        reply_to: Option<&str>,
+    ) -> Result<String, String> {
+        match self {
+            ChatChannel::Matrix(settings) => {
+                matrix::send(client, settings, outgoing, reply_to).await
+            }
+            ChatChannel::Telegram(settings) => {
+                telegram::send(client, settings, outgoing, reply_to).await
+            }
+        }
+    }
+
+    /// Replace message `id` by `outgoing`.
+    pub async fn edit(
+        &self,
+        client: &reqwest::Client,
+        id: &str,
+        outgoing: &Outgoing,
+    ) -> Result<(), String> {
+        match self {
+            ChatChannel::Matrix(settings) => matrix::edit(client, settings, id, outgoing).await,
+            ChatChannel::Telegram(settings) => telegram::edit(client, settings, id, outgoing).await,
+        }
+    }
+}
+
+/// The message posted about an alert.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct ChatMessage {
+    service: ChatService,
+    alert_id: String,
+    message_id: String,
+    sent_at: String,
+}
+
+#[derive(Default)]
+pub struct ChatMessages {
+    sent: Mutex<Vec<ChatMessage>>,
+    path: Option<PathBuf>,
+}
+
+impl ChatMessages {
+    pub fn open(path: PathBuf) -> Self {
+        ChatMessages {
+            sent: Mutex::new(read_json(&path)),
+            path: Some(path),
+        }
+    }
+
+    fn store(&self, sent: &[ChatMessage]) {
+        store_json(self.path.as_deref(), &sent, "chat messages");
+    }
+
+    /// The id of the message posted about `alert_id`.
+    pub fn find(&self, service: ChatService, alert_id: &str) -> Option<String> {
+        let sent = self.sent.lock().ok()?;
+        sent.iter()
+            .rfind(|m| m.service == service && m.alert_id == alert_id)
+            .map(|m| m.message_id.clone())
+    }
+
+    /// Remember `message_id` as the message about `alert_id`, forgetting old ones.
+    pub fn remember(
+        &self,
+        service: ChatService,
+        alert_id: &str,
+        message_id: &str,
+        now: DateTime<Utc>,
+    ) {
+        let Ok(mut sent) = self.sent.lock() else {
+            return;
+        };
+        sent.retain(|m| {
+            !(m.service == service && m.alert_id == alert_id)
+                && DateTime::parse_from_rfc3339(&m.sent_at)
+                    .is_ok_and(|at| now - at.to_utc() < Duration::days(KEEP_DAYS))
+        });
+        sent.push(ChatMessage {
+            service,
+            alert_id: alert_id.to_string(),
+            message_id: message_id.to_string(),
+            sent_at: now.to_rfc3339(),
+        });
+        self.store(&sent);
+    }
+
+    /// The message about `from` is now about `to`, after a reschedule changed the id.
+    fn rekey(&self, service: ChatService, from: &str, to: &str) {
+        let Ok(mut sent) = self.sent.lock() else {
+            return;
+        };
+        sent.retain(|m| !(m.service == service && m.alert_id == to));
+        for message in sent
+            .iter_mut()
+            .filter(|m| m.service == service && m.alert_id == from)
+        {
+            message.alert_id = to.to_string();
+        }
+        self.store(&sent);
+    }
+}
+
+/// Post `outgoing` to `channel`: a reschedule edits the alert's earlier message and a
+/// cancellation replies to it, when there is one; anything else is a new message.
+pub async fn deliver(
+    client: &reqwest::Client,
+    channel: &ChatChannel,
+    messages: &ChatMessages,
+    outgoing: &Outgoing,
+) -> Result<(), String> {
+    let service = channel.service();
+    let Some(alert) = &outgoing.alert else {
+        return channel.send(client, outgoing, None).await.map(|_| ());
+    };
+    let earlier_id = outgoing.previous_alert_id.as_deref().unwrap_or(&alert.id);
+    match (outgoing.event, messages.find(service, earlier_id)) {
+        (Some(EventKind::TimeChanged), Some(id)) => {
+            channel.edit(client, &id, outgoing).await?;
+            messages.rekey(service, earlier_id, &alert.id);
+        }
+        (Some(EventKind::Cancelled), Some(id)) => {
+            channel.send(client, outgoing, Some(&id)).await?;
+        }
+        _ => {
+            let id = channel.send(client, outgoing, None).await?;
+            messages.remember(service, &alert.id, &id, Utc::now());
+        }
+    }
+    Ok(())
+}
+
+/// Post `outgoing` to the configured chats in the background. Needs `ChatMessages`
+/// managed.
+pub fn dispatch(app: &AppHandle, settings: &Settings, outgoing: &[Outgoing]) {
+    let channels = ChatChannel::configured(settings);
+    if channels.is_empty() || outgoing.is_empty() {
+        return;
+    }
+    let app = app.clone();
+    let outgoing = outgoing.to_vec();
+    tauri::async_runtime::spawn(async move {
+        let client = reqwest::Client::new();
+        let messages = app.state::<ChatMessages>();
+        for channel in &channels {
+            for o in &outgoing {
+                if let Err(e) = deliver(&client, channel, &messages, o).await {
+                    log::warn!("Posting to {:?} failed: {}", channel.service(), e);
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert_diff::{diff, mark_cancelled};
+    use crate::api_logic::{parse_alert_time, AlertSource};
+    use crate::notifier::Lang;
+    use crate::rules::RuleAction;
+    use crate::test_support::{self, alert, temp_dir, Request};
+    use std::slice;
+    use std::sync::{Arc, Mutex};
+
+    /// A local stand-in for both the Matrix homeserver and the Telegram Bot API,
+    /// numbering the messages it accepts.
+    async fn stand_in() -> (String, Arc<Mutex<Vec<Request>>>) {
+        test_support::stand_in(|n, request| {
+            let reply = if request.path().contains("/_matrix/") {
+                format!(r#"{{"event_id": "$event{}"}}"#, n)
+            } else {
+                format!(r#"{{"ok": true, "result": {{"message_id": {}}}}}"#, n)
+            };
+            (200, reply)
+        })
+        .await
+    }
+
+    /// The notifications for a new alert, its reschedule and its cancellation.
+    fn story() -> Vec<Outgoing> {
+        let settings = Settings {
+            streetName: "Kuźnicza".to_string(),
+            ..Default::default()
+        };
+        let first = alert(
+            "tauron-a",
+            AlertSource::Tauron,
+            "2026-03-12T08:00:00",
+            "2026-03-12T16:00:00",
+            "Kuźnicza 1-30 <B>",
+        );
+        let moved = alert(
+            "tauron-b",
+            AlertSource::Tauron,
+            "2026-03-12T08:00:00",
+            "2026-03-12T18:00:00",
+            "Kuźnicza 1-30 <B>",
+        );
+        let added = diff(&[], slice::from_ref(&first));
+        let rescheduled = diff(slice::from_ref(&first), slice::from_ref(&moved));
+        let mut cancelled = diff(slice::from_ref(&moved), &[]);
+        mark_cancelled(
+            &mut cancelled,
+            parse_alert_time("2026-03-11T12:00:00").unwrap(),
+        );
+        [added, rescheduled, cancelled]
+            .iter()
+            .map(|changes| {
+                Outgoing::for_change(&changes[0], &settings, RuleAction::Notify, Lang::En)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_formatting() {
+        let outgoing = &story()[0];
+        assert_eq!(
+            telegram::text(outgoing),
+            "<b>Power outage at your address</b>\n12.03 08:00–16:00\nKuźnicza 1-30 &lt;B&gt;"
+        );
+        let content = matrix::content(outgoing);
+        assert_eq!(
+            content["body"],
+            "Power outage at your address\n12.03 08:00–16:00\nKuźnicza 1-30 <B>"
+        );
+        assert_eq!(
+            content["formatted_body"],
+            "<b>Power outage at your address</b><br>12.03 08:00–16:00<br>Kuźnicza 1-30 &lt;B&gt;"
+        );
+    }
+
+    #[test]
+    fn test_long_telegram_text_is_cut() {
+        let mut outgoing = story()[0].clone();
+        outgoing.body = ["<Kuźnicza> 1-30"; 400].join("\n");
+        let text = telegram::text(&outgoing);
+        assert!(text.encode_utf16().count() <= telegram::MAX_TEXT_LENGTH);
+        assert!(text.ends_with('…'));
+        // Never cut inside an entity
+        assert!(text
+            .match_indices('&')
+            .all(|(i, _)| text[i..].starts_with("&lt;") || text[i..].starts_with("&gt;")));
+        assert!(text.starts_with("<b>Power outage at your address</b>\n&lt;Kuźnicza&gt; 1-30\n"));
+    }
+
+    #[tokio::test]
+    async fn test_telegram_edits_and_replies() {
+        let (url, requests) = stand_in().await;
+        let channel = ChatChannel::Telegram(TelegramSettings {
+            apiUrl: Some(url),
+            botToken: "123:abc".to_string(),
+            chatId: "-100".to_string(),
+        });
+        let messages = ChatMessages::default();
+        let client = reqwest::Client::new();
+        for outgoing in story() {
+            deliver(&client, &channel, &messages, &outgoing)
+                .await
+                .unwrap();
+        }
+
+        let requests = requests.lock().unwrap();
+        let paths: Vec<&str> = requests.iter().map(Request::path).collect();
+        assert_eq!(
+            paths,
+            [
+                "/bot123:abc/sendMessage",
+                "/bot123:abc/editMessageText",
+                "/bot123:abc/sendMessage"
+            ]
+        );
+        assert_eq!(requests[0].json()["chat_id"], "-100");
+        assert_eq!(requests[0].json()["parse_mode"], "HTML");
+        assert_eq!(requests[1].json()["message_id"], 1);
+        assert!(requests[1].json()["text"]
+            .as_str()
+            .unwrap()
+            .contains("16:00 → 12.03 08:00–18:00"));
+        assert_eq!(requests[2].json()["reply_parameters"]["message_id"], 1);
+        assert_eq!(
+            messages.find(ChatService::Telegram, "tauron-b"),
+            Some("1".to_string())
+        );
+        assert_eq!(messages.find(ChatService::Telegram, "tauron-a"), None);
+    }
+
+    #[tokio::test]
+    async fn test_matrix_edits_and_replies() {
+        let (url, requests) = stand_in().await;
+        let channel = ChatChannel::Matrix(MatrixSettings {
+            homeserver: format!("{}/", url),
+            accessToken: "syt_token".to_string(),
+            roomId: "!room:example.com".to_string(),
+        });
+        let dir = temp_dir("chat");
+        let path = dir.join(CHAT_MESSAGES_FILE);
+        let client = reqwest::Client::new();
+        let story = story();
+        deliver(
+            &client,
+            &channel,
+            &ChatMessages::open(path.clone()),
+            &story[0],
+        )
+        .await
+        .unwrap();
+        // The message ids survive a restart
+        for outgoing in &story[1..] {
+            deliver(
+                &client,
+                &channel,
+                &ChatMessages::open(path.clone()),
+                outgoing,
+            )
+            .await
+            .unwrap();
+        }
+
+        let requests = requests.lock().unwrap();
+        assert!(requests[0]
+            .path()
+            .starts_with("/_matrix/client/v3/rooms/!room:example.com/send/m.room.message/awaria-"));
+        let edit = requests[1].json();
+        assert_eq!(edit["m.relates_to"]["rel_type"], "m.replace");
+        assert_eq!(edit["m.relates_to"]["event_id"], "$event1");
+        assert_eq!(
+            edit["m.new_content"]["body"]
+                .as_str()
+                .unwrap()
+                .lines()
+                .next(),
+            Some("Power outage rescheduled")
+        );
+        assert!(edit["body"].as_str().unwrap().starts_with("* "));
+        let reply = requests[2].json();
+        assert_eq!(
+            reply["m.relates_to"]["m.in_reply_to"]["event_id"],
+            "$event1"
+        );
+        assert!(reply["body"]
+            .as_str()
+            .unwrap()
+            .starts_with("Power outage cancelled"));
+        assert_ne!(requests[0].path(), requests[1].path());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
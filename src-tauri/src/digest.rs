@@ -0,0 +1,613 @@
+//! The daily digest: today's and tomorrow's alerts per location and source, for
+//! those who'd rather read one summary than get a notification for each alert. It
+//! is built at `Settings.digestTime` and delivered like notifications. The alert ids
+//! it saw are kept in `digest.json`, so the next one can mark what is new since.
+
+use crate::address::mentions_street;
+use crate::api_logic::{AlertSource, Settings, UnifiedAlert};
+use crate::notifier::{self, language, outage_name, Lang, Outgoing};
+use crate::providers::ALL_SOURCES;
+use crate::rules::{self, parse_time_of_day, EventKind, RuleAction};
+use crate::scheduler::AlertStore;
+use crate::settings_store::{read_json, store_json, SettingsCache};
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager};
+
+pub const DIGEST_FILE: &str = "digest.json";
+
+/// Event with the `RenderedDigest` each time one is built on schedule.
+pub const DIGEST_EVENT: &str = "digest";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Day {
+    Today,
+    Tomorrow,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DigestItem {
+    pub alert: UnifiedAlert,
+    /// The day it starts, or today for one already going on.
+    pub day: Day,
+    pub duration_minutes: Option<i64>,
+    /// Not in the previous digest.
+    pub is_new: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DigestSource {
+    pub source: AlertSource,
+    pub items: Vec<DigestItem>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DigestGroup {
+    /// The location's label; `None` for alerts elsewhere that a `digest_only` rule
+    /// sent here.
+    pub location: Option<String>,
+    pub sources: Vec<DigestSource>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Digest {
+    /// "YYYY-MM-DD".
+    pub date: String,
+    pub groups: Vec<DigestGroup>,
+}
+
+impl Digest {
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    fn items(&self) -> impl Iterator<Item = &DigestItem> {
+        self.groups
+            .iter()
+            .flat_map(|g| &g.sources)
+            .flat_map(|s| &s.items)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderedDigest {
+    pub digest: Digest,
+    pub text: String,
+    pub markdown: String,
+}
+
+/// Alerts of today and tomorrow that aren't over, with their day, by start.
+fn in_window(alerts: &[UnifiedAlert], now: NaiveDateTime) -> Vec<(&UnifiedAlert, Day)> {
+    let tomorrow = now
+        .date()
+        .succ_opt()
+        .unwrap_or(now.date())
+        .and_hms_opt(0, 0, 0)
+        .unwrap_or(now);
+    let day_after = tomorrow + Duration::days(1);
+    let mut window: Vec<(&UnifiedAlert, Day)> = alerts
+        .iter()
+        .filter(|a| !a.is_over(now))
+        .filter_map(|a| {
+            let start = a.start_time().filter(|&start| start < day_after)?;
+            Some((
+                a,
+                if start < tomorrow {
+                    Day::Today
+                } else {
+                    Day::Tomorrow
+                },
+            ))
+        })
+        .collect();
+    window.sort_by_key(|(a, _)| a.start_time());
+    window
+}
+
+fn by_source(items: Vec<DigestItem>) -> Vec<DigestSource> {
+    ALL_SOURCES
+        .into_iter()
+        .filter_map(|source| {
+            let items: Vec<DigestItem> = items
+                .iter()
+                .filter(|i| i.alert.source == source)
+                .cloned()
+                .collect();
+            (!items.is_empty()).then_some(DigestSource { source, items })
+        })
+        .collect()
+}
+
+/// The digest for `now`: alerts at the primary address and the saved locations, and
+/// alerts elsewhere that the rules mark `digest_only`. `seen` are the alert ids the
+/// previous digest saw, `None` if there was none (then nothing counts as new).
+pub fn build(
+    alerts: &[UnifiedAlert],
+    settings: &Settings,
+    seen: Option<&[String]>,
+    now: NaiveDateTime,
+) -> Digest {
+    let item = |alert: &UnifiedAlert, day: Day| DigestItem {
+        alert: alert.clone(),
+        day,
+        duration_minutes: alert
+            .start_time()
+            .zip(alert.end_time())
+            .map(|(start, end)| (end - start).num_minutes()),
+        is_new: seen.is_some_and(|seen| !seen.contains(&alert.id)),
+    };
+    let window = in_window(alerts, now);
+    let mentions = |alert: &UnifiedAlert, street: &str| {
+        alert
+            .message
+            .as_deref()
+            .is_some_and(|message| mentions_street(message, street))
+    };
+
+    let primary = (!settings.streetName.trim().is_empty()).then(|| settings.primary_location());
+    let locations: Vec<_> = primary
+        .into_iter()
+        .chain(settings.locations.iter().cloned())
+        .collect();
+    let mut groups: Vec<DigestGroup> = locations
+        .iter()
+        .filter_map(|location| {
+            let items = window
+                .iter()
+                .filter(|(alert, _)| mentions(alert, &location.streetName))
+                .map(|(alert, day)| item(alert, *day))
+                .collect();
+            let sources = by_source(items);
+            (!sources.is_empty()).then(|| DigestGroup {
+                location: Some(location.label.clone()),
+                sources,
+            })
+        })
+        .collect();
+
+    let elsewhere = window
+        .iter()
+        .filter(|(alert, _)| !locations.iter().any(|l| mentions(alert, &l.streetName)))
+        .filter(|(alert, _)| {
+            rules::evaluate(alert, EventKind::Added, settings, now).action == RuleAction::DigestOnly
+        })
+        .map(|(alert, day)| item(alert, *day))
+        .collect();
+    let sources = by_source(elsewhere);
+    if !sources.is_empty() {
+        groups.push(DigestGroup {
+            location: None,
+            sources,
+        });
+    }
+
+    Digest {
+        date: now.date().format("%Y-%m-%d").to_string(),
+        groups,
+    }
+}
+
+pub fn digest_title(date: &str, lang: Lang) -> String {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_or(date.to_string(), |d| d.format("%d.%m.%Y").to_string());
+    match lang {
+        Lang::Pl => format!("Podsumowanie dnia, {}", date),
+        Lang::En => format!("Daily digest, {}", date),
+    }
+}
+
+/// "45 min", "8 h", "1 h 30 min".
+pub fn format_duration(minutes: i64, lang: Lang) -> String {
+    let hours = match lang {
+        Lang::Pl => "godz.",
+        Lang::En => "h",
+    };
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{} min", m),
+        (h, 0) => format!("{} {}", h, hours),
+        (h, m) => format!("{} {} {} min", h, hours, m),
+    }
+}
+
+/// "Today 08:00–16:00 (8 h)", "Jutro od 09:00".
+fn item_when(item: &DigestItem, lang: Lang) -> String {
+    let day = match (item.day, lang) {
+        (Day::Today, Lang::Pl) => "Dziś",
+        (Day::Today, Lang::En) => "Today",
+        (Day::Tomorrow, Lang::Pl) => "Jutro",
+        (Day::Tomorrow, Lang::En) => "Tomorrow",
+    };
+    let Some(start) = item.alert.start_time() else {
+        return day.to_string();
+    };
+    let from = start.format("%H:%M");
+    let window = match item.alert.end_time() {
+        Some(end) if end.date() == start.date() => format!("{}–{}", from, end.format("%H:%M")),
+        Some(end) => format!("{} – {}", from, end.format("%d.%m %H:%M")),
+        None if lang == Lang::Pl => format!("od {}", from),
+        None => format!("from {}", from),
+    };
+    match item.duration_minutes {
+        Some(minutes) if minutes > 0 => {
+            format!("{} {} ({})", day, window, format_duration(minutes, lang))
+        }
+        _ => format!("{} {}", day, window),
+    }
+}
+
+fn item_text(item: &DigestItem) -> String {
+    let text = item
+        .alert
+        .message
+        .as_deref()
+        .or(item.alert.description.as_deref())
+        .unwrap_or_default();
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn group_name(group: &DigestGroup, lang: Lang) -> String {
+    match (&group.location, lang) {
+        (Some(label), _) => label.clone(),
+        (None, Lang::Pl) => "Inne alerty".to_string(),
+        (None, Lang::En) => "Other alerts".to_string(),
+    }
+}
+
+fn new_marker(lang: Lang) -> &'static str {
+    match lang {
+        Lang::Pl => "nowe",
+        Lang::En => "new",
+    }
+}
+
+fn empty_line(lang: Lang) -> &'static str {
+    match lang {
+        Lang::Pl => "Brak wyłączeń dziś i jutro.",
+        Lang::En => "No outages today or tomorrow.",
+    }
+}
+
+pub fn render_text(digest: &Digest, lang: Lang) -> String {
+    let mut out = vec![digest_title(&digest.date, lang)];
+    if digest.is_empty() {
+        out.push(empty_line(lang).to_string());
+    }
+    for group in &digest.groups {
+        out.push(String::new());
+        out.push(group_name(group, lang));
+        for source in &group.sources {
+            out.push(format!("{}:", outage_name(&source.source, lang)));
+            for item in &source.items {
+                let marker = if item.is_new {
+                    format!(" [{}]", new_marker(lang))
+                } else {
+                    String::new()
+                };
+                out.push(format!(
+                    "- {}{}: {}",
+                    item_when(item, lang),
+                    marker,
+                    item_text(item)
+                ));
+            }
+        }
+    }
+    out.join("\n")
+}
+
+pub fn render_markdown(digest: &Digest, lang: Lang) -> String {
+    let mut out = vec![format!("# {}", digest_title(&digest.date, lang))];
+    if digest.is_empty() {
+        out.push(String::new());
+        out.push(empty_line(lang).to_string());
+    }
+    for group in &digest.groups {
+        out.push(String::new());
+        out.push(format!("## {}", group_name(group, lang)));
+        for source in &group.sources {
+            out.push(String::new());
+            out.push(format!("### {}", outage_name(&source.source, lang)));
+            out.push(String::new());
+            for item in &source.items {
+                let marker = if item.is_new {
+                    format!(" _{}_", new_marker(lang))
+                } else {
+                    String::new()
+                };
+                out.push(format!(
+                    "- **{}**{}: {}",
+                    item_when(item, lang),
+                    marker,
+                    item_text(item)
+                ));
+            }
+        }
+    }
+    out.join("\n")
+}
+
+pub fn render(digest: Digest, lang: Lang) -> RenderedDigest {
+    RenderedDigest {
+        text: render_text(&digest, lang),
+        markdown: render_markdown(&digest, lang),
+        digest,
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+struct DigestState {
+    /// "YYYY-MM-DD" of the last digest built on schedule.
+    last_date: Option<String>,
+    /// Ids of the alerts current at the last digest.
+    seen: Option<Vec<String>>,
+}
+
+/// When the last digest went out and what it saw.
+#[derive(Default)]
+pub struct Digests {
+    state: Mutex<DigestState>,
+    path: Option<PathBuf>,
+}
+
+impl Digests {
+    pub fn open(path: PathBuf) -> Self {
+        Digests {
+            state: Mutex::new(read_json(&path)),
+            path: Some(path),
+        }
+    }
+
+    /// The alert ids the last digest saw.
+    pub fn seen(&self) -> Option<Vec<String>> {
+        self.state.lock().ok().and_then(|s| s.seen.clone())
+    }
+
+    /// Whether today's digest is due: `Settings.digestTime` has passed and none went
+    /// out today. One missed while the app was closed goes out at the next start.
+    pub fn is_due(&self, settings: &Settings, now: NaiveDateTime) -> bool {
+        let Some(time) = settings.digestTime.as_deref().and_then(parse_time_of_day) else {
+            return false;
+        };
+        let today = now.date().format("%Y-%m-%d").to_string();
+        now.time() >= time
+            && self
+                .state
+                .lock()
+                .is_ok_and(|s| s.last_date.as_deref() != Some(&today))
+    }
+
+    /// Remember that the digest for `date` went out while `alerts` were current.
+    pub fn record(&self, date: &str, alerts: &[UnifiedAlert]) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        *state = DigestState {
+            last_date: Some(date.to_string()),
+            seen: Some(alerts.iter().map(|a| a.id.clone()).collect()),
+        };
+        store_json(self.path.as_deref(), &*state, "digest state");
+    }
+}
+
+/// The digest as it would be built now, without marking it sent.
+pub fn preview(settings: &Settings, alerts: &[UnifiedAlert], digests: &Digests) -> RenderedDigest {
+    let digest = build(
+        alerts,
+        settings,
+        digests.seen().as_deref(),
+        Local::now().naive_local(),
+    );
+    render(digest, language(Some(settings)))
+}
+
+/// Build and deliver the digest when it is due. An empty one is announced with
+/// `DIGEST_EVENT` but not shown. Needs `Digests`, `Notifier`, `AlertStore` and
+/// `SettingsCache` managed.
+pub fn check(app: &AppHandle) {
+    let Ok(Some(settings)) = app.state::<SettingsCache>().get() else {
+        return;
+    };
+    let digests = app.state::<Digests>();
+    let now = Local::now().naive_local();
+    if !digests.is_due(&settings, now) {
+        return;
+    }
+    let alerts = app.state::<AlertStore>().snapshot(Some(&settings)).alerts;
+    let rendered = preview(&settings, &alerts, &digests);
+    digests.record(&rendered.digest.date, &alerts);
+    if let Err(e) = app.emit(DIGEST_EVENT, &rendered) {
+        log::warn!("Could not emit {}: {}", DIGEST_EVENT, e);
+    }
+    deliver(app, &settings, &rendered);
+}
+
+/// Send a digest through the notification channels.
+pub fn deliver(app: &AppHandle, settings: &Settings, rendered: &RenderedDigest) {
+    let Some(first) = rendered.digest.items().next() else {
+        return;
+    };
+    let lang = language(Some(settings));
+    let title = digest_title(&rendered.digest.date, lang);
+    let body = rendered.text.lines().skip(1).collect::<Vec<_>>().join("\n");
+    let outgoing = Outgoing {
+        line: title.clone(),
+        title,
+        body: body.trim().to_string(),
+        alert_id: first.alert.id.clone(),
+        action: RuleAction::Notify,
+        alert: None,
+        event: None,
+        previous_alert_id: None,
+    };
+    notifier::deliver(app, settings, vec![outgoing]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_logic::parse_alert_time;
+    use crate::rules::{NotificationRule, RuleCondition};
+    use crate::test_support::{alert, settings, temp_dir};
+
+    fn alerts() -> Vec<UnifiedAlert> {
+        let open_ended = |id, source, start, message| UnifiedAlert {
+            endDate: None,
+            ..alert(id, source, start, start, message)
+        };
+        vec![
+            alert(
+                "tauron-1",
+                AlertSource::Tauron,
+                "2026-03-13T09:00:00",
+                "2026-03-13T13:30:00",
+                "Kuźnicza 1-30",
+            ),
+            alert(
+                "water-2",
+                AlertSource::Water,
+                "2026-03-12T08:00:00",
+                "2026-03-12T16:00:00",
+                "Kuźnicza,  Ruska",
+            ),
+            alert(
+                "water-3",
+                AlertSource::Water,
+                "2026-03-12T06:00:00",
+                "2026-03-12T07:00:00",
+                "Kuźnicza 2",
+            ),
+            open_ended(
+                "water-4",
+                AlertSource::Water,
+                "2026-03-14T08:00:00",
+                "Kuźnicza 4",
+            ),
+            open_ended(
+                "water-5",
+                AlertSource::Water,
+                "2026-03-12T10:00:00",
+                "Legnicka 2",
+            ),
+            open_ended(
+                "fortum-6",
+                AlertSource::Fortum,
+                "2026-03-12T10:00:00",
+                "Legnicka 9",
+            ),
+        ]
+    }
+
+    fn now() -> NaiveDateTime {
+        parse_alert_time("2026-03-12T07:30:00").unwrap()
+    }
+
+    #[test]
+    fn test_build_groups_by_location_and_source() {
+        let mut s = settings();
+        let digest = build(&alerts(), &s, Some(&["tauron-1".to_string()]), now());
+        assert_eq!(digest.date, "2026-03-12");
+
+        // Over (water-3) and after tomorrow (water-4) are left out
+        let home = &digest.groups[0];
+        assert_eq!(home.location.as_deref(), Some("ul. Kuźnicza 25, Wrocław"));
+        let ids: Vec<(&str, Day, bool)> = home
+            .sources
+            .iter()
+            .flat_map(|s| &s.items)
+            .map(|i| (i.alert.id.as_str(), i.day, i.is_new))
+            .collect();
+        assert_eq!(
+            ids,
+            vec![
+                ("tauron-1", Day::Tomorrow, false),
+                ("water-2", Day::Today, true)
+            ]
+        );
+        assert_eq!(home.sources[0].items[0].duration_minutes, Some(270));
+
+        let plot = &digest.groups[1];
+        assert_eq!(plot.location.as_deref(), Some("Działka"));
+        assert_eq!(plot.sources[0].items[0].alert.id, "water-2");
+        assert_eq!(digest.groups.len(), 2);
+
+        // Alerts elsewhere come in through `digest_only` rules; no previous digest, nothing new
+        s.notificationRules.push(NotificationRule {
+            name: "fortum".to_string(),
+            when: RuleCondition {
+                sources: Some(vec!["fortum".to_string()]),
+                ..Default::default()
+            },
+            action: RuleAction::DigestOnly,
+        });
+        let digest = build(&alerts(), &s, None, now());
+        let other = digest.groups.last().unwrap();
+        assert_eq!(other.location, None);
+        assert_eq!(other.sources[0].items[0].alert.id, "fortum-6");
+        assert!(digest.items().all(|i| !i.is_new));
+    }
+
+    #[test]
+    fn test_render() {
+        let home_only = Settings {
+            locations: Vec::new(),
+            ..settings()
+        };
+        let digest = build(
+            &alerts(),
+            &home_only,
+            Some(&["tauron-1".to_string()]),
+            now(),
+        );
+        assert_eq!(
+            render_text(&digest, Lang::En),
+            "Daily digest, 12.03.2026\n\n\
+             ul. Kuźnicza 25, Wrocław\n\
+             Power outage:\n\
+             - Tomorrow 09:00–13:30 (4 h 30 min): Kuźnicza 1-30\n\
+             Water outage:\n\
+             - Today 08:00–16:00 (8 h) [new]: Kuźnicza, Ruska"
+        );
+        let markdown = render_markdown(&digest, Lang::Pl);
+        assert!(markdown.starts_with("# Podsumowanie dnia, 12.03.2026\n\n## ul. Kuźnicza 25, Wrocław\n\n### Wyłączenie prądu\n"));
+        assert!(markdown.contains("- **Dziś 08:00–16:00 (8 godz.)** _nowe_: Kuźnicza, Ruska"));
+
+        let empty = build(&[], &home_only, None, now());
+        assert_eq!(
+            render_text(&empty, Lang::Pl),
+            "Podsumowanie dnia, 12.03.2026\nBrak wyłączeń dziś i jutro."
+        );
+    }
+
+    #[test]
+    fn test_digest_schedule() {
+        let dir = temp_dir("digest");
+        let path = dir.join(DIGEST_FILE);
+        let at = |time: &str| parse_alert_time(time).unwrap();
+
+        let mut s = settings();
+        let digests = Digests::open(path.clone());
+        assert!(!digests.is_due(&s, at("2026-03-12T08:00:00")));
+        s.digestTime = Some("07:00".to_string());
+        assert!(!digests.is_due(&s, at("2026-03-12T06:59:00")));
+        assert!(digests.is_due(&s, at("2026-03-12T07:00:00")));
+
+        digests.record("2026-03-12", &alerts());
+        assert!(!digests.is_due(&s, at("2026-03-12T20:00:00")));
+        // Survives a restart, and tomorrow's is due again
+        let digests = Digests::open(path);
+        assert!(!digests.is_due(&s, at("2026-03-12T20:00:00")));
+        assert!(digests.is_due(&s, at("2026-03-13T07:05:00")));
+        assert_eq!(digests.seen().map(|seen| seen.len()), Some(6));
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,413 @@
+//! Email notification channel over SMTP, configured in `Settings.email`. The
+//! notifications delivered together (one poll's alerts, a digest) go out as one
+//! message with a plain text and an HTML part.
+
+use crate::api_logic::Settings;
+use crate::notifier::{alerts_title, language, Lang, Outgoing};
+use crate::rules::RuleAction;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailSecurity {
+    #[default]
+    Starttls,
+    /// TLS from the start ("SMTPS").
+    Tls,
+    /// Unencrypted, for a relay on the local network.
+    None,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[allow(non_snake_case)]
+pub struct EmailSettings {
+    pub host: String,
+    /// 587 for STARTTLS, 465 for TLS and 25 without encryption when unset.
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub security: EmailSecurity,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailErrorKind {
+    /// Missing or malformed settings, e.g. an invalid address.
+    Config,
+    Connection,
+    Tls,
+    Timeout,
+    /// The server refused the credentials.
+    Auth,
+    /// The server refused the message for good (5xx).
+    Rejected,
+    /// The server asked to try again later (4xx).
+    Temporary,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailError {
+    pub kind: EmailErrorKind,
+    pub message: String,
+    /// The SMTP reply code, when the server answered with an error.
+    pub code: Option<u16>,
+}
+
+impl EmailError {
+    pub fn config(message: impl Into<String>) -> Self {
+        EmailError {
+            kind: EmailErrorKind::Config,
+            message: message.into(),
+            code: None,
+        }
+    }
+
+    fn smtp(e: lettre::transport::smtp::Error) -> Self {
+        let code = e.status().map(u16::from);
+        let kind = match code {
+            Some(530 | 534 | 535 | 538) => EmailErrorKind::Auth,
+            Some(code) if code >= 500 => EmailErrorKind::Rejected,
+            Some(_) => EmailErrorKind::Temporary,
+            None if e.is_tls() => EmailErrorKind::Tls,
+            None if e.is_timeout() => EmailErrorKind::Timeout,
+            None => EmailErrorKind::Connection,
+        };
+        EmailError {
+            kind,
+            message: e.to_string(),
+            code,
+        }
+    }
+}
+
+impl fmt::Display for EmailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.code {
+            Some(code) => write!(f, "{} ({})", self.message, code),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Subject, plain text and HTML for `outgoing`.
+pub fn compose(outgoing: &[Outgoing], lang: Lang) -> (String, String, String) {
+    let subject = match outgoing {
+        [single] => single.title.clone(),
+        _ => alerts_title(outgoing.len(), lang),
+    };
+    let text = outgoing
+        .iter()
+        .map(|o| format!("{}\n{}", o.title, o.body))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let sections: String = outgoing
+        .iter()
+        .map(|o| {
+            let lines: Vec<String> = o.body.lines().map(escape_html).collect();
+            format!(
+                "<h3>{}</h3>\n<p>{}</p>\n",
+                escape_html(&o.title),
+                lines.join("<br>\n")
+            )
+        })
+        .collect();
+    let html = format!(
+        "<!DOCTYPE html>\n<html><body>\n{}</body></html>\n",
+        sections
+    );
+    (subject, text, html)
+}
+
+fn mailbox(address: &str) -> Result<Mailbox, EmailError> {
+    address
+        .trim()
+        .parse()
+        .map_err(|e| EmailError::config(format!("Invalid address {}: {}", address, e)))
+}
+
+pub fn message(
+    email: &EmailSettings,
+    outgoing: &[Outgoing],
+    lang: Lang,
+) -> Result<Message, EmailError> {
+    if email.to.is_empty() {
+        return Err(EmailError::config("No recipients"));
+    }
+    let (subject, text, html) = compose(outgoing, lang);
+    let mut builder = Message::builder()
+        .from(mailbox(&email.from)?)
+        .subject(subject);
+    for to in &email.to {
+        builder = builder.to(mailbox(to)?);
+    }
+    builder
+        .multipart(MultiPart::alternative_plain_html(text, html))
+        .map_err(|e| EmailError::config(e.to_string()))
+}
+
+fn transport(email: &EmailSettings) -> Result<AsyncSmtpTransport<Tokio1Executor>, EmailError> {
+    let host = email.host.trim();
+    if host.is_empty() {
+        return Err(EmailError::config("No SMTP host"));
+    }
+    let (builder, default_port) = match email.security {
+        EmailSecurity::Starttls => (
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host),
+            587,
+        ),
+        EmailSecurity::Tls => (AsyncSmtpTransport::<Tokio1Executor>::relay(host), 465),
+        EmailSecurity::None => (
+            Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(
+                host,
+            )),
+            25,
+        ),
+    };
+    let mut builder = builder
+        .map_err(EmailError::smtp)?
+        .port(email.port.unwrap_or(default_port))
+        .timeout(Some(TIMEOUT));
+    if let Some(username) = email.username.as_deref().filter(|u| !u.is_empty()) {
+        let password = email.password.clone().unwrap_or_default();
+        builder = builder.credentials(Credentials::new(username.to_string(), password));
+    }
+    Ok(builder.build())
+}
+
+/// Send `outgoing` as one message.
+pub async fn send(
+    email: &EmailSettings,
+    outgoing: &[Outgoing],
+    lang: Lang,
+) -> Result<(), EmailError> {
+    let message = message(email, outgoing, lang)?;
+    transport(email)?
+        .send(message)
+        .await
+        .map_err(EmailError::smtp)?;
+    Ok(())
+}
+
+/// A sample message, for trying the settings out.
+pub async fn send_test(email: &EmailSettings, lang: Lang) -> Result<(), EmailError> {
+    let body = match lang {
+        Lang::Pl => "Powiadomienia e-mail działają.",
+        Lang::En => "Email notifications work.",
+    };
+    let outgoing = Outgoing {
+        title: "AWARIA".to_string(),
+        body: body.to_string(),
+        line: "AWARIA".to_string(),
+        alert_id: String::new(),
+        action: RuleAction::Notify,
+        alert: None,
+        event: None,
+        previous_alert_id: None,
+    };
+    send(email, &[outgoing], lang).await
+}
+
+/// Email `outgoing` in the background, when email is set up.
+pub fn dispatch(settings: &Settings, outgoing: &[Outgoing]) {
+    let Some(email) = settings.email.clone() else {
+        return;
+    };
+    if outgoing.is_empty() {
+        return;
+    }
+    let lang = language(Some(settings));
+    let outgoing = outgoing.to_vec();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = send(&email, &outgoing, lang).await {
+            log::warn!("Email to {} failed: {}", email.to.join(", "), e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_logic::{AlertSource, UnifiedAlert};
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    fn outgoing(id: &str, title: &str, message: &str) -> Outgoing {
+        let alert = UnifiedAlert {
+            id: id.to_string(),
+            source: AlertSource::Tauron,
+            startDate: Some("2026-03-12T08:00:00".to_string()),
+            endDate: Some("2026-03-12T16:00:00".to_string()),
+            message: Some(message.to_string()),
+            description: None,
+        };
+        Outgoing::new(title.to_string(), &alert, RuleAction::Notify, Lang::En)
+    }
+
+    /// A local SMTP sink; with `auth_reply` it offers AUTH and answers it with that.
+    /// Returns its port and the messages it accepted.
+    async fn sink(auth_reply: Option<&'static str>) -> (u16, Arc<Mutex<Vec<String>>>) {
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = server.local_addr().unwrap().port();
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let received = messages.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = server.accept().await.unwrap();
+                let (read, mut write) = stream.into_split();
+                let mut lines = BufReader::new(read).lines();
+                write.write_all(b"220 sink ready\r\n").await.unwrap();
+                let mut data: Option<String> = None;
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if let Some(message) = data.as_mut() {
+                        if line == "." {
+                            received.lock().unwrap().push(data.take().unwrap());
+                            write.write_all(b"250 queued\r\n").await.unwrap();
+                        } else {
+                            message.push_str(&line);
+                            message.push('\n');
+                        }
+                        continue;
+                    }
+                    let command = line.to_uppercase();
+                    let reply = if command.starts_with("EHLO") {
+                        match auth_reply {
+                            Some(_) => "250-sink\r\n250 AUTH PLAIN LOGIN\r\n",
+                            None => "250 sink\r\n",
+                        }
+                    } else if command.starts_with("AUTH") {
+                        auth_reply.unwrap()
+                    } else if command.starts_with("DATA") {
+                        data = Some(String::new());
+                        "354 go ahead\r\n"
+                    } else if command.starts_with("QUIT") {
+                        write.write_all(b"221 bye\r\n").await.unwrap();
+                        break;
+                    } else {
+                        "250 ok\r\n"
+                    };
+                    write.write_all(reply.as_bytes()).await.unwrap();
+                }
+            }
+        });
+        (port, messages)
+    }
+
+    fn settings(port: u16) -> EmailSettings {
+        EmailSettings {
+            host: "127.0.0.1".to_string(),
+            port: Some(port),
+            security: EmailSecurity::None,
+            from: "AWARIA <awaria@example.com>".to_string(),
+            to: vec![
+                "grandma@example.com".to_string(),
+                "grandpa@example.com".to_string(),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_compose() {
+        let one = outgoing("a", "Power outage at your address", "Kuznicza 1-30 <i>");
+        let (subject, text, html) = compose(std::slice::from_ref(&one), Lang::En);
+        assert_eq!(subject, "Power outage at your address");
+        assert_eq!(
+            text,
+            "Power outage at your address\n12.03 08:00–16:00\nKuznicza 1-30 <i>"
+        );
+        assert!(html.contains("<h3>Power outage at your address</h3>\n<p>12.03 08:00–16:00<br>\nKuznicza 1-30 &lt;i&gt;</p>"));
+
+        let two = [one, outgoing("b", "Water outage", "Ruska 5")];
+        assert_eq!(compose(&two, Lang::Pl).0, "Alerty o wyłączeniach: 2");
+
+        let no_one = EmailSettings {
+            to: Vec::new(),
+            ..settings(25)
+        };
+        assert_eq!(
+            message(&no_one, &two, Lang::En).unwrap_err().kind,
+            EmailErrorKind::Config
+        );
+        let bad = EmailSettings {
+            from: "not an address".to_string(),
+            ..settings(25)
+        };
+        assert_eq!(
+            message(&bad, &two, Lang::En).unwrap_err().kind,
+            EmailErrorKind::Config
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_batches_into_one_message() {
+        let (port, messages) = sink(None).await;
+        let batch = [
+            outgoing("a", "Power outage at your address", "Kuznicza 1-30"),
+            outgoing("b", "Power outage rescheduled", "Kuznicza 2"),
+        ];
+        send(&settings(port), &batch, Lang::En).await.unwrap();
+
+        let messages = messages.lock().unwrap().clone();
+        assert_eq!(messages.len(), 1);
+        let message = &messages[0];
+        assert!(message.contains("Subject: Outage alerts: 2"));
+        assert!(message.contains("To: grandma@example.com, grandpa@example.com"));
+        assert!(message.contains("multipart/alternative"));
+        assert!(message.contains("Content-Type: text/plain"));
+        assert!(message.contains("Content-Type: text/html"));
+        assert!(message.contains("Power outage rescheduled"));
+    }
+
+    #[tokio::test]
+    async fn test_send_reports_structured_errors() {
+        let (port, _) = sink(Some("535 5.7.8 Authentication failed\r\n")).await;
+        let with_login = EmailSettings {
+            username: Some("awaria".to_string()),
+            password: Some("wrong".to_string()),
+            ..settings(port)
+        };
+        let error = send_test(&with_login, Lang::En).await.unwrap_err();
+        assert_eq!(error.kind, EmailErrorKind::Auth);
+        assert_eq!(error.code, Some(535));
+
+        // Nobody listening
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = closed.local_addr().unwrap().port();
+        drop(closed);
+        let error = send_test(&settings(port), Lang::En).await.unwrap_err();
+        assert_eq!(error.kind, EmailErrorKind::Connection);
+
+        let error = send_test(
+            &EmailSettings {
+                host: " ".to_string(),
+                ..settings(port)
+            },
+            Lang::En,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error.kind, EmailErrorKind::Config);
+    }
+}
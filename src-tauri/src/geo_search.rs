@@ -0,0 +1,322 @@
+use crate::api_logic::GeoItem;
+use std::collections::HashSet;
+
+/// Letters that have a Polish diacritic counterpart (ą, ć, ę, ł, ń, ó, ś, ź/ż).
+/// A query typed without diacritics can't be trusted past the first of these.
+const AMBIGUOUS_LETTERS: &str = "acelnosz";
+
+/// Shortest prefix we are willing to send to the geo endpoints.
+const MIN_QUERY_LEN: usize = 2;
+
+/// Larger localities in the Tauron area, preferred when several cities match equally well.
+const LARGE_LOCALITIES: &[&str] = &[
+    "wroclaw",
+    "krakow",
+    "katowice",
+    "opole",
+    "gliwice",
+    "czestochowa",
+    "sosnowiec",
+    "bielsko-biala",
+    "zabrze",
+    "bytom",
+    "rybnik",
+    "tychy",
+    "tarnow",
+    "walbrzych",
+    "legnica",
+    "chorzow",
+    "jelenia gora",
+    "dabrowa gornicza",
+    "jaworzno",
+    "lubin",
+];
+
+/// Street type prefixes that Tauron's street names don't always carry.
+pub const STREET_PREFIXES: &[&str] = &[
+    "ulica", "ul.", "ul", "aleja", "aleje", "al.", "plac", "pl.", "osiedle", "os.", "rondo",
+];
+
+/// Lowercase and strip Polish diacritics, e.g. "Wrocław" -> "wroclaw".
+pub fn fold_diacritics(input: &str) -> String {
+    input
+        .chars()
+        .flat_map(|c| c.to_lowercase())
+        .map(|c| match c {
+            'ą' => 'a',
+            'ć' => 'c',
+            'ę' => 'e',
+            'ł' => 'l',
+            'ń' => 'n',
+            'ó' => 'o',
+            'ś' => 's',
+            'ź' | 'ż' => 'z',
+            other => other,
+        })
+        .collect()
+}
+
+fn diacritic_variants(c: char) -> &'static [char] {
+    match c {
+        'a' => &['ą'],
+        'c' => &['ć'],
+        'e' => &['ę'],
+        'l' => &['ł'],
+        'n' => &['ń'],
+        'o' => &['ó'],
+        's' => &['ś'],
+        'z' => &['ź', 'ż'],
+        'A' => &['Ą'],
+        'C' => &['Ć'],
+        'E' => &['Ę'],
+        'L' => &['Ł'],
+        'N' => &['Ń'],
+        'O' => &['Ó'],
+        'S' => &['Ś'],
+        'Z' => &['Ź', 'Ż'],
+        _ => &[],
+    }
+}
+
+/// Build further queries that also match names written with diacritics.
+///
+/// Tauron's `partName` matching is diacritic-sensitive, so "Wroclaw" finds nothing.
+/// We send the prefix up to the first letter past `MIN_QUERY_LEN` that could carry a
+/// diacritic and filter the wider result locally. Ambiguous letters within the first
+/// `MIN_QUERY_LEN` can't be cut off, so the prefix is also sent with each of their
+/// diacritic forms ("Lod", "Łod", "Lód", "Łód" for "Lodz"). Empty when the original
+/// query is already safe.
+pub fn folded_queries(input: &str) -> Vec<String> {
+    let trimmed = input.trim();
+    let mut prefix = String::new();
+    for (i, c) in trimmed.chars().enumerate() {
+        if i >= MIN_QUERY_LEN && AMBIGUOUS_LETTERS.contains(c.to_ascii_lowercase()) {
+            break;
+        }
+        prefix.push(c);
+    }
+    if prefix.chars().count() < MIN_QUERY_LEN {
+        return Vec::new();
+    }
+    let mut queries = vec![String::new()];
+    for (i, c) in prefix.chars().enumerate() {
+        let variants = if i < MIN_QUERY_LEN {
+            diacritic_variants(c)
+        } else {
+            &[]
+        };
+        queries = queries
+            .iter()
+            .flat_map(|q| {
+                std::iter::once(c)
+                    .chain(variants.iter().copied())
+                    .map(move |v| format!("{}{}", q, v))
+            })
+            .collect();
+    }
+    queries.retain(|q| q != trimmed);
+    queries
+}
+
+/// `name` without a leading "ul."/"aleja"-style prefix. Only a whole word counts, so
+/// "Rondowa" stays as it is.
+pub fn strip_street_prefix(name: &str) -> &str {
+    for prefix in STREET_PREFIXES {
+        let len = prefix.len();
+        if !name.is_char_boundary(len) || !name[..len].eq_ignore_ascii_case(prefix) {
+            continue;
+        }
+        let rest = &name[len..];
+        if prefix.ends_with('.') || rest.is_empty() || rest.starts_with(char::is_whitespace) {
+            return rest.trim_start();
+        }
+    }
+    name
+}
+
+/// Lower is better: exact, prefix, word prefix, substring, anything else.
+fn match_tier(name: &str, query: &str) -> u8 {
+    if name == query {
+        0
+    } else if name.starts_with(query) {
+        1
+    } else if name
+        .split(|c: char| c.is_whitespace() || c == '-')
+        .any(|word| word.starts_with(query))
+    {
+        2
+    } else if name.contains(query) {
+        3
+    } else {
+        4
+    }
+}
+
+/// Confidence in 0..=1 that `name` is what the user meant by `query`.
+pub fn match_score(name: &str, query: &str) -> f64 {
+    let tier = match_tier(&fold_diacritics(name), &fold_diacritics(query.trim()));
+    match tier {
+        0 if name.trim().to_lowercase() == query.trim().to_lowercase() => 1.0,
+        0 => 0.95,
+        1 => 0.8,
+        2 => 0.6,
+        3 => 0.4,
+        _ => 0.2,
+    }
+}
+
+/// Like `match_score`, ignoring "ul."/"al."-style prefixes on either side.
+pub fn street_match_score(name: &str, query: &str) -> f64 {
+    match_score(strip_street_prefix(name), strip_street_prefix(query.trim()))
+}
+
+/// Merge the results of the original query with those of the widened `folded_queries`.
+/// Widened results only count when they match the query once diacritics are folded;
+/// duplicates are dropped by GAID.
+pub fn merge_results(query: &str, exact: Vec<GeoItem>, widened: Vec<GeoItem>) -> Vec<GeoItem> {
+    let folded_query = fold_diacritics(query.trim());
+    let mut seen = HashSet::new();
+    exact
+        .into_iter()
+        .chain(
+            widened
+                .into_iter()
+                .filter(|item| fold_diacritics(&item.Name).contains(&folded_query)),
+        )
+        .filter(|item| seen.insert(item.GAID))
+        .collect()
+}
+
+/// Order cities by match quality, then larger localities, then shorter names.
+pub fn rank_cities(query: &str, mut items: Vec<GeoItem>) -> Vec<GeoItem> {
+    let folded_query = fold_diacritics(query.trim());
+    items.sort_by_cached_key(|item| {
+        let folded = fold_diacritics(&item.Name);
+        (
+            match_tier(&folded, &folded_query),
+            item.Name != query.trim(),
+            !LARGE_LOCALITIES.contains(&folded.as_str()),
+            item.Name.chars().count(),
+            folded,
+        )
+    });
+    items
+}
+
+/// Order streets by match quality, ignoring "ul."/"al."-style prefixes.
+pub fn rank_streets(query: &str, mut items: Vec<GeoItem>) -> Vec<GeoItem> {
+    let folded_query = fold_diacritics(query.trim());
+    let folded_query = strip_street_prefix(&folded_query).to_string();
+    items.sort_by_cached_key(|item| {
+        let folded = fold_diacritics(&item.Name);
+        let bare = strip_street_prefix(&folded).to_string();
+        (
+            match_tier(&bare, &folded_query),
+            item.Name != query.trim(),
+            bare.chars().count(),
+            bare,
+        )
+    });
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geo(gaid: u64, name: &str) -> GeoItem {
+        GeoItem {
+            GAID: gaid,
+            Name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_fold_diacritics() {
+        assert_eq!(fold_diacritics("Wrocław"), "wroclaw");
+        assert_eq!(fold_diacritics("ŻÓŁĆ gęślą"), "zolc gesla");
+        assert_eq!(fold_diacritics("Kuźnicza"), "kuznicza");
+    }
+
+    #[test]
+    fn test_folded_queries() {
+        assert_eq!(folded_queries("Wroclaw"), ["Wr"]);
+        assert_eq!(folded_queries("Kuznicza"), ["Ku"]);
+        assert_eq!(folded_queries("Bytom"), ["Byt"]);
+        // Ambiguous letters among the first two are sent in every form
+        assert_eq!(folded_queries("Lodz"), ["Lod", "Lód", "Łod", "Łód"]);
+        assert_eq!(
+            folded_queries("Zory"),
+            ["Zóry", "Źory", "Źóry", "Żory", "Żóry"]
+        );
+        assert!(folded_queries("Slupsk").contains(&"Słup".to_string()));
+        assert!(folded_queries("Swidnica").contains(&"Świd".to_string()));
+        // Nothing ambiguous to widen
+        assert!(folded_queries("Rybik").is_empty());
+        assert!(folded_queries("W").is_empty());
+    }
+
+    #[test]
+    fn test_merge_dedupes_and_filters() {
+        let merged = merge_results(
+            "Wroclaw",
+            vec![geo(3, "Wroclaw Test")],
+            vec![
+                geo(1, "Wrocław"),
+                geo(2, "Wronki"),
+                geo(1, "Wrocław"),
+                geo(3, "Wroclaw Test"),
+            ],
+        );
+        assert_eq!(merged, vec![geo(3, "Wroclaw Test"), geo(1, "Wrocław")]);
+    }
+
+    #[test]
+    fn test_rank_cities_prefers_exact_then_large() {
+        let ranked = rank_cities(
+            "Wroc",
+            vec![
+                geo(3, "Nowa Wrocławska"),
+                geo(2, "Wrocanka"),
+                geo(1, "Wrocław"),
+            ],
+        );
+        let names: Vec<_> = ranked.iter().map(|g| g.Name.as_str()).collect();
+        assert_eq!(names, vec!["Wrocław", "Wrocanka", "Nowa Wrocławska"]);
+
+        let ranked = rank_cities("wrocław", vec![geo(2, "Wrocławice"), geo(1, "Wrocław")]);
+        assert_eq!(ranked[0].GAID, 1);
+    }
+
+    #[test]
+    fn test_match_score() {
+        assert_eq!(match_score("Wrocław", "Wrocław"), 1.0);
+        assert_eq!(match_score("Wrocław", "wroclaw"), 0.95);
+        assert_eq!(match_score("Wrocławice", "Wrocław"), 0.8);
+        assert_eq!(street_match_score("ul. Kuźnicza", "Kuznicza"), 0.95);
+        assert!(street_match_score("ul. Nowa Kuźnicza", "Kuźnicza") < 0.8);
+    }
+
+    #[test]
+    fn test_rank_streets_ignores_prefix() {
+        let ranked = rank_streets(
+            "Kuznicza",
+            vec![
+                geo(2, "ul. Nowa Kuźnicza"),
+                geo(3, "ul. Kuźniczaka"),
+                geo(1, "ul. Kuźnicza"),
+            ],
+        );
+        let ids: Vec<_> = ranked.iter().map(|g| g.GAID).collect();
+        assert_eq!(ids, vec![1, 3, 2]);
+    }
+
+    #[test]
+    fn test_strip_street_prefix_needs_whole_word() {
+        assert_eq!(strip_street_prefix("ul. Kuźnicza"), "Kuźnicza");
+        assert_eq!(strip_street_prefix("ul.Kuźnicza"), "Kuźnicza");
+        assert_eq!(strip_street_prefix("Rondo Reagana"), "Reagana");
+        assert_eq!(strip_street_prefix("Rondowa"), "Rondowa");
+        assert_eq!(street_match_score("Rondowa", "Rondowa"), 1.0);
+    }
+}
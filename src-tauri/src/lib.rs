@@ -1,16 +1,67 @@
+mod acknowledgements;
+mod address;
+mod alert_diff;
+mod alert_stream;
 mod api_logic;
-
-use tauri::command;
-use tauri::AppHandle;
-use tauri::Manager;
-use chrono::{Utc, SecondsFormat};
+mod bundle;
+mod chat;
+mod digest;
+mod email;
+mod geo_search;
+mod local_api;
+mod matrix;
+mod metrics;
+mod mqtt;
+mod notifier;
+#[cfg(feature = "postal")]
+mod postal;
+mod providers;
+mod reminders;
+mod rules;
+mod scheduler;
+mod settings_store;
+mod settings_sync;
+mod settings_watcher;
+mod telegram;
+#[cfg(feature = "teryt")]
+mod teryt;
+#[cfg(test)]
+mod test_support;
+mod validation;
+mod webhook;
+
+use acknowledgements::Acknowledgement;
 use api_logic::{
-    GeoItem, Settings, UnifiedAlert, BASE_URL, MPWIK_URL, FORTUM_URL, FORTUM_CITY_GUID, FORTUM_REGION_ID,
-    get_cities_query, get_streets_query, get_outages_query,
-    save_settings_to_path, load_settings_from_path
+    get_cities_query, get_outages_query, get_streets_query, AlertSource, GeoItem, Settings,
+    UnifiedAlert, BASE_URL,
 };
+use bundle::{ImportMode, ImportPreview};
+use chat::ChatMessages;
+use chrono::{SecondsFormat, Utc};
+use digest::{Digests, RenderedDigest};
+use email::{EmailError, EmailSettings};
+use local_api::LocalApi;
+use mqtt::Mqtt;
+use notifier::Notifier;
+use reminders::{PlannedReminder, Reminders};
+use rules::{DryRunReport, NotificationRule};
+use scheduler::{AlertSnapshot, AlertStore};
+use settings_store::{BackupInfo, RecoveryReport, SettingsCache};
 use std::fs;
 use std::path::PathBuf;
+use tauri::command;
+use tauri::AppHandle;
+use tauri::Manager;
+use tauri::State;
+use validation::ValidationReport;
+use webhook::{Webhook, WebhookDelivery};
+
+/// How many of the best-ranked cities `resolve_address` searches for the street.
+const RESOLVE_MAX_CITIES: usize = 3;
+
+/// Result cap for the offline TERYT prefix searches.
+#[cfg(feature = "teryt")]
+const TERYT_SEARCH_LIMIT: usize = 20;
 
 fn settings_path(app: &AppHandle) -> Result<PathBuf, String> {
     let data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
@@ -18,21 +69,14 @@ fn settings_path(app: &AppHandle) -> Result<PathBuf, String> {
     Ok(data_dir.join("settings.json"))
 }
 
-fn build_client() -> Result<reqwest::Client, String> {
-    reqwest::Client::builder()
-        .build()
-        .map_err(|e| e.to_string())
-}
-
-#[command]
-async fn lookup_city(city_name: String) -> Result<Vec<GeoItem>, String> {
-    let client = build_client()?;
-    let cache_bust = Utc::now().timestamp_millis().to_string();
-    let query = get_cities_query(&city_name, &cache_bust);
-
+async fn fetch_geo_items(
+    client: &reqwest::Client,
+    endpoint: &str,
+    query: &[(&'static str, String)],
+) -> Result<Vec<GeoItem>, String> {
     let res = client
-        .get(&format!("{}/enum/geo/cities", BASE_URL))
-        .query(&query)
+        .get(format!("{}/enum/geo/{}", BASE_URL, endpoint))
+        .query(query)
         .header("accept", "application/json")
         .header("x-requested-with", "XMLHttpRequest")
         .header("Referer", "https://www.tauron-dystrybucja.pl/wylaczenia")
@@ -47,61 +91,397 @@ async fn lookup_city(city_name: String) -> Result<Vec<GeoItem>, String> {
     res.json().await.map_err(|e| e.to_string())
 }
 
+/// Run the typed query and, when it could be missing diacritics, widened ones too.
+/// The widened queries are best-effort: their failure never hides the original results.
+async fn search_geo<F>(endpoint: &str, name: &str, build_query: F) -> Result<Vec<GeoItem>, String>
+where
+    F: Fn(&str) -> Vec<(&'static str, String)>,
+{
+    let client = providers::build_client()?;
+    let exact_query = build_query(name);
+
+    let prefixes = geo_search::folded_queries(name);
+    if prefixes.is_empty() {
+        return fetch_geo_items(&client, endpoint, &exact_query).await;
+    }
+
+    let mut widened_queries = tokio::task::JoinSet::new();
+    for prefix in prefixes {
+        let (client, endpoint, query) =
+            (client.clone(), endpoint.to_string(), build_query(&prefix));
+        widened_queries.spawn(async move { fetch_geo_items(&client, &endpoint, &query).await });
+    }
+    let exact = fetch_geo_items(&client, endpoint, &exact_query).await;
+    let mut widened = Vec::new();
+    let mut widened_ok = false;
+    while let Some(result) = widened_queries.join_next().await {
+        if let Ok(Ok(items)) = result {
+            widened.extend(items);
+            widened_ok = true;
+        }
+    }
+
+    match exact {
+        Err(e) if !widened_ok => Err(e),
+        exact => Ok(geo_search::merge_results(
+            name,
+            exact.unwrap_or_default(),
+            widened,
+        )),
+    }
+}
+
+/// Postal area for `postal_code`, if one was given and the build bundles the PNA index.
+#[cfg(feature = "postal")]
+fn postal_area(
+    postal_code: Option<&str>,
+    house_no: Option<&str>,
+) -> Result<Option<postal::PostalArea>, String> {
+    match postal_code {
+        Some(code) => Ok(postal::bundled()?.area(code, house_no)),
+        None => Ok(None),
+    }
+}
+
+/// Narrow lookup results to a postal area. Narrowing never empties the list: if the
+/// area doesn't know any of the results, they are returned unchanged.
+#[cfg(feature = "postal")]
+fn narrow_to_postal_area(
+    items: Vec<GeoItem>,
+    postal_code: Option<&str>,
+    streets: bool,
+) -> Result<Vec<GeoItem>, String> {
+    let Some(area) = postal_area(postal_code, None)? else {
+        return Ok(items);
+    };
+    let narrowed = if streets {
+        area.narrow_streets(items.clone())
+    } else {
+        area.narrow_cities(items.clone())
+    };
+    Ok(if narrowed.is_empty() { items } else { narrowed })
+}
+
+#[cfg(not(feature = "postal"))]
+fn narrow_to_postal_area(
+    items: Vec<GeoItem>,
+    _postal_code: Option<&str>,
+    _streets: bool,
+) -> Result<Vec<GeoItem>, String> {
+    Ok(items)
+}
+
 #[command]
-async fn lookup_street(street_name: String, city_gaid: u64) -> Result<Vec<GeoItem>, String> {
-    let client = build_client()?;
+async fn lookup_city(
+    city_name: String,
+    postal_code: Option<String>,
+) -> Result<Vec<GeoItem>, String> {
     let cache_bust = Utc::now().timestamp_millis().to_string();
-    let query = get_streets_query(&street_name, city_gaid, &cache_bust);
+    let items = search_geo("cities", &city_name, |name| {
+        get_cities_query(name, &cache_bust)
+    })
+    .await?;
+    let items = narrow_to_postal_area(items, postal_code.as_deref(), false)?;
+    Ok(geo_search::rank_cities(&city_name, items))
+}
 
-    let res = client
-        .get(&format!("{}/enum/geo/streets", BASE_URL))
-        .query(&query)
-        .header("accept", "application/json")
-        .header("x-requested-with", "XMLHttpRequest")
-        .header("Referer", "https://www.tauron-dystrybucja.pl/wylaczenia")
-        .send()
+#[command]
+async fn lookup_street(
+    street_name: String,
+    city_gaid: u64,
+    postal_code: Option<String>,
+) -> Result<Vec<GeoItem>, String> {
+    let cache_bust = Utc::now().timestamp_millis().to_string();
+    let items = search_geo("streets", &street_name, |name| {
+        get_streets_query(name, city_gaid, &cache_bust)
+    })
+    .await?;
+    let items = narrow_to_postal_area(items, postal_code.as_deref(), true)?;
+    Ok(geo_search::rank_streets(&street_name, items))
+}
+
+/// Localities and streets covered by a postal code, with the alert sources for that area.
+#[cfg(feature = "postal")]
+#[command]
+async fn lookup_postal_code(
+    postal_code: String,
+    house_no: Option<String>,
+) -> Result<postal::PostalArea, String> {
+    postal_area(Some(&postal_code), house_no.as_deref())?
+        .ok_or_else(|| format!("Unknown postal code: {}", postal_code))
+}
+
+/// Resolve free text like "Kuźnicza 25, Wrocław" into candidate locations, best first.
+#[command]
+async fn resolve_address(text: String) -> Result<Vec<address::AddressCandidate>, String> {
+    let parsed = address::parse_address(&text)?;
+    let cities = lookup_city(parsed.city.clone(), parsed.postal_code.clone()).await?;
+
+    let mut lookups = Vec::new();
+    for city in cities.into_iter().take(RESOLVE_MAX_CITIES) {
+        match lookup_street(parsed.street.clone(), city.GAID, parsed.postal_code.clone()).await {
+            Ok(streets) => lookups.push((city, streets)),
+            Err(e) => log::warn!("Street lookup in {} failed: {}", city.Name, e),
+        }
+    }
+
+    Ok(address::build_candidates(&parsed, lookups))
+}
+
+/// Offline locality search in the bundled TERYT index.
+#[cfg(feature = "teryt")]
+#[command]
+async fn teryt_search_cities(prefix: String) -> Result<Vec<teryt::TerytCity>, String> {
+    Ok(teryt::bundled()?.search_cities(&prefix, TERYT_SEARCH_LIMIT))
+}
+
+/// Offline street search within the TERYT locality `simc`.
+#[cfg(feature = "teryt")]
+#[command]
+async fn teryt_search_streets(
+    simc: String,
+    prefix: String,
+) -> Result<Vec<teryt::TerytStreet>, String> {
+    Ok(teryt::bundled()?.search_streets(&simc, &prefix, TERYT_SEARCH_LIMIT))
+}
+
+/// Map a TERYT locality (and optionally street) to Tauron GAIDs. Needs network.
+#[cfg(feature = "teryt")]
+#[command]
+async fn teryt_to_gaids(simc: String, sym_ul: Option<String>) -> Result<teryt::TerytGaids, String> {
+    let index = teryt::bundled()?;
+    let city = index
+        .city(&simc)
+        .ok_or_else(|| format!("Unknown TERYT locality: {}", simc))?;
+
+    let cities = lookup_city(city.name.clone(), None).await?;
+    let (city_gaid, city_ambiguous) = teryt::exact_geo_match(&cities, &city.name, false)
+        .ok_or_else(|| format!("Tauron does not know {}", city.name))?;
+
+    let Some(sym_ul) = sym_ul else {
+        return Ok(teryt::TerytGaids {
+            cityGAID: city_gaid,
+            streetGAID: None,
+            ambiguous: city_ambiguous,
+        });
+    };
+
+    let street = index
+        .street(&simc, &sym_ul)
+        .ok_or_else(|| format!("Unknown TERYT street: {}", sym_ul))?;
+    let streets = lookup_street(street.name.clone(), city_gaid, None).await?;
+    let (street_gaid, street_ambiguous) =
+        teryt::exact_geo_match(&streets, &street.full_name(), true).ok_or_else(|| {
+            format!(
+                "Tauron does not know {} in {}",
+                street.full_name(),
+                city.name
+            )
+        })?;
+
+    Ok(teryt::TerytGaids {
+        cityGAID: city_gaid,
+        streetGAID: Some(street_gaid),
+        ambiguous: city_ambiguous || street_ambiguous,
+    })
+}
+
+#[command]
+async fn save_settings(
+    app: AppHandle,
+    cache: State<'_, SettingsCache>,
+    settings: Settings,
+) -> Result<(), String> {
+    cache.save(&settings)?;
+    settings_watcher::sync_settings(&app);
+    Ok(())
+}
+
+/// Normalize the settings and check them against Tauron: GAIDs against the geo
+/// endpoints and a test outage query. Network failures only give warnings.
+#[command]
+async fn validate_settings(settings: Settings) -> Result<ValidationReport, String> {
+    let (normalized, mut issues) = validation::normalize(&settings);
+
+    if normalized.cityGAID != 0 && !normalized.cityName.is_empty() {
+        let cities = lookup_city(normalized.cityName.clone(), None).await;
+        issues.extend(validation::check_city(&normalized, &cities));
+    }
+    if normalized.cityGAID != 0 && normalized.streetGAID != 0 && !normalized.streetName.is_empty() {
+        let streets = lookup_street(normalized.streetName.clone(), normalized.cityGAID, None).await;
+        issues.extend(validation::check_street(&normalized, &streets));
+    }
+
+    let report = ValidationReport::new(normalized, issues);
+    if !report.valid {
+        return Ok(report);
+    }
+    let outages = providers::fetch_tauron(&report.settings.primary_location())
         .await
-        .map_err(|e| e.to_string())?;
+        .map(|alerts| alerts.len())
+        .map_err(|e| e.to_string());
+    let mut issues = report.issues;
+    issues.push(validation::check_outage_query(&outages));
+    Ok(ValidationReport::new(report.settings, issues))
+}
 
-    if !res.status().is_success() {
-        return Err(format!("HTTP error: {}", res.status()));
+/// Validate, then save the normalized settings unless there are errors.
+/// The report says which happened through `valid`.
+#[command]
+async fn save_validated_settings(
+    app: AppHandle,
+    cache: State<'_, SettingsCache>,
+    settings: Settings,
+) -> Result<ValidationReport, String> {
+    let report = validate_settings(settings).await?;
+    if report.valid {
+        cache.save(&report.settings)?;
+        settings_watcher::sync_settings(&app);
     }
+    Ok(report)
+}
 
-    res.json().await.map_err(|e| e.to_string())
+#[command]
+async fn load_settings(cache: State<'_, SettingsCache>) -> Result<Option<Settings>, String> {
+    cache.get()
+}
+
+#[command]
+async fn list_settings_backups(cache: State<'_, SettingsCache>) -> Result<Vec<BackupInfo>, String> {
+    Ok(settings_store::list_backups(cache.path()))
+}
+
+#[command]
+async fn restore_settings_backup(
+    app: AppHandle,
+    cache: State<'_, SettingsCache>,
+    id: String,
+) -> Result<Settings, String> {
+    let settings = settings_store::restore_backup(cache.path(), &id)?;
+    cache.invalidate();
+    settings_watcher::sync_settings(&app);
+    Ok(settings)
+}
+
+/// Reports an automatic recovery from a corrupt settings file, until dismissed.
+#[command]
+async fn settings_recovery_status(
+    cache: State<'_, SettingsCache>,
+) -> Result<Option<RecoveryReport>, String> {
+    // Loading is what triggers recovery, so make sure it has happened
+    cache.get().ok();
+    Ok(settings_store::recovery_report(cache.path()))
+}
+
+#[command]
+async fn dismiss_settings_recovery(cache: State<'_, SettingsCache>) -> Result<(), String> {
+    settings_store::dismiss_recovery_report(cache.path())
+}
+
+/// All settings and acknowledgements as a pretty-printed bundle for another device.
+#[command]
+async fn export_settings(cache: State<'_, SettingsCache>) -> Result<String, String> {
+    let settings = cache
+        .get()?
+        .ok_or_else(|| "Nothing to export yet. Please set up your location first.".to_string())?;
+    let acks = acknowledgements::load(&acknowledgements::acknowledgements_path(cache.path()))?;
+    let bundle = bundle::export(&settings, acks)?;
+    serde_json::to_string_pretty(&bundle).map_err(|e| e.to_string())
 }
 
+/// Preview importing a bundle; with `apply` the result is also saved, if the bundle is valid.
+/// The previous settings stay available as a backup.
 #[command]
-async fn save_settings(app: AppHandle, settings: Settings) -> Result<(), String> {
-    let path = settings_path(&app)?;
-    save_settings_to_path(&path, &settings)
+async fn import_settings(
+    app: AppHandle,
+    cache: State<'_, SettingsCache>,
+    bundle: String,
+    mode: ImportMode,
+    apply: bool,
+) -> Result<ImportPreview, String> {
+    let acks_path = acknowledgements::acknowledgements_path(cache.path());
+    let current = cache.get()?;
+    let current_acks = acknowledgements::load(&acks_path)?;
+    let preview = bundle::preview(&bundle, mode, current.as_ref(), &current_acks);
+
+    if apply {
+        if let Some(settings) = &preview.settings {
+            cache.save(settings)?;
+            acknowledgements::save(&acks_path, &preview.acknowledgements)?;
+            settings_watcher::sync_settings(&app);
+        }
+    }
+    Ok(preview)
+}
+
+/// Turn folder sync on (`Some(path)`) or off (`None`) and sync right away.
+#[command]
+async fn set_sync_folder(
+    app: AppHandle,
+    cache: State<'_, SettingsCache>,
+    folder: Option<String>,
+) -> Result<settings_sync::SyncStatus, String> {
+    let folder = folder
+        .map(|f| f.trim().to_string())
+        .filter(|f| !f.is_empty());
+    if let Some(folder) = &folder {
+        if !std::path::Path::new(folder).is_dir() {
+            return Err(format!("Folder not found: {}", folder));
+        }
+    }
+    let mut settings = cache
+        .get()?
+        .ok_or_else(|| "No settings configured. Please set up your location first.".to_string())?;
+    settings.syncFolder = folder.clone();
+    cache.save(&settings)?;
+    settings_watcher::sync_settings(&app);
+    Ok(settings_sync::status(cache.path(), folder.as_deref()))
+}
+
+#[command]
+async fn sync_status(cache: State<'_, SettingsCache>) -> Result<settings_sync::SyncStatus, String> {
+    let folder = cache.get()?.and_then(|s| s.syncFolder);
+    Ok(settings_sync::status(cache.path(), folder.as_deref()))
+}
+
+#[command]
+async fn acknowledge_alert(
+    cache: State<'_, SettingsCache>,
+    id: String,
+) -> Result<Vec<Acknowledgement>, String> {
+    acknowledgements::acknowledge(&acknowledgements::acknowledgements_path(cache.path()), &id)
 }
 
 #[command]
-async fn load_settings(app: AppHandle) -> Result<Option<Settings>, String> {
-    let path = settings_path(&app)?;
-    load_settings_from_path(&path)
+async fn list_acknowledgements(
+    cache: State<'_, SettingsCache>,
+) -> Result<Vec<Acknowledgement>, String> {
+    acknowledgements::load(&acknowledgements::acknowledgements_path(cache.path()))
 }
 
 #[command]
-async fn fetch_outages(app: AppHandle) -> Result<api_logic::OutageResponse, String> {
-    let path = settings_path(&app)?;
-    let settings = load_settings_from_path(&path)?
+async fn fetch_outages(
+    cache: State<'_, SettingsCache>,
+) -> Result<api_logic::OutageResponse, String> {
+    let settings = cache
+        .get()?
         .ok_or_else(|| "No settings configured. Please set up your location first.".to_string())?;
 
     let now = Utc::now();
     let from_date = now.to_rfc3339_opts(SecondsFormat::Millis, true);
     let cache_bust = now.timestamp_millis().to_string();
-    
+
     let query = get_outages_query(
         settings.cityGAID,
         settings.streetGAID,
         &settings.houseNo,
         &from_date,
-        &cache_bust
+        &cache_bust,
     );
 
-    let client = build_client()?;
-    let res = client.get(&format!("{}/outages/address", BASE_URL))
+    let client = providers::build_client()?;
+    let res = client
+        .get(format!("{}/outages/address", BASE_URL))
         .query(&query)
         .header("accept", "application/json")
         .header("x-requested-with", "XMLHttpRequest")
@@ -114,11 +494,13 @@ async fn fetch_outages(app: AppHandle) -> Result<api_logic::OutageResponse, Stri
         return Err(format!("HTTP error! status: {}", res.status()));
     }
 
-    let mut data = res.json::<api_logic::OutageResponse>()
+    let mut data = res
+        .json::<api_logic::OutageResponse>()
         .await
         .map_err(|e| e.to_string())?;
 
-    let query_str = query.iter()
+    let query_str = query
+        .iter()
         .map(|(k, v)| format!("{}={}", k, v))
         .collect::<Vec<_>>()
         .join("&");
@@ -129,98 +511,41 @@ async fn fetch_outages(app: AppHandle) -> Result<api_logic::OutageResponse, Stri
 
 #[command]
 async fn fetch_water_alerts() -> Result<Vec<UnifiedAlert>, String> {
-    let client = build_client()?;
-    let res = client
-        .post(MPWIK_URL)
-        .header("content-type", "application/x-www-form-urlencoded; charset=UTF-8")
-        .header("accept", "application/json")
-        .header("x-requested-with", "XMLHttpRequest")
-        .header("origin", "https://www.mpwik.wroc.pl")
-        .header("referer", "https://www.mpwik.wroc.pl/")
-        .body("action=all")
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
-
-    if !res.status().is_success() {
-        return Err(format!("MPWiK HTTP error: {}", res.status()));
-    }
-
-    let data: api_logic::MpwikResponse = res.json().await.map_err(|e| e.to_string())?;
-    let alerts: Vec<UnifiedAlert> = data
-        .failures
-        .unwrap_or_default()
-        .iter()
-        .map(|f| f.to_unified())
-        .collect();
-
-    Ok(alerts)
+    providers::fetch_water().await.map_err(|e| e.to_string())
 }
 
 #[command]
 async fn fetch_fortum_alerts() -> Result<Vec<UnifiedAlert>, String> {
-    let client = build_client()?;
-    
-    let planned_url = format!("{}?cityGuid={}&regionId={}&current=false", FORTUM_URL, FORTUM_CITY_GUID, FORTUM_REGION_ID);
-    let current_url = format!("{}?cityGuid={}&regionId={}&current=true", FORTUM_URL, FORTUM_CITY_GUID, FORTUM_REGION_ID);
-    
-    let (planned_res, current_res) = tokio::join!(
-        client.get(&planned_url).header("accept", "application/json").send(),
-        client.get(&current_url).header("accept", "application/json").send()
-    );
-
-    let planned_data: api_logic::FortumResponse = planned_res
-        .map_err(|e| e.to_string())?
-        .json()
-        .await
-        .map_err(|e| e.to_string())?;
-
-    let current_data: api_logic::FortumResponse = current_res
-        .map_err(|e| e.to_string())?
-        .json()
-        .await
-        .map_err(|e| e.to_string())?;
-
-    let mut seen_ids = std::collections::HashSet::new();
-    let mut all_points = planned_data.points;
-    all_points.extend(current_data.points);
-    
-    let alerts: Vec<UnifiedAlert> = all_points
-        .into_iter()
-        .filter(|p| seen_ids.insert(p.switch_off_id.clone()))
-        .map(|p| p.to_unified())
-        .collect();
-
-    Ok(alerts)
+    providers::fetch_fortum().await.map_err(|e| e.to_string())
 }
 
+/// Fetch every source now. Results also go to the background scheduler's store.
 #[command]
-async fn fetch_all_alerts(app: AppHandle) -> Result<Vec<UnifiedAlert>, String> {
-    let path = settings_path(&app)?;
-    let settings = load_settings_from_path(&path)?;
+async fn fetch_all_alerts(
+    app: AppHandle,
+    cache: State<'_, SettingsCache>,
+    store: State<'_, AlertStore>,
+) -> Result<Vec<UnifiedAlert>, String> {
+    let settings = cache.get()?;
 
     let mut all_alerts: Vec<UnifiedAlert> = Vec::new();
     let mut errors: Vec<String> = Vec::new();
+    let mut changes = Vec::new();
 
-    // Fetch Tauron alerts (requires settings)
-    if let Some(ref s) = settings {
-        match fetch_outages_internal(s).await {
-            Ok(tauron_alerts) => all_alerts.extend(tauron_alerts),
-            Err(e) => errors.push(format!("Tauron: {}", e)),
+    // Tauron needs an address
+    let sources: Vec<AlertSource> = providers::ALL_SOURCES
+        .into_iter()
+        .filter(|source| *source != AlertSource::Tauron || settings.is_some())
+        .collect();
+    for (source, fetched) in
+        scheduler::fetch_and_record(&store, &sources, settings.as_ref(), &mut changes).await
+    {
+        all_alerts.extend(fetched.alerts());
+        if let Some(e) = fetched.error() {
+            errors.push(format!("{}: {}", providers::source_label(&source), e));
         }
     }
-
-    // Fetch MPWiK water alerts (no settings needed)
-    match fetch_water_alerts().await {
-        Ok(water_alerts) => all_alerts.extend(water_alerts),
-        Err(e) => errors.push(format!("MPWiK: {}", e)),
-    }
-
-    // Fetch Fortum alerts (Wrocław only, no settings needed)
-    match fetch_fortum_alerts().await {
-        Ok(fortum_alerts) => all_alerts.extend(fortum_alerts),
-        Err(e) => errors.push(format!("Fortum: {}", e)),
-    }
+    scheduler::emit_snapshot(&app, &store, settings.as_ref(), &changes);
 
     if all_alerts.is_empty() && !errors.is_empty() {
         return Err(errors.join("; "));
@@ -229,73 +554,179 @@ async fn fetch_all_alerts(app: AppHandle) -> Result<Vec<UnifiedAlert>, String> {
     Ok(all_alerts)
 }
 
-/// Internal helper: fetch Tauron outages and convert to UnifiedAlert vec.
-async fn fetch_outages_internal(settings: &Settings) -> Result<Vec<UnifiedAlert>, String> {
-    let now = Utc::now();
-    let from_date = now.to_rfc3339_opts(SecondsFormat::Millis, true);
-    let cache_bust = now.timestamp_millis().to_string();
+/// Alerts and per-source fetch status as last polled in the background.
+#[command]
+async fn get_alert_snapshot(
+    cache: State<'_, SettingsCache>,
+    store: State<'_, AlertStore>,
+) -> Result<AlertSnapshot, String> {
+    Ok(store.snapshot(cache.get()?.as_ref()))
+}
 
-    let query = get_outages_query(
-        settings.cityGAID,
-        settings.streetGAID,
-        &settings.houseNo,
-        &from_date,
-        &cache_bust,
-    );
+/// Alert changes since `mark_alerts_seen`, for "new" and "changed" badges.
+#[command]
+async fn get_alert_changes(
+    store: State<'_, AlertStore>,
+) -> Result<Vec<alert_diff::AlertChange>, String> {
+    Ok(store.unseen())
+}
 
-    let client = build_client()?;
-    let res = client
-        .get(&format!("{}/outages/address", BASE_URL))
-        .query(&query)
-        .header("accept", "application/json")
-        .header("x-requested-with", "XMLHttpRequest")
-        .header("Referer", "https://www.tauron-dystrybucja.pl/wylaczenia")
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
+#[command]
+async fn mark_alerts_seen(store: State<'_, AlertStore>) -> Result<(), String> {
+    store.mark_seen()
+}
 
-    if !res.status().is_success() {
-        return Err(format!("HTTP error! status: {}", res.status()));
-    }
+/// A notification was clicked, on platforms that report it to the frontend.
+#[command]
+fn open_notified_alert(app: AppHandle, alert_id: String) {
+    notifier::open_alert(&app, alert_id);
+}
 
-    let data = res
-        .json::<api_logic::OutageResponse>()
-        .await
-        .map_err(|e| e.to_string())?;
+/// How the notification rules (`rules`, or the saved ones) would treat the current
+/// alerts if they all appeared now.
+#[command]
+async fn dry_run_rules(
+    rules: Option<Vec<NotificationRule>>,
+    cache: State<'_, SettingsCache>,
+    store: State<'_, AlertStore>,
+) -> Result<DryRunReport, String> {
+    let settings = cache.get()?.unwrap_or_default();
+    let alerts = store.snapshot(Some(&settings)).alerts;
+    let rules = rules.unwrap_or_else(|| settings.notificationRules.clone());
+    Ok(rules::dry_run(
+        &alerts,
+        &rules,
+        &settings,
+        chrono::Local::now().naive_local(),
+    ))
+}
 
-    let alerts: Vec<UnifiedAlert> = data
-        .OutageItems
-        .unwrap_or_default()
-        .iter()
-        .map(|item| item.to_unified())
-        .collect();
+/// Reminders still to come for the current alerts, earliest first.
+#[command]
+async fn get_reminders(
+    cache: State<'_, SettingsCache>,
+    store: State<'_, AlertStore>,
+    reminders: State<'_, Reminders>,
+) -> Result<Vec<PlannedReminder>, String> {
+    let Some(settings) = cache.get()? else {
+        return Ok(Vec::new());
+    };
+    let alerts = store.snapshot(Some(&settings)).alerts;
+    Ok(reminders.planned(&alerts, &settings, chrono::Local::now().naive_local()))
+}
 
-    Ok(alerts)
+/// The daily digest as it would go out now.
+#[command]
+async fn preview_digest(
+    cache: State<'_, SettingsCache>,
+    store: State<'_, AlertStore>,
+    digests: State<'_, Digests>,
+) -> Result<RenderedDigest, String> {
+    let settings = cache.get()?.unwrap_or_default();
+    let alerts = store.snapshot(Some(&settings)).alerts;
+    Ok(digest::preview(&settings, &alerts, &digests))
+}
+
+/// Send a sample notification through `webhook`.
+#[command]
+async fn test_webhook(webhook: Webhook) -> Result<WebhookDelivery, String> {
+    webhook::send_test(&webhook).await
+}
+
+/// Send a test message with `email`, or the saved email settings.
+#[command]
+async fn send_test_email(
+    email: Option<EmailSettings>,
+    cache: State<'_, SettingsCache>,
+) -> Result<(), EmailError> {
+    let settings = cache.get().ok().flatten();
+    let lang = notifier::language(settings.as_ref());
+    let email = email
+        .or_else(|| settings.and_then(|s| s.email))
+        .ok_or_else(|| EmailError::config("Email is not set up"))?;
+    email::send_test(&email, lang).await
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-  tauri::Builder::default()
-    .setup(|app| {
-      if cfg!(debug_assertions) {
-        app.handle().plugin(
-          tauri_plugin_log::Builder::default()
-            .level(log::LevelFilter::Info)
-            .build(),
-        )?;
-      }
-      Ok(())
-    })
-    .invoke_handler(tauri::generate_handler![
-        fetch_outages,
-        fetch_all_alerts,
-        fetch_water_alerts,
-        fetch_fortum_alerts,
-        lookup_city,
-        lookup_street,
-        save_settings,
-        load_settings
-    ])
-    .run(tauri::generate_context!())
-    .expect("error while running tauri application");
+    tauri::Builder::default()
+        .plugin(tauri_plugin_notification::init())
+        .setup(|app| {
+            if cfg!(debug_assertions) {
+                app.handle().plugin(
+                    tauri_plugin_log::Builder::default()
+                        .level(log::LevelFilter::Info)
+                        .build(),
+                )?;
+            }
+            let path = settings_path(app.handle())?;
+            app.manage(SettingsCache::new(path));
+            if let Err(e) = settings_watcher::start(app.handle().clone()) {
+                log::warn!("Settings file watcher not started: {}", e);
+            }
+            settings_watcher::start_sync(app.handle());
+            let alert_state =
+                settings_path(app.handle())?.with_file_name(scheduler::ALERT_STATE_FILE);
+            app.manage(AlertStore::open(alert_state));
+            let notified = settings_path(app.handle())?.with_file_name(notifier::NOTIFIED_FILE);
+            app.manage(Notifier::open(notified));
+            let reminders = settings_path(app.handle())?.with_file_name(reminders::REMINDERS_FILE);
+            app.manage(Reminders::open(reminders));
+            let digests = settings_path(app.handle())?.with_file_name(digest::DIGEST_FILE);
+            app.manage(Digests::open(digests));
+            let chat_messages =
+                settings_path(app.handle())?.with_file_name(chat::CHAT_MESSAGES_FILE);
+            app.manage(ChatMessages::open(chat_messages));
+            app.manage(Mqtt::default());
+            app.manage(LocalApi::default());
+            scheduler::start(app.handle().clone());
+            Ok(())
+        })
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::Focused(true) = event {
+                notifier::focus_notified_alert(window.app_handle());
+            }
+        })
+        .invoke_handler(tauri::generate_handler![
+            fetch_outages,
+            fetch_all_alerts,
+            fetch_water_alerts,
+            fetch_fortum_alerts,
+            lookup_city,
+            lookup_street,
+            resolve_address,
+            #[cfg(feature = "postal")]
+            lookup_postal_code,
+            #[cfg(feature = "teryt")]
+            teryt_search_cities,
+            #[cfg(feature = "teryt")]
+            teryt_search_streets,
+            #[cfg(feature = "teryt")]
+            teryt_to_gaids,
+            save_settings,
+            validate_settings,
+            save_validated_settings,
+            load_settings,
+            list_settings_backups,
+            restore_settings_backup,
+            settings_recovery_status,
+            dismiss_settings_recovery,
+            export_settings,
+            import_settings,
+            acknowledge_alert,
+            list_acknowledgements,
+            set_sync_folder,
+            sync_status,
+            get_alert_snapshot,
+            get_alert_changes,
+            mark_alerts_seen,
+            open_notified_alert,
+            get_reminders,
+            dry_run_rules,
+            preview_digest,
+            test_webhook,
+            send_test_email
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
 }
@@ -0,0 +1,922 @@
+//! Optional HTTP API for scripts and dashboards on the local network, configured in
+//! `Settings.localApi` and off by default. It serves what the background scheduler's
+//! `AlertStore` holds, the same data `fetch_all_alerts` records into:
+//!
+//! - `GET /alerts`, filtered by `source`, `location`, `status`, `from` and `to`
+//! - `GET /sources/status`
+//! - `GET /locations`
+//! - `/ws`, a WebSocket streaming alert changes, see `alert_stream`
+//! - `GET /metrics` for Prometheus, when `metrics` is on, see `metrics`
+//!
+//! With a token set, every request needs `Authorization: Bearer <token>` or `?token=`.
+//! Without one the server only listens on loopback addresses.
+
+use crate::address::mentions_street;
+use crate::alert_diff::AlertChange;
+use crate::alert_stream::{self, StreamMessage};
+use crate::api_logic::{parse_alert_time, AlertSource, SavedLocation, Settings, UnifiedAlert};
+use crate::metrics;
+use crate::providers::ALL_SOURCES;
+use crate::scheduler::{AlertSnapshot, AlertStore, SourceStatus};
+use crate::settings_store::SettingsCache;
+use axum::extract::{Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use subtle::ConstantTimeEq;
+use tauri::{AppHandle, Manager};
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, oneshot};
+
+pub const DEFAULT_BIND: &str = "127.0.0.1";
+
+pub const DEFAULT_PORT: u16 = 8765;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[allow(non_snake_case)]
+pub struct LocalApiSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Address to listen on, `DEFAULT_BIND` when unset; "0.0.0.0" for every interface.
+    #[serde(default)]
+    pub bind: Option<String>,
+    /// `DEFAULT_PORT` when unset.
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub token: Option<String>,
+    /// Serve Prometheus metrics at `/metrics`.
+    #[serde(default)]
+    pub metrics: bool,
+}
+
+impl LocalApiSettings {
+    fn bind(&self) -> &str {
+        self.bind
+            .as_deref()
+            .map(str::trim)
+            .filter(|b| !b.is_empty())
+            .unwrap_or(DEFAULT_BIND)
+    }
+
+    pub fn address(&self) -> String {
+        let bind = self.bind();
+        let port = self.port.unwrap_or(DEFAULT_PORT);
+        match bind.contains(':') {
+            true => format!("[{}]:{}", bind.trim_matches(['[', ']']), port),
+            false => format!("{}:{}", bind, port),
+        }
+    }
+
+    /// Whether only this machine can connect.
+    pub fn is_loopback(&self) -> bool {
+        let bind = self.bind().trim_matches(['[', ']']);
+        bind.eq_ignore_ascii_case("localhost")
+            || bind.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+    }
+
+    /// Serving the network needs a token; an open API is for this machine only.
+    pub fn check(&self) -> Result<(), String> {
+        let has_token = self.token.as_deref().is_some_and(|t| !t.is_empty());
+        match has_token || self.is_loopback() {
+            true => Ok(()),
+            false => Err(format!("Listening on {} needs a token", self.bind())),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertStatus {
+    Upcoming,
+    Ongoing,
+    /// Still listed after its end.
+    Overrunning,
+    Over,
+    Cancelled,
+}
+
+impl AlertStatus {
+    const ALL: [AlertStatus; 5] = [
+        AlertStatus::Upcoming,
+        AlertStatus::Ongoing,
+        AlertStatus::Overrunning,
+        AlertStatus::Over,
+        AlertStatus::Cancelled,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertStatus::Upcoming => "upcoming",
+            AlertStatus::Ongoing => "ongoing",
+            AlertStatus::Overrunning => "overrunning",
+            AlertStatus::Over => "over",
+            AlertStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// An alert as `/alerts` returns it.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiAlert {
+    #[serde(flatten)]
+    pub alert: UnifiedAlert,
+    pub status: AlertStatus,
+    /// Ids of the locations it mentions, as in `/locations`.
+    pub locations: Vec<String>,
+    /// The window before it was rescheduled.
+    pub previous_start_date: Option<String>,
+    pub previous_end_date: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiLocation {
+    pub id: String,
+    pub label: String,
+    /// The address in the main settings rather than a saved location.
+    pub primary: bool,
+    pub city_name: String,
+    pub street_name: String,
+    pub house_no: String,
+    /// Upcoming, ongoing and overrunning alerts there.
+    pub active_alerts: usize,
+    pub ongoing: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertsResponse {
+    pub generated_at: String,
+    pub count: usize,
+    pub alerts: Vec<ApiAlert>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourcesResponse {
+    pub generated_at: String,
+    pub sources: Vec<SourceStatus>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocationsResponse {
+    pub generated_at: String,
+    pub locations: Vec<ApiLocation>,
+}
+
+/// `/alerts` query parameters; lists are comma-separated.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct AlertQuery {
+    pub source: Option<String>,
+    /// Location ids or labels.
+    pub location: Option<String>,
+    pub status: Option<String>,
+    /// Alerts not over before this date ("2026-03-12") or time ("2026-03-12T08:00:00").
+    pub from: Option<String>,
+    /// Alerts starting no later than this date (through its end) or time.
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AlertFilter {
+    pub sources: Option<Vec<AlertSource>>,
+    pub locations: Option<Vec<String>>,
+    pub statuses: Option<Vec<AlertStatus>>,
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+}
+
+fn list(value: Option<&str>) -> Option<Vec<&str>> {
+    value.map(|v| {
+        v.split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .collect()
+    })
+}
+
+/// A date means its start, or with `end_of_day` the start of the next day.
+fn parse_bound(value: &str, end_of_day: bool) -> Result<NaiveDateTime, String> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let start = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+        return Ok(if end_of_day {
+            start + Duration::days(1)
+        } else {
+            start
+        });
+    }
+    parse_alert_time(value).ok_or_else(|| format!("Invalid date: {}", value))
+}
+
+impl AlertFilter {
+    /// Check `query` against the known sources, statuses and `settings`' locations.
+    pub fn parse(query: &AlertQuery, settings: &Settings) -> Result<Self, String> {
+        let sources = list(query.source.as_deref())
+            .map(|names| {
+                names
+                    .into_iter()
+                    .map(|name| {
+                        ALL_SOURCES
+                            .into_iter()
+                            .find(|s| s.as_str() == name)
+                            .ok_or_else(|| format!("Unknown source: {}", name))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+        let statuses = list(query.status.as_deref())
+            .map(|names| {
+                names
+                    .into_iter()
+                    .map(|name| {
+                        AlertStatus::ALL
+                            .into_iter()
+                            .find(|s| s.as_str() == name)
+                            .ok_or_else(|| format!("Unknown status: {}", name))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+        let known = settings.identified_locations();
+        let locations = list(query.location.as_deref())
+            .map(|names| {
+                names
+                    .into_iter()
+                    .map(|name| {
+                        known
+                            .iter()
+                            .find(|(id, location)| {
+                                id == name || location.label.eq_ignore_ascii_case(name)
+                            })
+                            .map(|(id, _)| id.clone())
+                            .ok_or_else(|| format!("Unknown location: {}", name))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+        Ok(AlertFilter {
+            sources,
+            locations,
+            statuses,
+            from: query
+                .from
+                .as_deref()
+                .map(|v| parse_bound(v, false))
+                .transpose()?,
+            to: query
+                .to
+                .as_deref()
+                .map(|v| parse_bound(v, true))
+                .transpose()?,
+        })
+    }
+
+    pub fn matches(&self, alert: &ApiAlert) -> bool {
+        let start = alert.alert.start_time();
+        let end = alert.alert.end_time();
+        self.sources
+            .as_ref()
+            .map_or(true, |s| s.contains(&alert.alert.source))
+            && self
+                .statuses
+                .as_ref()
+                .map_or(true, |s| s.contains(&alert.status))
+            && self.locations.as_ref().map_or(true, |ids| {
+                alert.locations.iter().any(|id| ids.contains(id))
+            })
+            && self
+                .from
+                .map_or(true, |from| end.map_or(true, |end| end >= from))
+            && self
+                .to
+                .map_or(true, |to| start.map_or(true, |start| start < to))
+    }
+}
+
+pub fn alert_status(
+    alert: &UnifiedAlert,
+    snapshot: &AlertSnapshot,
+    now: NaiveDateTime,
+) -> AlertStatus {
+    if snapshot.overrunning.contains(&alert.id) {
+        AlertStatus::Overrunning
+    } else if alert.is_over(now) {
+        AlertStatus::Over
+    } else if alert.start_time().is_some_and(|start| start <= now) {
+        AlertStatus::Ongoing
+    } else {
+        AlertStatus::Upcoming
+    }
+}
+
+/// The listed and the cancelled alerts, sorted by start.
+pub fn alerts(settings: &Settings, snapshot: &AlertSnapshot, now: NaiveDateTime) -> Vec<ApiAlert> {
+    let locations = settings.identified_locations();
+    let listed = snapshot
+        .alerts
+        .iter()
+        .map(|a| (a, alert_status(a, snapshot, now)));
+    let cancelled = snapshot
+        .cancelled
+        .iter()
+        .filter(|c| !snapshot.alerts.iter().any(|a| a.id == c.id))
+        .map(|a| (a, AlertStatus::Cancelled));
+    let mut alerts: Vec<ApiAlert> = listed
+        .chain(cancelled)
+        .map(|(alert, status)| api_alert(alert, status, &locations, snapshot))
+        .collect();
+    alerts.sort_by_key(|a| a.alert.start_time());
+    alerts
+}
+
+/// `alert` with `status`, the `locations` (from `Settings::identified_locations`) it
+/// mentions and its window before a reschedule.
+pub fn api_alert(
+    alert: &UnifiedAlert,
+    status: AlertStatus,
+    locations: &[(String, SavedLocation)],
+    snapshot: &AlertSnapshot,
+) -> ApiAlert {
+    let previous = snapshot.rescheduled.iter().find(|r| r.alert_id == alert.id);
+    ApiAlert {
+        alert: alert.clone(),
+        status,
+        locations: locations
+            .iter()
+            .filter(|(_, l)| {
+                alert
+                    .message
+                    .as_deref()
+                    .is_some_and(|m| mentions_street(m, &l.streetName))
+            })
+            .map(|(id, _)| id.clone())
+            .collect(),
+        previous_start_date: previous.and_then(|r| r.previous_start_date.clone()),
+        previous_end_date: previous.and_then(|r| r.previous_end_date.clone()),
+    }
+}
+
+pub fn locations(
+    settings: &Settings,
+    snapshot: &AlertSnapshot,
+    now: NaiveDateTime,
+) -> Vec<ApiLocation> {
+    let alerts = alerts(settings, snapshot, now);
+    let primary = !settings.streetName.trim().is_empty();
+    settings
+        .identified_locations()
+        .into_iter()
+        .enumerate()
+        .map(|(n, (id, location))| {
+            let here: Vec<&ApiAlert> = alerts
+                .iter()
+                .filter(|a| a.locations.contains(&id))
+                .collect();
+            let active = |s: AlertStatus| {
+                matches!(
+                    s,
+                    AlertStatus::Upcoming | AlertStatus::Ongoing | AlertStatus::Overrunning
+                )
+            };
+            ApiLocation {
+                active_alerts: here.iter().filter(|a| active(a.status)).count(),
+                ongoing: here
+                    .iter()
+                    .any(|a| matches!(a.status, AlertStatus::Ongoing | AlertStatus::Overrunning)),
+                id,
+                label: location.label,
+                primary: primary && n == 0,
+                city_name: location.cityName,
+                street_name: location.streetName,
+                house_no: location.houseNo,
+            }
+        })
+        .collect()
+}
+
+/// The settings and alerts to answer from.
+pub type Current = Arc<dyn Fn() -> (Settings, AlertSnapshot) + Send + Sync>;
+
+/// Stream messages waiting to go out to the WebSocket clients.
+const EVENT_BACKLOG: usize = 256;
+
+#[derive(Clone)]
+pub struct ApiState {
+    pub token: Option<String>,
+    pub current: Current,
+    /// What the WebSocket clients get after their snapshot.
+    pub events: broadcast::Sender<StreamMessage>,
+    pub metrics: bool,
+}
+
+pub fn generated_at() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+pub fn error(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(serde_json::json!({ "error": message.into() }))).into_response()
+}
+
+async fn authorize(State(state): State<ApiState>, request: Request, next: Next) -> Response {
+    let Some(token) = state.token.as_deref().filter(|t| !t.is_empty()) else {
+        return next.run(request).await;
+    };
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::to_string);
+    let query = Query::<BTreeMap<String, String>>::try_from_uri(request.uri())
+        .ok()
+        .and_then(|Query(mut params)| params.remove("token"));
+    // Constant time, so response times don't give away how much of a guess was right
+    if bearer
+        .or(query)
+        .is_some_and(|given| bool::from(given.as_bytes().ct_eq(token.as_bytes())))
+    {
+        next.run(request).await
+    } else {
+        error(StatusCode::UNAUTHORIZED, "Missing or wrong API token")
+    }
+}
+
+async fn get_alerts(State(state): State<ApiState>, Query(query): Query<AlertQuery>) -> Response {
+    let (settings, snapshot) = (state.current)();
+    let filter = match AlertFilter::parse(&query, &settings) {
+        Ok(filter) => filter,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e),
+    };
+    let alerts: Vec<ApiAlert> = alerts(&settings, &snapshot, Local::now().naive_local())
+        .into_iter()
+        .filter(|a| filter.matches(a))
+        .collect();
+    Json(AlertsResponse {
+        generated_at: generated_at(),
+        count: alerts.len(),
+        alerts,
+    })
+    .into_response()
+}
+
+async fn get_sources(State(state): State<ApiState>) -> Json<SourcesResponse> {
+    let (_, snapshot) = (state.current)();
+    Json(SourcesResponse {
+        generated_at: generated_at(),
+        sources: snapshot.sources,
+    })
+}
+
+async fn get_locations(State(state): State<ApiState>) -> Json<LocationsResponse> {
+    let (settings, snapshot) = (state.current)();
+    Json(LocationsResponse {
+        generated_at: generated_at(),
+        locations: locations(&settings, &snapshot, Local::now().naive_local()),
+    })
+}
+
+pub fn router(state: ApiState) -> Router {
+    let mut router = Router::new()
+        .route("/alerts", get(get_alerts))
+        .route("/sources/status", get(get_sources))
+        .route("/locations", get(get_locations))
+        .route("/ws", get(alert_stream::handler));
+    if state.metrics {
+        router = router.route("/metrics", get(metrics::handler));
+    }
+    router
+        .fallback(|| async { error(StatusCode::NOT_FOUND, "Not found") })
+        .layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .with_state(state)
+}
+
+/// Answer on `listener` until `shutdown` completes.
+pub async fn serve(
+    listener: TcpListener,
+    state: ApiState,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> std::io::Result<()> {
+    axum::serve(listener, router(state))
+        .with_graceful_shutdown(shutdown)
+        .await
+}
+
+struct Running {
+    settings: LocalApiSettings,
+    stop: oneshot::Sender<()>,
+    task: tauri::async_runtime::JoinHandle<()>,
+    /// Dropping it ends the WebSocket streams.
+    events: broadcast::Sender<StreamMessage>,
+}
+
+/// The server, while `Settings.localApi` is enabled, and the alert statuses the
+/// WebSocket clients last heard of.
+#[derive(Default)]
+pub struct LocalApi {
+    running: Mutex<Option<Running>>,
+    statuses: Mutex<BTreeMap<String, AlertStatus>>,
+}
+
+impl LocalApi {
+    fn broadcast(&self, messages: Vec<StreamMessage>) {
+        let Ok(running) = self.running.lock() else {
+            return;
+        };
+        if let Some(running) = running.as_ref() {
+            for message in messages {
+                // Fails only when nobody is listening
+                running.events.send(message).ok();
+            }
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        self.running.lock().is_ok_and(|running| running.is_some())
+    }
+}
+
+fn current(app: &AppHandle) -> Current {
+    let app = app.clone();
+    Arc::new(move || {
+        let settings = app
+            .state::<SettingsCache>()
+            .get()
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        let snapshot = app.state::<AlertStore>().snapshot(Some(&settings));
+        (settings, snapshot)
+    })
+}
+
+/// Start, restart or stop the server as `Settings.localApi` says, and tell the
+/// WebSocket clients about alerts that started or ended. Needs `SettingsCache`,
+/// `AlertStore` and `LocalApi` managed.
+pub fn update(app: &AppHandle) {
+    let wanted = app
+        .state::<SettingsCache>()
+        .get()
+        .ok()
+        .flatten()
+        .and_then(|s| s.localApi)
+        .filter(|api| api.enabled);
+    let server = app.state::<LocalApi>();
+    if let Ok(mut running) = server.running.lock() {
+        if running.as_ref().map(|r| &r.settings) != wanted.as_ref() {
+            let previous = running.take();
+            *running = match wanted {
+                Some(settings) => Some(start(app, settings, previous)),
+                None => {
+                    if let Some(previous) = previous {
+                        previous.stop.send(()).ok();
+                    }
+                    None
+                }
+            };
+        }
+    }
+
+    if server.is_running() {
+        let (settings, snapshot) = current(app)();
+        let alerts = alerts(&settings, &snapshot, Local::now().naive_local());
+        let transitions = match server.statuses.lock() {
+            Ok(mut statuses) => alert_stream::status_transitions(&mut statuses, &alerts),
+            Err(_) => Vec::new(),
+        };
+        server.broadcast(transitions);
+    }
+}
+
+fn start(app: &AppHandle, settings: LocalApiSettings, previous: Option<Running>) -> Running {
+    let (events, _) = broadcast::channel(EVENT_BACKLOG);
+    let state = ApiState {
+        token: settings.token.clone(),
+        current: current(app),
+        events: events.clone(),
+        metrics: settings.metrics,
+    };
+    let (stop, stopped) = oneshot::channel();
+    let address = settings.address();
+    let check = settings.check();
+    let task = tauri::async_runtime::spawn(async move {
+        // The previous server must let go of the port first
+        if let Some(Running { stop, task, .. }) = previous {
+            stop.send(()).ok();
+            task.await.ok();
+        }
+        if let Err(e) = check {
+            log::warn!("Local API not started: {}", e);
+            return;
+        }
+        let listener = match TcpListener::bind(&address).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                log::warn!("Local API could not listen on {}: {}", address, e);
+                return;
+            }
+        };
+        log::info!("Local API listening on {}", address);
+        if let Err(e) = serve(listener, state, async {
+            stopped.await.ok();
+        })
+        .await
+        {
+            log::warn!("Local API on {} stopped: {}", address, e);
+        }
+    });
+    Running {
+        settings,
+        stop,
+        task,
+        events,
+    }
+}
+
+/// Pass `changes` on to the WebSocket clients, when the server runs.
+pub fn broadcast_changes(app: &AppHandle, settings: Option<&Settings>, changes: &[AlertChange]) {
+    let server = app.state::<LocalApi>();
+    if !server.is_running() {
+        return;
+    }
+    let settings = settings.cloned().unwrap_or_default();
+    let snapshot = app.state::<AlertStore>().snapshot(Some(&settings));
+    server.broadcast(alert_stream::change_messages(
+        &settings,
+        &snapshot,
+        changes,
+        Local::now().naive_local(),
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scheduler::Reschedule;
+    use crate::test_support::{alert, settings};
+
+    fn snapshot() -> AlertSnapshot {
+        AlertSnapshot {
+            alerts: vec![
+                alert(
+                    "water-b",
+                    AlertSource::Water,
+                    "2026-03-13T09:00:00",
+                    "2026-03-13T12:00:00",
+                    "Kuźnicza 25",
+                ),
+                alert(
+                    "tauron-a",
+                    AlertSource::Tauron,
+                    "2026-03-12T08:00:00",
+                    "2026-03-12T16:00:00",
+                    "Kuźnicza 1-30",
+                ),
+                alert(
+                    "water-c",
+                    AlertSource::Water,
+                    "2026-03-12T06:00:00",
+                    "2026-03-12T08:00:00",
+                    "Ruska 5",
+                ),
+                alert(
+                    "fortum-d",
+                    AlertSource::Fortum,
+                    "2026-03-11T08:00:00",
+                    "2026-03-11T16:00:00",
+                    "Ruska, Kuźnicza",
+                ),
+            ],
+            sources: Vec::new(),
+            rescheduled: vec![Reschedule {
+                alert_id: "water-b".to_string(),
+                previous_start_date: Some("2026-03-12T09:00:00".to_string()),
+                previous_end_date: Some("2026-03-12T12:00:00".to_string()),
+                rescheduled_at: "2026-03-11T10:00:00Z".to_string(),
+            }],
+            cancelled: vec![alert(
+                "tauron-e",
+                AlertSource::Tauron,
+                "2026-03-14T08:00:00",
+                "2026-03-14T10:00:00",
+                "Ruska",
+            )],
+            overrunning: vec!["water-c".to_string()],
+        }
+    }
+
+    fn filtered(query: AlertQuery) -> Result<Vec<String>, String> {
+        let now = parse_alert_time("2026-03-12T10:00:00").unwrap();
+        let filter = AlertFilter::parse(&query, &settings())?;
+        Ok(alerts(&settings(), &snapshot(), now)
+            .into_iter()
+            .filter(|a| filter.matches(a))
+            .map(|a| a.alert.id)
+            .collect())
+    }
+
+    #[test]
+    fn test_alerts_and_filters() {
+        let now = parse_alert_time("2026-03-12T10:00:00").unwrap();
+        let all = alerts(&settings(), &snapshot(), now);
+        let summary: Vec<(&str, AlertStatus)> = all
+            .iter()
+            .map(|a| (a.alert.id.as_str(), a.status))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("fortum-d", AlertStatus::Over),
+                ("water-c", AlertStatus::Overrunning),
+                ("tauron-a", AlertStatus::Ongoing),
+                ("water-b", AlertStatus::Upcoming),
+                ("tauron-e", AlertStatus::Cancelled),
+            ]
+        );
+        assert_eq!(all[0].locations, ["ul_kuznicza_25_wroclaw", "dzialka"]);
+        assert_eq!(
+            all[3].previous_start_date.as_deref(),
+            Some("2026-03-12T09:00:00")
+        );
+
+        let query = |source: &str, location: &str, status: &str| AlertQuery {
+            source: (!source.is_empty()).then(|| source.to_string()),
+            location: (!location.is_empty()).then(|| location.to_string()),
+            status: (!status.is_empty()).then(|| status.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            filtered(query("water", "", "")).unwrap(),
+            ["water-c", "water-b"]
+        );
+        assert_eq!(
+            filtered(query("", "Działka", "")).unwrap(),
+            ["fortum-d", "water-c", "tauron-e"]
+        );
+        assert_eq!(
+            filtered(query("tauron,water", "dzialka", "overrunning, cancelled")).unwrap(),
+            ["water-c", "tauron-e"]
+        );
+        assert_eq!(
+            filtered(AlertQuery {
+                from: Some("2026-03-12T12:00:00".to_string()),
+                to: Some("2026-03-13".to_string()),
+                ..Default::default()
+            })
+            .unwrap(),
+            ["tauron-a", "water-b"]
+        );
+        assert_eq!(
+            filtered(query("gas", "", "")).unwrap_err(),
+            "Unknown source: gas"
+        );
+        assert_eq!(
+            filtered(query("", "Mars", "")).unwrap_err(),
+            "Unknown location: Mars"
+        );
+        assert!(filtered(AlertQuery {
+            from: Some("yesterday".to_string()),
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_locations() {
+        let now = parse_alert_time("2026-03-12T10:00:00").unwrap();
+        let locations = locations(&settings(), &snapshot(), now);
+        assert_eq!(locations.len(), 2);
+        assert!(locations[0].primary);
+        assert_eq!(locations[0].active_alerts, 2);
+        assert!(locations[0].ongoing);
+        assert_eq!(locations[1].id, "dzialka");
+        assert!(!locations[1].primary);
+        // The overrunning water outage; the cancelled one doesn't count
+        assert_eq!(locations[1].active_alerts, 1);
+    }
+
+    #[test]
+    fn test_open_api_stays_on_loopback() {
+        let api = |bind: &str, token: Option<&str>| LocalApiSettings {
+            enabled: true,
+            bind: Some(bind.to_string()),
+            token: token.map(str::to_string),
+            ..Default::default()
+        };
+        assert!(LocalApiSettings::default().check().is_ok());
+        assert!(api("localhost", None).check().is_ok());
+        assert!(api("::1", None).check().is_ok());
+        assert!(api("0.0.0.0", None).check().is_err());
+        assert!(api("192.168.1.20", Some("")).check().is_err());
+        assert!(api("0.0.0.0", Some("secret")).check().is_ok());
+        assert_eq!(api("::1", None).address(), "[::1]:8765");
+    }
+
+    #[tokio::test]
+    async fn test_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let state = ApiState {
+            token: Some("se/cret+1".to_string()),
+            current: Arc::new(|| (settings(), snapshot())),
+            events: broadcast::channel(1).0,
+            metrics: true,
+        };
+        let (stop, stopped) = oneshot::channel::<()>();
+        let server = tokio::spawn(serve(listener, state, async {
+            stopped.await.ok();
+        }));
+        let client = reqwest::Client::new();
+
+        let response = client.get(format!("{}/alerts", url)).send().await.unwrap();
+        assert_eq!(response.status(), 401);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["error"], "Missing or wrong API token");
+        let response = client
+            .get(format!("{}/alerts?token=se/cret+2", url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+
+        let body: serde_json::Value = client
+            .get(format!(
+                "{}/alerts?source=water&location=ul_kuznicza_25_wroclaw",
+                url
+            ))
+            .bearer_auth("se/cret+1")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(body["count"], 1);
+        let alert = &body["alerts"][0];
+        assert_eq!(alert["id"], "water-b");
+        assert_eq!(alert["source"], "water");
+        assert_eq!(alert["startDate"], "2026-03-13T09:00:00");
+        assert_eq!(alert["previousStartDate"], "2026-03-12T09:00:00");
+        assert_eq!(alert["locations"][0], "ul_kuznicza_25_wroclaw");
+
+        let response = client
+            .get(format!("{}/alerts?status=late&token=se%2Fcret%2B1", url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        let body: serde_json::Value = client
+            .get(format!("{}/locations?token=se%2Fcret%2B1", url))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(body["locations"][1]["label"], "Działka");
+        assert_eq!(body["locations"][1]["streetName"], "Ruska");
+
+        let response = client
+            .get(format!("{}/sources/status?token=se%2Fcret%2B1", url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let response = client
+            .get(format!("{}/nothing?token=se%2Fcret%2B1", url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+
+        let response = client
+            .get(format!("{}/metrics", url))
+            .bearer_auth("se/cret+1")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert!(response.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain"));
+        let body = response.text().await.unwrap();
+        // The overrunning one, whatever the clock says
+        assert!(body
+            .contains("awaria_location_active_alerts{location=\"dzialka\",source=\"water\"} 1\n"));
+
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+}
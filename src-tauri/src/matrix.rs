@@ -0,0 +1,130 @@
+//! Matrix client-server API calls for the chat channel (see `chat`): messages are
+//! sent to one room, edited with an `m.replace` relation and answered with a reply.
+
+use crate::email::escape_html;
+use crate::notifier::Outgoing;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[allow(non_snake_case)]
+pub struct MatrixSettings {
+    /// Base URL of the homeserver, e.g. "https://matrix.example.com".
+    pub homeserver: String,
+    /// Access token of the bot account, which must have joined the room.
+    pub accessToken: String,
+    /// Room id ("!abc:example.com").
+    pub roomId: String,
+}
+
+/// Makes transaction ids unique within a run; the time does so across runs.
+static TRANSACTIONS: AtomicU64 = AtomicU64::new(0);
+
+fn send_url(matrix: &MatrixSettings) -> Result<reqwest::Url, String> {
+    let mut url = reqwest::Url::parse(&matrix.homeserver)
+        .map_err(|e| format!("Invalid homeserver URL: {}", e))?;
+    let txn_id = format!(
+        "awaria-{}-{}",
+        chrono::Utc::now().timestamp_millis(),
+        TRANSACTIONS.fetch_add(1, Ordering::Relaxed)
+    );
+    url.path_segments_mut()
+        .map_err(|_| "Invalid homeserver URL".to_string())?
+        .pop_if_empty()
+        .extend([
+            "_matrix",
+            "client",
+            "v3",
+            "rooms",
+            &matrix.roomId,
+            "send",
+            "m.room.message",
+            &txn_id,
+        ]);
+    Ok(url)
+}
+
+/// An `m.text` message with the title in bold.
+pub fn content(outgoing: &Outgoing) -> Value {
+    let lines: Vec<String> = outgoing.body.lines().map(escape_html).collect();
+    let html = [format!("<b>{}</b>", escape_html(&outgoing.title))]
+        .into_iter()
+        .chain(lines)
+        .collect::<Vec<_>>()
+        .join("<br>");
+    json!({
+        "msgtype": "m.text",
+        "body": format!("{}\n{}", outgoing.title, outgoing.body).trim_end(),
+        "format": "org.matrix.custom.html",
+        "formatted_body": html,
+    })
+}
+
+/// Send `content` to the room; returns the event id.
+async fn put(
+    client: &reqwest::Client,
+    matrix: &MatrixSettings,
+    content: &Value,
+) -> Result<String, String> {
+    let response = client
+        .put(send_url(matrix)?)
+        .timeout(TIMEOUT)
+        .bearer_auth(&matrix.accessToken)
+        .json(content)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let status = response.status();
+    let body: Value = response.json().await.unwrap_or_default();
+    if !status.is_success() {
+        return Err(match body["error"].as_str() {
+            Some(error) => format!("Matrix answered {}: {}", status, error),
+            None => format!("Matrix answered {}", status),
+        });
+    }
+    body["event_id"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| "Matrix answered without an event id".to_string())
+}
+
+/// Post `outgoing`, as a reply to `reply_to` when given; returns the event id.
+pub async fn send(
+    client: &reqwest::Client,
+    matrix: &MatrixSettings,
+    outgoing: &Outgoing,
+    reply_to: Option<&str>,
+) -> Result<String, String> {
+    let mut content = content(outgoing);
+    if let Some(event_id) = reply_to {
+        content["m.relates_to"] = json!({ "m.in_reply_to": { "event_id": event_id } });
+    }
+    put(client, matrix, &content).await
+}
+
+/// Replace the message `event_id` by `outgoing`. Clients without edit support show
+/// the fallback, the new text marked with "* ".
+pub async fn edit(
+    client: &reqwest::Client,
+    matrix: &MatrixSettings,
+    event_id: &str,
+    outgoing: &Outgoing,
+) -> Result<(), String> {
+    let new_content = content(outgoing);
+    let mut content = new_content.clone();
+    content["body"] = json!(format!(
+        "* {}",
+        new_content["body"].as_str().unwrap_or_default()
+    ));
+    content["formatted_body"] = json!(format!(
+        "* {}",
+        new_content["formatted_body"].as_str().unwrap_or_default()
+    ));
+    content["m.new_content"] = new_content;
+    content["m.relates_to"] = json!({ "rel_type": "m.replace", "event_id": event_id });
+    put(client, matrix, &content).await.map(|_| ())
+}
@@ -0,0 +1,364 @@
+//! Prometheus metrics for the local API's `/metrics`, in the text exposition format.
+//! Alert gauges come from the same alerts as `/alerts`, fetch counters from what
+//! `AlertStore` recorded since the app started.
+
+use crate::api_logic::{AlertSource, Settings};
+use crate::local_api::{alerts, AlertStatus, ApiAlert, ApiState};
+use crate::providers::{ALL_SOURCES, ERROR_KINDS};
+use crate::scheduler::AlertSnapshot;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use std::fmt::{Display, Write};
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[derive(Default)]
+struct Exposition(String);
+
+impl Exposition {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        writeln!(self.0, "# HELP {} {}", name, help).ok();
+        writeln!(self.0, "# TYPE {} {}", name, kind).ok();
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl Display) {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(k, v)| format!("{}=\"{}\"", k, escape(v)))
+            .collect();
+        match labels.is_empty() {
+            true => writeln!(self.0, "{} {}", name, value).ok(),
+            false => writeln!(self.0, "{}{{{}}} {}", name, labels.join(","), value).ok(),
+        };
+    }
+}
+
+fn active(alert: &ApiAlert) -> bool {
+    matches!(
+        alert.status,
+        AlertStatus::Upcoming | AlertStatus::Ongoing | AlertStatus::Overrunning
+    )
+}
+
+fn ongoing(alert: &ApiAlert) -> bool {
+    matches!(
+        alert.status,
+        AlertStatus::Ongoing | AlertStatus::Overrunning
+    )
+}
+
+fn timestamp(time: NaiveDateTime) -> Option<i64> {
+    Local
+        .from_local_datetime(&time)
+        .earliest()
+        .map(|t| t.timestamp())
+}
+
+fn seconds(ms: u64) -> f64 {
+    ms as f64 / 1000.0
+}
+
+/// Every metric, with a sample for each source, location and error kind even when
+/// it is zero, so series don't appear out of nowhere.
+pub fn render(settings: &Settings, snapshot: &AlertSnapshot, now: NaiveDateTime) -> String {
+    let alerts = alerts(settings, snapshot, now);
+    let locations = settings.identified_locations();
+    let of = |source: AlertSource| alerts.iter().filter(move |a| a.alert.source == source);
+    let at = |source: AlertSource, id: &str| {
+        let id = id.to_string();
+        of(source).filter(move |a| a.locations.contains(&id))
+    };
+    let mut out = Exposition::default();
+
+    out.family(
+        "awaria_active_alerts",
+        "gauge",
+        "Upcoming, ongoing and overrunning alerts.",
+    );
+    for source in ALL_SOURCES {
+        out.sample(
+            "awaria_active_alerts",
+            &[("source", source.as_str())],
+            of(source).filter(|a| active(a)).count(),
+        );
+    }
+
+    let name = "awaria_location_active_alerts";
+    out.family(
+        name,
+        "gauge",
+        "Upcoming, ongoing and overrunning alerts mentioning a location.",
+    );
+    for (id, _) in &locations {
+        for source in ALL_SOURCES {
+            let count = at(source, id).filter(|a| active(a)).count();
+            out.sample(
+                name,
+                &[("location", id), ("source", source.as_str())],
+                count,
+            );
+        }
+    }
+
+    let name = "awaria_location_outage_ongoing";
+    out.family(
+        name,
+        "gauge",
+        "1 while an outage at a location is ongoing or overrunning.",
+    );
+    for (id, _) in &locations {
+        for source in ALL_SOURCES {
+            let value = at(source, id).any(ongoing) as u8;
+            out.sample(
+                name,
+                &[("location", id), ("source", source.as_str())],
+                value,
+            );
+        }
+    }
+
+    let name = "awaria_location_next_outage_start_timestamp_seconds";
+    out.family(
+        name,
+        "gauge",
+        "Start of the next upcoming outage at a location, when there is one.",
+    );
+    for (id, _) in &locations {
+        for source in ALL_SOURCES {
+            let next = at(source, id)
+                .filter(|a| a.status == AlertStatus::Upcoming)
+                .filter_map(|a| a.alert.start_time())
+                .min()
+                .and_then(timestamp);
+            if let Some(next) = next {
+                out.sample(name, &[("location", id), ("source", source.as_str())], next);
+            }
+        }
+    }
+
+    out.family(
+        "awaria_source_enabled",
+        "gauge",
+        "1 when the source is polled.",
+    );
+    for status in &snapshot.sources {
+        out.sample(
+            "awaria_source_enabled",
+            &[("source", status.source.as_str())],
+            status.enabled as u8,
+        );
+    }
+
+    let name = "awaria_source_last_success_timestamp_seconds";
+    out.family(name, "gauge", "End of the last successful fetch.");
+    for status in &snapshot.sources {
+        let last = status
+            .last_success_at
+            .as_deref()
+            .and_then(|at| DateTime::parse_from_rfc3339(at).ok());
+        if let Some(last) = last {
+            out.sample(
+                name,
+                &[("source", status.source.as_str())],
+                last.timestamp(),
+            );
+        }
+    }
+
+    let name = "awaria_last_fetch_duration_seconds";
+    out.family(name, "gauge", "How long the last fetch took.");
+    for status in &snapshot.sources {
+        if let Some(ms) = status.latency_ms {
+            out.sample(name, &[("source", status.source.as_str())], seconds(ms));
+        }
+    }
+
+    out.family(
+        "awaria_fetch_duration_seconds",
+        "summary",
+        "Time spent fetching a source.",
+    );
+    for status in &snapshot.sources {
+        let labels = [("source", status.source.as_str())];
+        out.sample(
+            "awaria_fetch_duration_seconds_sum",
+            &labels,
+            seconds(status.total_latency_ms),
+        );
+        out.sample(
+            "awaria_fetch_duration_seconds_count",
+            &labels,
+            status.fetch_count,
+        );
+    }
+
+    out.family(
+        "awaria_fetch_errors_total",
+        "counter",
+        "Failed fetches by kind of error.",
+    );
+    for status in &snapshot.sources {
+        for kind in ERROR_KINDS {
+            let count = status.error_counts.get(kind.as_str()).copied().unwrap_or(0);
+            out.sample(
+                "awaria_fetch_errors_total",
+                &[("source", status.source.as_str()), ("kind", kind.as_str())],
+                count,
+            );
+        }
+    }
+    out.0
+}
+
+pub async fn handler(State(state): State<ApiState>) -> Response {
+    let (settings, snapshot) = (state.current)();
+    let body = render(&settings, &snapshot, Local::now().naive_local());
+    ([(header::CONTENT_TYPE, CONTENT_TYPE)], body).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_logic::{parse_alert_time, SavedLocation};
+    use crate::scheduler::SourceStatus;
+    use crate::test_support::alert;
+    use std::collections::BTreeMap;
+
+    fn status(source: AlertSource) -> SourceStatus {
+        SourceStatus {
+            source,
+            enabled: true,
+            alert_count: 0,
+            last_fetch_at: None,
+            last_success_at: None,
+            last_error: None,
+            latency_ms: None,
+            next_fetch_at: None,
+            fetch_count: 0,
+            total_latency_ms: 0,
+            error_counts: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let settings = Settings {
+            cityName: "Wrocław".to_string(),
+            streetName: "Kuźnicza".to_string(),
+            houseNo: "25".to_string(),
+            locations: vec![SavedLocation {
+                label: "Działka \"Pod lasem\"".to_string(),
+                cityName: "Wrocław".to_string(),
+                streetName: "Ruska".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let water = SourceStatus {
+            last_success_at: Some("2026-03-12T09:00:00+00:00".to_string()),
+            latency_ms: Some(250),
+            fetch_count: 4,
+            total_latency_ms: 1500,
+            error_counts: BTreeMap::from([("timeout".to_string(), 1)]),
+            ..status(AlertSource::Water)
+        };
+        let snapshot = AlertSnapshot {
+            alerts: vec![
+                alert(
+                    "water-a",
+                    AlertSource::Water,
+                    "2026-03-12T08:00:00",
+                    "2026-03-12T16:00:00",
+                    "Kuźnicza 25",
+                ),
+                alert(
+                    "water-b",
+                    AlertSource::Water,
+                    "2026-03-14T08:00:00",
+                    "2026-03-14T12:00:00",
+                    "Kuźnicza, Ruska",
+                ),
+                alert(
+                    "water-c",
+                    AlertSource::Water,
+                    "2026-03-13T08:00:00",
+                    "2026-03-13T12:00:00",
+                    "Ruska 5",
+                ),
+                alert(
+                    "fortum-d",
+                    AlertSource::Fortum,
+                    "2026-03-11T08:00:00",
+                    "2026-03-11T12:00:00",
+                    "Ruska",
+                ),
+            ],
+            sources: vec![
+                status(AlertSource::Tauron),
+                water,
+                status(AlertSource::Fortum),
+            ],
+            rescheduled: Vec::new(),
+            cancelled: Vec::new(),
+            overrunning: Vec::new(),
+        };
+        let now = parse_alert_time("2026-03-12T10:00:00").unwrap();
+        let text = render(&settings, &snapshot, now);
+        let lines: Vec<&str> = text.lines().collect();
+        let has = |line: &str| lines.contains(&line);
+
+        assert!(has("# TYPE awaria_active_alerts gauge"));
+        assert!(has("awaria_active_alerts{source=\"water\"} 3"));
+        assert!(has("awaria_active_alerts{source=\"fortum\"} 0"));
+        let primary = "location=\"kuznicza_25_wroclaw\"";
+        assert!(has(&format!(
+            "awaria_location_active_alerts{{{},source=\"water\"}} 2",
+            primary
+        )));
+        assert!(has(&format!(
+            "awaria_location_outage_ongoing{{{},source=\"water\"}} 1",
+            primary
+        )));
+        assert!(has(
+            "awaria_location_outage_ongoing{location=\"dzialka_pod_lasem\",source=\"water\"} 0"
+        ));
+        let next = timestamp(parse_alert_time("2026-03-13T08:00:00").unwrap()).unwrap();
+        assert!(has(&format!(
+            "awaria_location_next_outage_start_timestamp_seconds{{location=\"dzialka_pod_lasem\",source=\"water\"}} {}",
+            next
+        )));
+        assert!(!text.contains("awaria_location_next_outage_start_timestamp_seconds{location=\"dzialka_pod_lasem\",source=\"fortum\"}"));
+        assert!(has(
+            "awaria_source_last_success_timestamp_seconds{source=\"water\"} 1773306000"
+        ));
+        assert!(has(
+            "awaria_last_fetch_duration_seconds{source=\"water\"} 0.25"
+        ));
+        assert!(has(
+            "awaria_fetch_duration_seconds_sum{source=\"water\"} 1.5"
+        ));
+        assert!(has(
+            "awaria_fetch_duration_seconds_count{source=\"water\"} 4"
+        ));
+        assert!(has(
+            "awaria_fetch_errors_total{source=\"water\",kind=\"timeout\"} 1"
+        ));
+        assert!(has(
+            "awaria_fetch_errors_total{source=\"tauron\",kind=\"decode\"} 0"
+        ));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a \"b\" \\ c\nd"), "a \\\"b\\\" \\\\ c\\nd");
+    }
+}
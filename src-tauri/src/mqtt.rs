@@ -0,0 +1,666 @@
+//! MQTT publisher for Home Assistant, configured in `Settings.mqtt`. Every location
+//! (the primary address and the saved ones) becomes a device with sensors for the
+//! alert count per source, the start and end of the current or next outage, and an
+//! "outage ongoing" binary sensor. Discovery configs and states are published
+//! retained and only when they change; after a reconnect all of them are sent again.
+//! `<baseTopic>/status` says "online", or "offline" through the last will.
+
+use crate::address::mentions_street;
+use crate::api_logic::{Settings, UnifiedAlert};
+use crate::providers::{source_label, ALL_SOURCES};
+use crate::scheduler::{source_enabled, AlertStore};
+use crate::settings_store::SettingsCache;
+use chrono::{Local, NaiveDateTime, TimeZone};
+use rumqttc::{AsyncClient, Event, LastWill, MqttOptions, Packet, QoS};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tokio::sync::{mpsc, watch};
+
+pub const DEFAULT_PORT: u16 = 1883;
+
+const DEFAULT_BASE_TOPIC: &str = "awaria";
+
+const DEFAULT_DISCOVERY_PREFIX: &str = "homeassistant";
+
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+/// Wait before reconnecting, doubled after every failed attempt up to
+/// `MAX_RECONNECT_DELAY`.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// Requests queued for the connection before `publish` waits.
+const CAPACITY: usize = 64;
+
+/// Makes client ids unique, so a restarted publisher doesn't take over the old one's
+/// session.
+static CONNECTIONS: AtomicU32 = AtomicU32::new(0);
+
+/// Retained messages by topic.
+pub type Messages = BTreeMap<String, String>;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[allow(non_snake_case)]
+pub struct MqttSettings {
+    pub host: String,
+    /// `DEFAULT_PORT` when unset.
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Topic the states and the availability go under, "awaria" when unset.
+    #[serde(default)]
+    pub baseTopic: Option<String>,
+    /// Home Assistant's discovery prefix, "homeassistant" when unset.
+    #[serde(default)]
+    pub discoveryPrefix: Option<String>,
+}
+
+impl MqttSettings {
+    fn base_topic(&self) -> &str {
+        topic_or(self.baseTopic.as_deref(), DEFAULT_BASE_TOPIC)
+    }
+
+    fn discovery_prefix(&self) -> &str {
+        topic_or(self.discoveryPrefix.as_deref(), DEFAULT_DISCOVERY_PREFIX)
+    }
+
+    pub fn availability_topic(&self) -> String {
+        format!("{}/status", self.base_topic())
+    }
+}
+
+fn topic_or<'a>(topic: Option<&'a str>, default: &'a str) -> &'a str {
+    topic
+        .map(|t| t.trim_matches('/'))
+        .filter(|t| !t.is_empty())
+        .unwrap_or(default)
+}
+
+/// What Home Assistant is told about a location.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct LocationState {
+    /// Used in topics and entity ids, see `Settings::identified_locations`.
+    #[serde(skip)]
+    pub id: String,
+    #[serde(skip)]
+    pub label: String,
+    /// Current and upcoming alerts per enabled source.
+    pub alerts: BTreeMap<String, usize>,
+    /// Of the outage that is ongoing or starts next, with the local offset.
+    pub next_start: Option<String>,
+    pub next_end: Option<String>,
+    pub ongoing: bool,
+    pub ongoing_sources: Vec<String>,
+}
+
+fn local_timestamp(time: NaiveDateTime) -> Option<String> {
+    Local
+        .from_local_datetime(&time)
+        .earliest()
+        .map(|t| t.to_rfc3339())
+}
+
+/// The state of every location. Alerts count until their end, or for as long as they
+/// are `overrunning`.
+pub fn location_states(
+    settings: &Settings,
+    alerts: &[UnifiedAlert],
+    overrunning: &[String],
+    now: NaiveDateTime,
+) -> Vec<LocationState> {
+    let mut states: Vec<LocationState> = Vec::new();
+    for (id, location) in settings.identified_locations() {
+        let here: Vec<&UnifiedAlert> = alerts
+            .iter()
+            .filter(|a| source_enabled(Some(settings), &a.source))
+            .filter(|a| !a.is_over(now) || overrunning.contains(&a.id))
+            .filter(|a| {
+                a.message
+                    .as_deref()
+                    .is_some_and(|m| mentions_street(m, &location.streetName))
+            })
+            .collect();
+        let counts = ALL_SOURCES
+            .iter()
+            .filter(|source| source_enabled(Some(settings), source))
+            .map(|source| {
+                (
+                    source.as_str().to_string(),
+                    here.iter().filter(|a| a.source == *source).count(),
+                )
+            })
+            .collect();
+        let next = here
+            .iter()
+            .filter_map(|a| Some((a.start_time()?, a)))
+            .min_by_key(|(start, _)| *start);
+        let ongoing_sources: Vec<String> = ALL_SOURCES
+            .iter()
+            .filter(|source| {
+                here.iter().any(|a| {
+                    a.source == **source && a.start_time().is_some_and(|start| start <= now)
+                })
+            })
+            .map(|source| source.as_str().to_string())
+            .collect();
+        states.push(LocationState {
+            id,
+            label: location.label,
+            alerts: counts,
+            next_start: next.and_then(|(start, _)| local_timestamp(start)),
+            next_end: next
+                .and_then(|(_, a)| a.end_time())
+                .and_then(local_timestamp),
+            ongoing: !ongoing_sources.is_empty(),
+            ongoing_sources,
+        });
+    }
+    states
+}
+
+/// Discovery configs and states for `states`.
+pub fn messages(mqtt: &MqttSettings, states: &[LocationState]) -> Messages {
+    let mut messages = Messages::new();
+    for state in states {
+        let state_topic = format!("{}/{}/state", mqtt.base_topic(), state.id);
+        let device = json!({
+            "identifiers": [format!("awaria_{}", state.id)],
+            "name": format!("AWARIA {}", state.label),
+            "manufacturer": "AWARIA",
+            "model": "Outage alerts",
+        });
+        let mut entity =
+            |component: &str, key: &str, name: String, mut config: serde_json::Value| {
+                config["name"] = json!(name);
+                config["unique_id"] = json!(format!("awaria_{}_{}", state.id, key));
+                config["state_topic"] = json!(state_topic);
+                config["availability_topic"] = json!(mqtt.availability_topic());
+                config["device"] = device.clone();
+                let topic = format!(
+                    "{}/{}/awaria_{}/{}/config",
+                    mqtt.discovery_prefix(),
+                    component,
+                    state.id,
+                    key
+                );
+                messages.insert(topic, config.to_string());
+            };
+
+        for source in ALL_SOURCES
+            .iter()
+            .filter(|s| state.alerts.contains_key(s.as_str()))
+        {
+            entity(
+                "sensor",
+                &format!("{}_alerts", source.as_str()),
+                format!("{} alerts", source_label(source)),
+                json!({
+                    "value_template": format!("{{{{ value_json.alerts.{} }}}}", source.as_str()),
+                    "state_class": "measurement",
+                    "icon": "mdi:alert-outline",
+                }),
+            );
+        }
+        for (key, name) in [
+            ("next_start", "Next outage start"),
+            ("next_end", "Next outage end"),
+        ] {
+            entity(
+                "sensor",
+                key,
+                name.to_string(),
+                json!({
+                    "value_template": format!("{{{{ value_json.{} }}}}", key),
+                    "device_class": "timestamp",
+                }),
+            );
+        }
+        entity(
+            "binary_sensor",
+            "ongoing",
+            "Outage ongoing".to_string(),
+            json!({
+                "value_template": "{{ 'ON' if value_json.ongoing else 'OFF' }}",
+                "device_class": "problem",
+                "json_attributes_topic": state_topic,
+                "json_attributes_template": "{{ {'sources': value_json.ongoing_sources} | tojson }}",
+            }),
+        );
+
+        let payload = serde_json::to_string(state).unwrap_or_default();
+        messages.insert(state_topic, payload);
+    }
+    messages
+}
+
+fn options(mqtt: &MqttSettings) -> MqttOptions {
+    let id = format!(
+        "awaria-{}-{}",
+        std::process::id(),
+        CONNECTIONS.fetch_add(1, Ordering::Relaxed)
+    );
+    let mut options = MqttOptions::new(id, mqtt.host.clone(), mqtt.port.unwrap_or(DEFAULT_PORT));
+    options.set_keep_alive(KEEP_ALIVE);
+    options.set_last_will(LastWill::new(
+        mqtt.availability_topic(),
+        "offline",
+        QoS::AtLeastOnce,
+        true,
+    ));
+    if let Some(username) = &mqtt.username {
+        options.set_credentials(username, mqtt.password.clone().unwrap_or_default());
+    }
+    options
+}
+
+async fn publish(client: &AsyncClient, topic: &str, payload: &str) {
+    if let Err(e) = client
+        .publish(topic, QoS::AtLeastOnce, true, payload.as_bytes().to_vec())
+        .await
+    {
+        log::warn!("Could not publish {}: {}", topic, e);
+    }
+}
+
+/// Publish what changed from `published` to `current`; topics no longer in `current`
+/// get an empty retained message, which removes them.
+async fn sync(client: &AsyncClient, published: &mut Messages, current: &Messages) {
+    for topic in published
+        .keys()
+        .filter(|topic| !current.contains_key(*topic))
+    {
+        publish(client, topic, "").await;
+    }
+    for (topic, payload) in current {
+        if published.get(topic) != Some(payload) {
+            publish(client, topic, payload).await;
+        }
+    }
+    *published = current.clone();
+}
+
+/// Keep `mqtt`'s broker up to date with `messages` until its sender is dropped, then
+/// say "offline" and disconnect.
+pub async fn run(mqtt: MqttSettings, mut messages: watch::Receiver<Messages>) {
+    let (client, mut eventloop) = AsyncClient::new(options(&mqtt), CAPACITY);
+    let (connected_tx, mut connected) = mpsc::unbounded_channel();
+    let host = mqtt.host.clone();
+    let poller = tokio::spawn(async move {
+        let mut delay = RECONNECT_DELAY;
+        loop {
+            match eventloop.poll().await {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    delay = RECONNECT_DELAY;
+                    connected_tx.send(true).ok();
+                }
+                Ok(Event::Outgoing(rumqttc::Outgoing::Disconnect)) => break,
+                Ok(_) => {}
+                Err(e) => {
+                    log::warn!("MQTT connection to {} failed: {}", host, e);
+                    connected_tx.send(false).ok();
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+                }
+            }
+        }
+    });
+
+    let availability = mqtt.availability_topic();
+    let mut online = false;
+    let mut published = Messages::new();
+    loop {
+        tokio::select! {
+            Some(now_online) = connected.recv() => {
+                online = now_online;
+                if online {
+                    // A new session: the broker may have lost the retained messages
+                    published.clear();
+                    publish(&client, &availability, "online").await;
+                    let current = messages.borrow().clone();
+                    sync(&client, &mut published, &current).await;
+                }
+            }
+            changed = messages.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+                let current = messages.borrow_and_update().clone();
+                if online {
+                    sync(&client, &mut published, &current).await;
+                }
+            }
+        }
+    }
+
+    if online {
+        publish(&client, &availability, "offline").await;
+    }
+    client.disconnect().await.ok();
+    let stop = poller.abort_handle();
+    if tokio::time::timeout(Duration::from_secs(5), poller)
+        .await
+        .is_err()
+    {
+        stop.abort();
+    }
+}
+
+struct Running {
+    settings: MqttSettings,
+    sender: watch::Sender<Messages>,
+    task: tauri::async_runtime::JoinHandle<()>,
+}
+
+/// The publisher, while `Settings.mqtt` is set.
+#[derive(Default)]
+pub struct Mqtt {
+    running: Mutex<Option<Running>>,
+}
+
+/// Start, restart or stop the publisher as `Settings.mqtt` says, and hand it the
+/// current states. Needs `SettingsCache`, `AlertStore` and `Mqtt` managed.
+pub fn update(app: &AppHandle) {
+    let settings = app.state::<SettingsCache>().get().ok().flatten();
+    let publisher = app.state::<Mqtt>();
+    let Ok(mut running) = publisher.running.lock() else {
+        return;
+    };
+    let Some((settings, mqtt)) = settings.and_then(|s| Some((s.clone(), s.mqtt?))) else {
+        // Dropping the sender stops the publisher
+        *running = None;
+        return;
+    };
+    let snapshot = app.state::<AlertStore>().snapshot(Some(&settings));
+    let states = location_states(
+        &settings,
+        &snapshot.alerts,
+        &snapshot.overrunning,
+        Local::now().naive_local(),
+    );
+    let current = messages(&mqtt, &states);
+    match running.as_ref() {
+        Some(publisher) if publisher.settings == mqtt => {
+            publisher.sender.send_if_modified(|messages| {
+                let modified = *messages != current;
+                *messages = current;
+                modified
+            });
+        }
+        _ => {
+            let previous = running.take();
+            let (sender, receiver) = watch::channel(current);
+            let settings = mqtt.clone();
+            let task = tauri::async_runtime::spawn(async move {
+                // Let the previous connection say "offline" first
+                if let Some(Running { sender, task, .. }) = previous {
+                    drop(sender);
+                    task.await.ok();
+                }
+                run(settings, receiver).await;
+            });
+            *running = Some(Running {
+                settings: mqtt,
+                sender,
+                task,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_logic::{parse_alert_time, AlertSource};
+    use crate::test_support::{self, alert};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::Notify;
+
+    /// The shared settings with Fortum disabled.
+    fn settings() -> Settings {
+        Settings {
+            enabledSources: Some(vec!["tauron".to_string(), "water".to_string()]),
+            ..test_support::settings()
+        }
+    }
+
+    fn alerts() -> Vec<UnifiedAlert> {
+        vec![
+            alert(
+                "tauron-a",
+                AlertSource::Tauron,
+                "2026-03-12T08:00:00",
+                "2026-03-12T16:00:00",
+                "Kuźnicza 1-30",
+            ),
+            alert(
+                "water-b",
+                AlertSource::Water,
+                "2026-03-13T09:00:00",
+                "2026-03-13T12:00:00",
+                "Kuźnicza 25",
+            ),
+            alert(
+                "water-c",
+                AlertSource::Water,
+                "2026-03-12T06:00:00",
+                "2026-03-12T08:00:00",
+                "Ruska 5",
+            ),
+            alert(
+                "fortum-d",
+                AlertSource::Fortum,
+                "2026-03-12T08:00:00",
+                "2026-03-12T16:00:00",
+                "Kuźnicza",
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_location_states() {
+        let now = parse_alert_time("2026-03-12T10:00:00").unwrap();
+        let states = location_states(&settings(), &alerts(), &[], now);
+        assert_eq!(states.len(), 2);
+
+        let home = &states[0];
+        assert_eq!(home.id, "ul_kuznicza_25_wroclaw");
+        // Fortum is disabled
+        assert_eq!(
+            home.alerts,
+            BTreeMap::from([("tauron".to_string(), 1), ("water".to_string(), 1)])
+        );
+        assert!(home.ongoing);
+        assert_eq!(home.ongoing_sources, ["tauron"]);
+        assert!(home
+            .next_start
+            .as_deref()
+            .unwrap()
+            .starts_with("2026-03-12T08:00:00"));
+        assert!(home
+            .next_end
+            .as_deref()
+            .unwrap()
+            .starts_with("2026-03-12T16:00:00"));
+
+        // The water outage there is over, unless it is still listed past its end
+        let plot = &states[1];
+        assert_eq!(plot.id, "dzialka");
+        assert_eq!(plot.alerts["water"], 0);
+        assert!(!plot.ongoing);
+        assert_eq!(plot.next_start, None);
+        let plot = &location_states(&settings(), &alerts(), &["water-c".to_string()], now)[1];
+        assert!(plot.ongoing);
+        assert_eq!(plot.alerts["water"], 1);
+    }
+
+    #[test]
+    fn test_messages() {
+        let mqtt = MqttSettings {
+            host: "localhost".to_string(),
+            discoveryPrefix: Some("ha/".to_string()),
+            ..Default::default()
+        };
+        let now = parse_alert_time("2026-03-12T10:00:00").unwrap();
+        let messages = messages(&mqtt, &location_states(&settings(), &alerts(), &[], now));
+        // Per location: two source counts, next start and end, ongoing, and the state
+        assert_eq!(messages.len(), 12);
+
+        let config: serde_json::Value =
+            serde_json::from_str(&messages["ha/sensor/awaria_dzialka/water_alerts/config"])
+                .unwrap();
+        assert_eq!(config["name"], "MPWiK alerts");
+        assert_eq!(config["unique_id"], "awaria_dzialka_water_alerts");
+        assert_eq!(config["state_topic"], "awaria/dzialka/state");
+        assert_eq!(config["value_template"], "{{ value_json.alerts.water }}");
+        assert_eq!(config["availability_topic"], "awaria/status");
+        assert_eq!(config["device"]["name"], "AWARIA Działka");
+        assert!(!messages.contains_key("ha/sensor/awaria_dzialka/fortum_alerts/config"));
+        let ongoing: serde_json::Value = serde_json::from_str(
+            &messages["ha/binary_sensor/awaria_ul_kuznicza_25_wroclaw/ongoing/config"],
+        )
+        .unwrap();
+        assert_eq!(ongoing["device_class"], "problem");
+
+        let state: serde_json::Value =
+            serde_json::from_str(&messages["awaria/dzialka/state"]).unwrap();
+        assert_eq!(state["alerts"]["tauron"], 0);
+        assert_eq!(state["next_start"], serde_json::Value::Null);
+        assert_eq!(state["ongoing"], false);
+    }
+
+    /// A publish the broker stand-in got: topic, payload and the retain flag.
+    type Received = Arc<std::sync::Mutex<Vec<(String, String, bool)>>>;
+
+    async fn read_packet(stream: &mut TcpStream) -> Option<(u8, Vec<u8>)> {
+        let header = stream.read_u8().await.ok()?;
+        let (mut length, mut shift) = (0usize, 0);
+        loop {
+            let byte = stream.read_u8().await.ok()?;
+            length |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).await.ok()?;
+        Some((header, body))
+    }
+
+    /// A local MQTT 3.1.1 broker stand-in recording the publishes it gets. Notifying
+    /// the returned `Notify` drops the current connection.
+    async fn broker() -> (u16, Received, Arc<Notify>) {
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = server.local_addr().unwrap().port();
+        let received: Received = Arc::default();
+        let kick = Arc::new(Notify::new());
+        let (seen, kicked) = (received.clone(), kick.clone());
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = server.accept().await.unwrap();
+                loop {
+                    let packet = tokio::select! {
+                        packet = read_packet(&mut stream) => packet,
+                        _ = kicked.notified() => None,
+                    };
+                    let Some((header, body)) = packet else {
+                        break;
+                    };
+                    let reply: Vec<u8> = match header >> 4 {
+                        1 => vec![0x20, 2, 0, 0],
+                        3 => {
+                            let topic_len = u16::from_be_bytes([body[0], body[1]]) as usize;
+                            let topic =
+                                String::from_utf8_lossy(&body[2..2 + topic_len]).to_string();
+                            let qos = (header >> 1) & 3;
+                            let payload_at = 2 + topic_len + if qos > 0 { 2 } else { 0 };
+                            let payload = String::from_utf8_lossy(&body[payload_at..]).to_string();
+                            seen.lock().unwrap().push((topic, payload, header & 1 == 1));
+                            match qos {
+                                0 => Vec::new(),
+                                _ => vec![0x40, 2, body[2 + topic_len], body[3 + topic_len]],
+                            }
+                        }
+                        12 => vec![0xd0, 0],
+                        14 => break,
+                        _ => Vec::new(),
+                    };
+                    stream.write_all(&reply).await.unwrap();
+                }
+            }
+        });
+        (port, received, kick)
+    }
+
+    async fn wait_for(received: &Received, count: usize) {
+        for _ in 0..100 {
+            if received.lock().unwrap().len() >= count {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!(
+            "Expected {} publishes, got {:?}",
+            count,
+            received.lock().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_publishes_retained_and_reconnects() {
+        let (port, received, kick) = broker().await;
+        let mqtt = MqttSettings {
+            host: "127.0.0.1".to_string(),
+            port: Some(port),
+            ..Default::default()
+        };
+        let now = parse_alert_time("2026-03-12T10:00:00").unwrap();
+        let states = location_states(&settings(), &alerts(), &[], now);
+        let (sender, receiver) = watch::channel(messages(&mqtt, &states));
+        let task = tokio::spawn(run(mqtt.clone(), receiver));
+
+        wait_for(&received, 13).await;
+        {
+            let received = received.lock().unwrap();
+            assert_eq!(
+                received[0],
+                ("awaria/status".to_string(), "online".to_string(), true)
+            );
+            assert!(received.iter().all(|(_, _, retain)| *retain));
+        }
+
+        // Only changes go out, and a location that is gone is removed
+        sender.send_replace(messages(&mqtt, &states[..1]));
+        wait_for(&received, 19).await;
+        {
+            let received = received.lock().unwrap();
+            assert!(received[13..]
+                .iter()
+                .all(|(topic, payload, _)| topic.contains("dzialka") && payload.is_empty()));
+        }
+
+        // After a reconnect, everything is sent again
+        kick.notify_one();
+        wait_for(&received, 26).await;
+        assert_eq!(received.lock().unwrap()[19].1, "online");
+
+        drop(sender);
+        task.await.unwrap();
+        wait_for(&received, 27).await;
+        let received = received.lock().unwrap();
+        assert_eq!(
+            received.last().unwrap(),
+            &("awaria/status".to_string(), "offline".to_string(), true)
+        );
+    }
+}
@@ -0,0 +1,786 @@
+//! Native notifications for alert changes, as the notification rules decide (by default
+//! new, rescheduled, cancelled and overrunning alerts at the configured address). Every event is announced at most once,
+//! also across restarts; the keys already announced are kept in `notified-alerts.json`
+//! next to `settings.json`, and notifications held for quiet hours in
+//! `held-notifications.json`.
+
+use crate::address::mentions_street;
+use crate::alert_diff::{AlertChange, ChangeKind};
+use crate::api_logic::{alert_id, AlertSource, Settings, UnifiedAlert};
+use crate::rules::{self, EventKind, RuleAction};
+use crate::scheduler::source_enabled;
+use crate::settings_store::{read_json, store_json, SettingsCache};
+use crate::{chat, email, webhook};
+use chrono::{DateTime, Duration, Local, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::plugin::PermissionState;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_notification::NotificationExt;
+
+pub const NOTIFIED_FILE: &str = "notified-alerts.json";
+
+/// Notifications waiting for quiet hours to end.
+pub const HELD_FILE: &str = "held-notifications.json";
+
+/// Event asking the frontend to scroll to and highlight an alert, by id.
+pub const FOCUS_ALERT_EVENT: &str = "focus-alert";
+
+/// Notified keys are forgotten after this long; by then the alert is long over.
+const KEEP_DAYS: i64 = 30;
+
+/// More notifications than this at once get one summary instead.
+const MAX_SEPARATE: usize = 3;
+
+/// A pending focus older than this is dropped: the window was focused for some other
+/// reason, not from the notification.
+const FOCUS_MINUTES: i64 = 2;
+
+/// Longest message text shown in a notification body.
+const MAX_BODY_CHARS: usize = 200;
+
+#[cfg(target_os = "linux")]
+const ALERT_SOUND: &str = "message-new-instant";
+#[cfg(not(target_os = "linux"))]
+const ALERT_SOUND: &str = "Default";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    Pl,
+    En,
+}
+
+/// The notification language: `Settings.language`, or for "system" the OS locale.
+pub fn language(settings: Option<&Settings>) -> Lang {
+    match settings.and_then(|s| s.language.as_deref()) {
+        Some("pl") => Lang::Pl,
+        Some("en") => Lang::En,
+        _ => {
+            let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+                .iter()
+                .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+                .unwrap_or_default();
+            if locale.starts_with("pl") {
+                Lang::Pl
+            } else {
+                Lang::En
+            }
+        }
+    }
+}
+
+/// Whether the alert mentions the street of the primary address or of a saved
+/// location, the same addresses the digest covers.
+pub fn affects_address(alert: &UnifiedAlert, settings: &Settings) -> bool {
+    let Some(message) = alert.message.as_deref() else {
+        return false;
+    };
+    settings
+        .identified_locations()
+        .iter()
+        .any(|(_, location)| mentions_street(message, &location.streetName))
+}
+
+pub fn event_kind(change: ChangeKind) -> EventKind {
+    match change {
+        ChangeKind::Added => EventKind::Added,
+        ChangeKind::TimeChanged => EventKind::TimeChanged,
+        ChangeKind::TextChanged => EventKind::TextChanged,
+        ChangeKind::Removed => EventKind::Removed,
+        ChangeKind::Cancelled => EventKind::Cancelled,
+        ChangeKind::Overrunning => EventKind::Overrunning,
+    }
+}
+
+/// Key under which an event counts as announced. New alerts use the alert id; other
+/// events also hash the alert's contents, so a second move notifies again.
+fn notified_key(kind: EventKind, alert: &UnifiedAlert) -> String {
+    match kind {
+        EventKind::Added => alert.id.clone(),
+        _ => format!(
+            "{}:{}",
+            kind.as_str(),
+            alert_id(
+                &alert.source,
+                &[
+                    Some(&alert.id),
+                    alert.startDate.as_deref(),
+                    alert.endDate.as_deref(),
+                    alert.message.as_deref()
+                ],
+            )
+        ),
+    }
+}
+
+/// Changes the rules want a notification for, with their dedup key and action.
+/// Disabled sources stay quiet, as do alerts that are already over (unless that is
+/// the news).
+pub fn changes_to_notify<'a>(
+    changes: &'a [AlertChange],
+    settings: &Settings,
+    now: NaiveDateTime,
+) -> Vec<(String, &'a AlertChange, RuleAction)> {
+    changes
+        .iter()
+        .filter(|c| source_enabled(Some(settings), &c.alert.source))
+        .filter(|c| {
+            matches!(c.kind, ChangeKind::Removed | ChangeKind::Overrunning) || !c.alert.is_over(now)
+        })
+        .filter_map(|c| {
+            let kind = event_kind(c.kind);
+            let decision = rules::evaluate(&c.alert, kind, settings, now);
+            decision
+                .action
+                .notifies()
+                .then(|| (notified_key(kind, &c.alert), c, decision.action))
+        })
+        .collect()
+}
+
+/// What an outage of `source` is called in notifications.
+pub fn outage_name(source: &AlertSource, lang: Lang) -> &'static str {
+    match (source, lang) {
+        (AlertSource::Tauron | AlertSource::Fortum, Lang::Pl) => "Wyłączenie prądu",
+        (AlertSource::Tauron | AlertSource::Fortum, Lang::En) => "Power outage",
+        (AlertSource::Water, Lang::Pl) => "Przerwa w dostawie wody",
+        (AlertSource::Water, Lang::En) => "Water outage",
+    }
+}
+
+pub fn source_title(source: &AlertSource, lang: Lang) -> String {
+    match lang {
+        Lang::Pl => format!("{} pod Twoim adresem", outage_name(source, lang)),
+        Lang::En => format!("{} at your address", outage_name(source, lang)),
+    }
+}
+
+pub fn change_title(
+    kind: ChangeKind,
+    alert: &UnifiedAlert,
+    settings: &Settings,
+    lang: Lang,
+) -> String {
+    let name = outage_name(&alert.source, lang);
+    match (kind, lang) {
+        (ChangeKind::Added, _) if affects_address(alert, settings) => {
+            source_title(&alert.source, lang)
+        }
+        (ChangeKind::Added, _) => name.to_string(),
+        (ChangeKind::TimeChanged, Lang::Pl) => format!("{}: zmiana terminu", name),
+        (ChangeKind::TimeChanged, Lang::En) => format!("{} rescheduled", name),
+        (ChangeKind::TextChanged, Lang::Pl) => format!("{}: aktualizacja", name),
+        (ChangeKind::TextChanged, Lang::En) => format!("{} updated", name),
+        (ChangeKind::Removed, Lang::Pl) => format!("{}: usunięto z listy", name),
+        (ChangeKind::Removed, Lang::En) => format!("{} no longer listed", name),
+        (ChangeKind::Cancelled, Lang::Pl) => format!("{}: odwołano", name),
+        (ChangeKind::Cancelled, Lang::En) => format!("{} cancelled", name),
+        (ChangeKind::Overrunning, Lang::Pl) => format!("{}: przedłuża się", name),
+        (ChangeKind::Overrunning, Lang::En) => format!("{} running late", name),
+    }
+}
+
+/// "12.03 08:00–16:00", "12.03 08:00 – 13.03 10:00", or "from 12.03 08:00".
+pub fn format_window(alert: &UnifiedAlert, lang: Lang) -> Option<String> {
+    let start = alert.start_time()?;
+    let from = start.format("%d.%m %H:%M");
+    Some(match alert.end_time() {
+        Some(end) if end.date() == start.date() => format!("{}–{}", from, end.format("%H:%M")),
+        Some(end) => format!("{} – {}", from, end.format("%d.%m %H:%M")),
+        None if lang == Lang::Pl => format!("od {}", from),
+        None => format!("from {}", from),
+    })
+}
+
+fn shorten(text: &str) -> String {
+    let text = text.trim();
+    match text.char_indices().nth(MAX_BODY_CHARS) {
+        Some((at, _)) => format!("{}…", text[..at].trim_end()),
+        None => text.to_string(),
+    }
+}
+
+pub fn notification_body(alert: &UnifiedAlert, lang: Lang) -> String {
+    let lines: Vec<String> = format_window(alert, lang)
+        .into_iter()
+        .chain(alert.message.as_deref().map(shorten))
+        .collect();
+    lines.join("\n")
+}
+
+/// A notification to show, or held until quiet hours end.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Outgoing {
+    pub title: String,
+    pub body: String,
+    /// One-line version for summaries.
+    pub line: String,
+    /// The alert the window shows once the notification brings it up.
+    pub alert_id: String,
+    pub action: RuleAction,
+    /// The alert it is about; `None` for summaries and digests.
+    #[serde(default)]
+    pub alert: Option<UnifiedAlert>,
+    #[serde(default)]
+    pub event: Option<EventKind>,
+    /// The alert's id before it was rescheduled, when that changed it.
+    #[serde(default)]
+    pub previous_alert_id: Option<String>,
+}
+
+impl Outgoing {
+    pub fn new(title: String, alert: &UnifiedAlert, action: RuleAction, lang: Lang) -> Self {
+        let line = match format_window(alert, lang) {
+            Some(window) => format!("{} ({})", title, window),
+            None => title.clone(),
+        };
+        Outgoing {
+            body: notification_body(alert, lang),
+            title,
+            line,
+            alert_id: alert.id.clone(),
+            action,
+            alert: Some(alert.clone()),
+            event: None,
+            previous_alert_id: None,
+        }
+    }
+
+    /// The notification for a change; a rescheduled alert shows "old → new".
+    pub fn for_change(
+        change: &AlertChange,
+        settings: &Settings,
+        action: RuleAction,
+        lang: Lang,
+    ) -> Self {
+        let title = change_title(change.kind, &change.alert, settings, lang);
+        let mut outgoing = Outgoing {
+            event: Some(event_kind(change.kind)),
+            previous_alert_id: change
+                .previous
+                .as_ref()
+                .map(|p| p.id.clone())
+                .filter(|id| *id != change.alert.id),
+            ..Outgoing::new(title, &change.alert, action, lang)
+        };
+        if let (ChangeKind::TimeChanged, Some(previous)) = (change.kind, &change.previous) {
+            let old = format_window(previous, lang).unwrap_or_else(|| "?".to_string());
+            let new = format_window(&change.alert, lang).unwrap_or_else(|| "?".to_string());
+            let moved = format!("{} → {}", old, new);
+            outgoing.line = format!("{} ({})", outgoing.title, moved);
+            outgoing.body = [Some(moved), change.alert.message.as_deref().map(shorten)]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+        outgoing
+    }
+}
+
+/// "Outage alerts: 5", for several notifications in one.
+pub fn alerts_title(count: usize, lang: Lang) -> String {
+    match lang {
+        Lang::Pl => format!("Alerty o wyłączeniach: {}", count),
+        Lang::En => format!("Outage alerts: {}", count),
+    }
+}
+
+/// Replace more than `MAX_SEPARATE` notifications of the same action by one summary.
+pub fn summarize(outgoing: Vec<Outgoing>, lang: Lang) -> Vec<Outgoing> {
+    let mut result: Vec<Outgoing> = Vec::new();
+    let mut actions: Vec<RuleAction> = outgoing.iter().map(|o| o.action).collect();
+    actions.dedup();
+    for action in actions {
+        if result.iter().any(|o| o.action == action) {
+            continue;
+        }
+        let group: Vec<&Outgoing> = outgoing.iter().filter(|o| o.action == action).collect();
+        if group.len() <= MAX_SEPARATE {
+            result.extend(group.into_iter().cloned());
+            continue;
+        }
+        let title = alerts_title(group.len(), lang);
+        result.push(Outgoing {
+            line: title.clone(),
+            title,
+            body: group
+                .iter()
+                .map(|o| o.line.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            alert_id: group[0].alert_id.clone(),
+            action,
+            alert: None,
+            event: None,
+            previous_alert_id: None,
+        });
+    }
+    result
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct NotifiedAlert {
+    /// The alert id for new alerts, see `notified_key`.
+    alert_id: String,
+    notified_at: String,
+}
+
+/// What was already announced, what waits for quiet hours to end, and the alert the
+/// window should show if it gets focus soon (where a click on the notification only
+/// brings it to the front).
+#[derive(Default)]
+pub struct Notifier {
+    notified: Mutex<Vec<NotifiedAlert>>,
+    held: Mutex<Vec<Outgoing>>,
+    focus: Mutex<Option<(String, DateTime<Utc>)>>,
+    path: Option<PathBuf>,
+}
+
+impl Notifier {
+    /// Load state from `path` (the notified keys) and `HELD_FILE` next to it.
+    pub fn open(path: PathBuf) -> Self {
+        Notifier {
+            notified: Mutex::new(read_json(&path)),
+            held: Mutex::new(read_json(&path.with_file_name(HELD_FILE))),
+            focus: Mutex::new(None),
+            path: Some(path),
+        }
+    }
+
+    fn held_path(&self) -> Option<PathBuf> {
+        self.path.as_ref().map(|p| p.with_file_name(HELD_FILE))
+    }
+
+    /// Keep the items whose key wasn't announced yet and remember those keys.
+    pub fn claim<T>(&self, items: Vec<(String, T)>, now: DateTime<Utc>) -> Vec<T> {
+        let Ok(mut notified) = self.notified.lock() else {
+            return Vec::new();
+        };
+        let before = notified.len();
+        notified.retain(|n| {
+            DateTime::parse_from_rfc3339(&n.notified_at)
+                .is_ok_and(|at| now - at.to_utc() < Duration::days(KEEP_DAYS))
+        });
+        let pruned = notified.len() != before;
+
+        let mut fresh = Vec::new();
+        for (key, item) in items {
+            if !notified.iter().any(|n| n.alert_id == key) {
+                notified.push(NotifiedAlert {
+                    alert_id: key,
+                    notified_at: now.to_rfc3339(),
+                });
+                fresh.push(item);
+            }
+        }
+
+        if pruned || !fresh.is_empty() {
+            store_json(self.path.as_deref(), &*notified, "notified alerts");
+        }
+        fresh
+    }
+
+    pub fn hold(&self, outgoing: Vec<Outgoing>) {
+        if let Ok(mut held) = self.held.lock() {
+            held.extend(outgoing);
+            store_json(self.held_path().as_deref(), &*held, "held notifications");
+        }
+    }
+
+    pub fn take_held(&self) -> Vec<Outgoing> {
+        let Ok(mut held) = self.held.lock() else {
+            return Vec::new();
+        };
+        if held.is_empty() {
+            return Vec::new();
+        }
+        let taken = std::mem::take(&mut *held);
+        store_json(self.held_path().as_deref(), &*held, "held notifications");
+        taken
+    }
+
+    pub fn set_focus(&self, alert_id: &str, now: DateTime<Utc>) {
+        if let Ok(mut focus) = self.focus.lock() {
+            *focus = Some((alert_id.to_string(), now));
+        }
+    }
+
+    /// The alert notified last, unless that was more than `FOCUS_MINUTES` ago.
+    pub fn take_focus(&self, now: DateTime<Utc>) -> Option<String> {
+        let (alert_id, set_at) = self.focus.lock().ok()?.take()?;
+        (now - set_at < Duration::minutes(FOCUS_MINUTES)).then_some(alert_id)
+    }
+}
+
+/// Notify about `changes` as the rules say. Needs `Notifier` managed.
+pub fn notify_changes(app: &AppHandle, settings: Option<&Settings>, changes: &[AlertChange]) {
+    let Some(settings) = settings else {
+        return;
+    };
+    let notifier = app.state::<Notifier>();
+    let candidates = changes_to_notify(changes, settings, Local::now().naive_local())
+        .into_iter()
+        .map(|(key, change, action)| (key, (change, action)))
+        .collect();
+    let lang = language(Some(settings));
+    let outgoing = notifier
+        .claim(candidates, Utc::now())
+        .into_iter()
+        .map(|(change, action)| Outgoing::for_change(change, settings, action, lang))
+        .collect();
+    deliver(app, settings, outgoing);
+}
+
+/// Show `outgoing` now, or hold it when quiet hours say so.
+pub fn deliver(app: &AppHandle, settings: &Settings, outgoing: Vec<Outgoing>) {
+    let now = Local::now().naive_local();
+    let (held, due): (Vec<Outgoing>, Vec<Outgoing>) = outgoing
+        .into_iter()
+        .partition(|o| rules::is_held(o.action, settings, now));
+    if !held.is_empty() {
+        app.state::<Notifier>().hold(held);
+    }
+    show_all(app, settings, due);
+}
+
+/// Show what quiet hours held back, once they are over.
+pub fn release_held(app: &AppHandle) {
+    let Ok(Some(settings)) = app.state::<SettingsCache>().get() else {
+        return;
+    };
+    if rules::in_quiet_hours(settings.quietHours.as_ref(), Local::now().naive_local()) {
+        return;
+    }
+    let held = app.state::<Notifier>().take_held();
+    show_all(app, &settings, held);
+}
+
+/// Send `outgoing` through all channels: one email, one chat message each (so they
+/// can be edited and replied to later), one webhook call each (so the placeholders
+/// have an alert to fill in), and on the desktop one by one or summarized.
+fn show_all(app: &AppHandle, settings: &Settings, outgoing: Vec<Outgoing>) {
+    email::dispatch(settings, &outgoing);
+    chat::dispatch(app, settings, &outgoing);
+    webhook::dispatch(settings, &outgoing);
+    let outgoing = summarize(outgoing, language(Some(settings)));
+    for o in &outgoing {
+        show(app, o);
+    }
+    if let Some(last) = outgoing.last() {
+        app.state::<Notifier>()
+            .set_focus(&last.alert_id, Utc::now());
+    }
+}
+
+pub fn show(app: &AppHandle, outgoing: &Outgoing) {
+    let notification = app.notification();
+    if let Ok(PermissionState::Prompt | PermissionState::PromptWithRationale) =
+        notification.permission_state()
+    {
+        if let Err(e) = notification.request_permission() {
+            log::warn!("Notification permission request failed: {}", e);
+        }
+    }
+    let builder = notification
+        .builder()
+        .title(&outgoing.title)
+        .body(&outgoing.body)
+        .extra("alertId", &outgoing.alert_id);
+    let builder = match outgoing.action {
+        RuleAction::NotifySilent => builder.silent(),
+        _ => builder.sound(ALERT_SOUND),
+    };
+    if let Err(e) = builder.show() {
+        log::warn!("Could not show notification: {}", e);
+    }
+}
+
+/// On window focus shortly after a notification, point the frontend at the alert
+/// notified last.
+pub fn focus_notified_alert(app: &AppHandle) {
+    if let Some(alert_id) = app.state::<Notifier>().take_focus(Utc::now()) {
+        emit_focus(app, alert_id);
+    }
+}
+
+/// A click on the notification for `alert_id`, where the platform reports it: bring
+/// the window up and point the frontend at that alert.
+pub fn open_alert(app: &AppHandle, alert_id: String) {
+    app.state::<Notifier>().take_focus(Utc::now());
+    if let Some(window) = app.get_webview_window("main") {
+        if let Err(e) = window
+            .show()
+            .and_then(|_| window.unminimize())
+            .and_then(|_| window.set_focus())
+        {
+            log::warn!("Could not bring the window up: {}", e);
+        }
+    }
+    emit_focus(app, alert_id);
+}
+
+fn emit_focus(app: &AppHandle, alert_id: String) {
+    if let Err(e) = app.emit(FOCUS_ALERT_EVENT, alert_id) {
+        log::warn!("Could not emit {}: {}", FOCUS_ALERT_EVENT, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert_diff::{diff, overrunning};
+    use crate::api_logic::parse_alert_time;
+    use crate::rules::{NotificationRule, RuleCondition};
+    use crate::test_support::{alert, settings, temp_dir};
+    use std::slice;
+
+    fn alerts_of(candidates: &[(String, &AlertChange, RuleAction)]) -> Vec<UnifiedAlert> {
+        candidates.iter().map(|(_, c, _)| c.alert.clone()).collect()
+    }
+
+    #[test]
+    fn test_changes_to_notify() {
+        let now = parse_alert_time("2026-03-12T09:00:00").unwrap();
+        let here = alert(
+            "water-1",
+            AlertSource::Water,
+            "2026-03-12T08:00:00",
+            "2026-03-12T16:00:00",
+            "Kuźnicza 1-30",
+        );
+        let elsewhere = alert(
+            "water-2",
+            AlertSource::Water,
+            "2026-03-12T08:00:00",
+            "2026-03-12T16:00:00",
+            "Ruska 5",
+        );
+        let over = alert(
+            "water-3",
+            AlertSource::Water,
+            "2026-03-12T08:00:00",
+            "2026-03-12T08:30:00",
+            "Kuźnicza 2",
+        );
+        let fortum = alert(
+            "fortum-4",
+            AlertSource::Fortum,
+            "2026-03-12T08:00:00",
+            "2026-03-12T16:00:00",
+            "Kuźnicza",
+        );
+        let current = vec![here.clone(), elsewhere, over, fortum.clone()];
+        let home_only = Settings {
+            locations: Vec::new(),
+            ..settings()
+        };
+
+        let changes = diff(&[], &current);
+        assert_eq!(
+            alerts_of(&changes_to_notify(&changes, &home_only, now)),
+            vec![here.clone(), fortum]
+        );
+
+        // Disabled sources stay quiet
+        let water_only = Settings {
+            enabledSources: Some(vec!["water".to_string()]),
+            ..home_only.clone()
+        };
+        assert_eq!(
+            alerts_of(&changes_to_notify(&changes, &water_only, now)),
+            vec![here.clone()]
+        );
+
+        // Moves at the address notify, removals of finished alerts don't
+        let moved = alert(
+            "water-5",
+            AlertSource::Water,
+            "2026-03-12T08:00:00",
+            "2026-03-12T18:00:00",
+            "Kuźnicza 1-30",
+        );
+        let moves = diff(&current, slice::from_ref(&moved));
+        let notified = changes_to_notify(&moves, &home_only, now);
+        assert_eq!(alerts_of(&notified), vec![moved.clone()]);
+        assert!(notified[0].0.starts_with("time_changed:"));
+
+        // A rule can make text changes urgent
+        let reworded = alert(
+            "water-6",
+            AlertSource::Water,
+            "2026-03-12T08:00:00",
+            "2026-03-12T18:00:00",
+            "Kuźnicza 1-32",
+        );
+        let rewords = diff(slice::from_ref(&moved), slice::from_ref(&reworded));
+        assert!(changes_to_notify(&rewords, &home_only, now).is_empty());
+        let with_rule = Settings {
+            notificationRules: vec![NotificationRule {
+                name: "rewords".to_string(),
+                when: RuleCondition {
+                    kinds: Some(vec![EventKind::TextChanged]),
+                    ..Default::default()
+                },
+                action: RuleAction::NotifyNow,
+            }],
+            ..home_only.clone()
+        };
+        let notified = changes_to_notify(&rewords, &with_rule, now);
+        assert_eq!(alerts_of(&notified), vec![reworded]);
+        assert_eq!(notified[0].2, RuleAction::NotifyNow);
+
+        // Running late is news even though the end has passed
+        let late = overrunning(&current, &[], now, Duration::hours(8));
+        let notified = changes_to_notify(&late, &home_only, now);
+        assert_eq!(notified.len(), 1);
+        assert!(notified[0].0.starts_with("overrunning:"));
+
+        // Saved locations are the user's addresses too
+        assert_eq!(changes_to_notify(&changes, &settings(), now).len(), 3);
+        assert_eq!(
+            change_title(ChangeKind::Added, &current[1], &settings(), Lang::En),
+            "Water outage at your address"
+        );
+    }
+
+    #[test]
+    fn test_claim_deduplicates_across_restarts() {
+        let dir = temp_dir("notified");
+        let path = dir.join(NOTIFIED_FILE);
+        let now = Utc::now();
+        let key = |k: &str| (k.to_string(), k.to_string());
+
+        let notifier = Notifier::open(path.clone());
+        assert_eq!(notifier.claim(vec![key("tauron-a")], now), vec!["tauron-a"]);
+        assert!(notifier.claim(vec![key("tauron-a")], now).is_empty());
+
+        let notifier = Notifier::open(path.clone());
+        assert_eq!(
+            notifier.claim(vec![key("tauron-a"), key("tauron-b")], now),
+            vec!["tauron-b"]
+        );
+
+        // Long forgotten keys may notify again
+        let later = now + Duration::days(KEEP_DAYS + 1);
+        assert_eq!(
+            Notifier::open(path.clone()).claim(vec![key("tauron-a")], later),
+            vec!["tauron-a"]
+        );
+
+        // Held notifications survive a restart until taken
+        let a = alert(
+            "tauron-a",
+            AlertSource::Tauron,
+            "2026-03-12T08:00:00",
+            "2026-03-12T16:00:00",
+            "Kuźnicza",
+        );
+        let held = Outgoing::new("t".to_string(), &a, RuleAction::Notify, Lang::En);
+        Notifier::open(path.clone()).hold(vec![held.clone()]);
+        assert_eq!(Notifier::open(path.clone()).take_held(), vec![held]);
+        assert!(Notifier::open(path).take_held().is_empty());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_focus_expires() {
+        let notifier = Notifier::default();
+        let now = Utc::now();
+        notifier.set_focus("tauron-a", now);
+        assert_eq!(
+            notifier.take_focus(now + Duration::seconds(30)),
+            Some("tauron-a".to_string())
+        );
+        assert_eq!(notifier.take_focus(now + Duration::seconds(30)), None);
+
+        // Focusing the window much later has nothing to do with the notification
+        notifier.set_focus("tauron-a", now);
+        assert_eq!(
+            notifier.take_focus(now + Duration::minutes(FOCUS_MINUTES)),
+            None
+        );
+        assert_eq!(notifier.take_focus(now), None);
+    }
+
+    #[test]
+    fn test_notification_texts() {
+        let s = settings();
+        let a = alert(
+            "tauron-a",
+            AlertSource::Tauron,
+            "2026-03-12T08:00:00",
+            "2026-03-12T16:00:00",
+            "Kuźnicza 1-30",
+        );
+        let title = change_title(ChangeKind::Added, &a, &s, Lang::Pl);
+        assert_eq!(title, "Wyłączenie prądu pod Twoim adresem");
+        let o = Outgoing::new(title, &a, RuleAction::Notify, Lang::Pl);
+        assert_eq!(o.body, "12.03 08:00–16:00\nKuźnicza 1-30");
+        let moved = alert(
+            "tauron-b",
+            AlertSource::Tauron,
+            "2026-03-12T08:00:00",
+            "2026-03-12T18:00:00",
+            "Kuźnicza 1-30",
+        );
+        let change = diff(slice::from_ref(&a), slice::from_ref(&moved)).remove(0);
+        let o = Outgoing::for_change(&change, &s, RuleAction::Notify, Lang::En);
+        assert_eq!(o.title, "Power outage rescheduled");
+        assert_eq!(
+            o.body,
+            "12.03 08:00–16:00 → 12.03 08:00–18:00\nKuźnicza 1-30"
+        );
+        let mut cancelled = diff(slice::from_ref(&a), &[]);
+        crate::alert_diff::mark_cancelled(
+            &mut cancelled,
+            parse_alert_time("2026-03-11T12:00:00").unwrap(),
+        );
+        let o = Outgoing::for_change(&cancelled[0], &s, RuleAction::Notify, Lang::Pl);
+        assert_eq!(o.title, "Wyłączenie prądu: odwołano");
+
+        let overnight = alert(
+            "water-b",
+            AlertSource::Water,
+            "2026-03-12T08:00:00",
+            "2026-03-13T06:00:00",
+            "Kuźnicza",
+        );
+        assert_eq!(
+            format_window(&overnight, Lang::En).unwrap(),
+            "12.03 08:00 – 13.03 06:00"
+        );
+
+        let notify =
+            |a: &UnifiedAlert| Outgoing::new("New".to_string(), a, RuleAction::Notify, Lang::En);
+        let urgent = Outgoing::new("Now".to_string(), &a, RuleAction::NotifyNow, Lang::En);
+        let many = vec![
+            notify(&a),
+            notify(&a),
+            urgent.clone(),
+            notify(&overnight),
+            notify(&overnight),
+        ];
+        let summary = summarize(many, Lang::En);
+        assert_eq!(summary.len(), 2);
+        assert_eq!(summary[0].title, "Outage alerts: 4");
+        assert_eq!(
+            summary[0].body.lines().next(),
+            Some("New (12.03 08:00–16:00)")
+        );
+        assert_eq!(summary[1], urgent);
+
+        let long = alert(
+            "fortum-c",
+            AlertSource::Fortum,
+            "2026-03-12T08:00:00",
+            "2026-03-12T16:00:00",
+            &"ą".repeat(300),
+        );
+        assert!(notification_body(&long, Lang::En).ends_with('…'));
+    }
+}
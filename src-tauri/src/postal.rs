@@ -0,0 +1,339 @@
+//! Postal code (PNA) index: which localities and streets a code covers.
+//!
+//! `build.rs` generates the index from the Poczta Polska PNA register at `$PNA_CSV`
+//! and bundles it gzip-compressed. Without it debug builds use the seed in
+//! `data/pna.tsv.gz`. Lines are tab-separated:
+//!
+//! ```text
+//! <pna> <locality> <street> <numbers> <voivodeship>
+//! ```
+//!
+//! An empty street means the code covers the whole locality; empty numbers mean the
+//! whole street.
+
+use crate::api_logic::GeoItem;
+use crate::geo_search::{fold_diacritics, match_score, street_match_score};
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::OnceLock;
+
+const BUNDLED_INDEX: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/pna.tsv.gz"));
+
+/// Voivodeships served by Tauron Dystrybucja.
+const TAURON_VOIVODESHIPS: &[&str] = &["dolnośląskie", "opolskie", "śląskie", "małopolskie"];
+
+/// Localities covered by the Wrocław-only sources (MPWiK water, Fortum).
+const WROCLAW_SOURCES_LOCALITIES: &[&str] = &["wroclaw"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PnaRow {
+    locality: String,
+    street: String,
+    numbers: String,
+    voivodeship: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PostalLocality {
+    pub name: String,
+    pub voivodeship: String,
+    /// Streets covered by the code; empty when it covers the whole locality.
+    pub streets: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PostalArea {
+    pub postal_code: String,
+    pub localities: Vec<PostalLocality>,
+    /// Alert sources that cover this area, in `Settings.enabledSources` form.
+    pub providers: Vec<String>,
+}
+
+impl PostalArea {
+    /// Keep the cities that belong to this area.
+    pub fn narrow_cities(&self, items: Vec<GeoItem>) -> Vec<GeoItem> {
+        items
+            .into_iter()
+            .filter(|item| {
+                self.localities
+                    .iter()
+                    .any(|l| match_score(&item.Name, &l.name) >= 0.95)
+            })
+            .collect()
+    }
+
+    /// Keep the streets that belong to this area. A locality covered as a whole
+    /// doesn't narrow anything.
+    pub fn narrow_streets(&self, items: Vec<GeoItem>) -> Vec<GeoItem> {
+        if self.localities.iter().any(|l| l.streets.is_empty()) {
+            return items;
+        }
+        items
+            .into_iter()
+            .filter(|item| {
+                self.localities
+                    .iter()
+                    .flat_map(|l| &l.streets)
+                    .any(|street| street_match_score(&item.Name, street) >= 0.95)
+            })
+            .collect()
+    }
+}
+
+pub struct PostalIndex {
+    rows: HashMap<String, Vec<PnaRow>>,
+}
+
+impl PostalIndex {
+    pub fn parse(data: &str) -> Result<Self, String> {
+        let mut rows: HashMap<String, Vec<PnaRow>> = HashMap::new();
+        for (line_no, line) in data.lines().enumerate() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [pna, locality, street, numbers, voivodeship] = fields.as_slice() else {
+                return Err(format!("PNA index: malformed line {}", line_no + 1));
+            };
+            rows.entry(pna.to_string()).or_default().push(PnaRow {
+                locality: locality.to_string(),
+                street: street.to_string(),
+                numbers: numbers.to_string(),
+                voivodeship: voivodeship.to_string(),
+            });
+        }
+        Ok(PostalIndex { rows })
+    }
+
+    pub fn from_gzip(bytes: &[u8]) -> Result<Self, String> {
+        let mut data = String::new();
+        GzDecoder::new(bytes)
+            .read_to_string(&mut data)
+            .map_err(|e| format!("PNA index: {}", e))?;
+        Self::parse(&data)
+    }
+
+    /// Localities and streets covered by `postal_code` ("50-141" or "50141").
+    /// With `house_no`, streets whose number ranges exclude it are left out.
+    pub fn area(&self, postal_code: &str, house_no: Option<&str>) -> Option<PostalArea> {
+        let code = normalize_postal_code(postal_code)?;
+        let rows = self.rows.get(&code)?;
+
+        let mut localities: Vec<PostalLocality> = Vec::new();
+        for row in rows {
+            if let Some(house_no) = house_no {
+                if !row.street.is_empty() && !numbers_match(&row.numbers, house_no) {
+                    continue;
+                }
+            }
+            let idx = match localities.iter().position(|l| l.name == row.locality) {
+                Some(idx) => idx,
+                None => {
+                    localities.push(PostalLocality {
+                        name: row.locality.clone(),
+                        voivodeship: row.voivodeship.clone(),
+                        streets: Vec::new(),
+                    });
+                    localities.len() - 1
+                }
+            };
+            if !row.street.is_empty() && !localities[idx].streets.contains(&row.street) {
+                localities[idx].streets.push(row.street.clone());
+            }
+        }
+
+        let providers = providers_for(&localities);
+        Some(PostalArea {
+            postal_code: code,
+            localities,
+            providers,
+        })
+    }
+}
+
+/// "50141" -> "50-141"; anything that isn't a postal code gives `None`.
+pub fn normalize_postal_code(input: &str) -> Option<String> {
+    let digits: String = input.chars().filter(|c| c.is_ascii_digit()).collect();
+    let shape_ok = input.trim().chars().all(|c| c.is_ascii_digit() || c == '-');
+    if digits.len() != 5 || !shape_ok {
+        return None;
+    }
+    Some(format!("{}-{}", &digits[..2], &digits[2..]))
+}
+
+fn providers_for(localities: &[PostalLocality]) -> Vec<String> {
+    let mut providers = Vec::new();
+    if localities
+        .iter()
+        .any(|l| TAURON_VOIVODESHIPS.contains(&l.voivodeship.to_lowercase().as_str()))
+    {
+        providers.push("tauron".to_string());
+    }
+    if localities
+        .iter()
+        .any(|l| WROCLAW_SOURCES_LOCALITIES.contains(&fold_diacritics(&l.name).as_str()))
+    {
+        providers.push("water".to_string());
+        providers.push("fortum".to_string());
+    }
+    providers
+}
+
+fn leading_number(s: &str) -> Option<u32> {
+    let digits: String = s.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+/// Match a house number against PNA number ranges such as "1-7(n), 2-20(p), 30-DK".
+/// "(n)"/"(p)" restrict to odd/even numbers and "DK" means "to the end".
+pub fn numbers_match(spec: &str, house_no: &str) -> bool {
+    if spec.trim().is_empty() {
+        return true;
+    }
+    let Some(number) = leading_number(house_no.trim()) else {
+        return true;
+    };
+
+    spec.split(',').map(str::trim).any(|range| {
+        let (range, parity) = match range.split_once('(') {
+            Some((r, p)) => (r.trim(), p.trim_end_matches(')').trim()),
+            None => (range, ""),
+        };
+        let parity_ok = match parity {
+            "n" => number % 2 == 1,
+            "p" => number % 2 == 0,
+            _ => true,
+        };
+        let in_range = match range.split_once('-') {
+            Some((from, to)) => {
+                let from = leading_number(from.trim()).unwrap_or(0);
+                let to = if to.trim() == "DK" {
+                    u32::MAX
+                } else {
+                    leading_number(to.trim()).unwrap_or(u32::MAX)
+                };
+                (from..=to).contains(&number)
+            }
+            None => leading_number(range) == Some(number),
+        };
+        parity_ok && in_range
+    })
+}
+
+/// The bundled index, decompressed on first use.
+pub fn bundled() -> Result<&'static PostalIndex, String> {
+    static INDEX: OnceLock<Result<PostalIndex, String>> = OnceLock::new();
+    INDEX
+        .get_or_init(|| PostalIndex::from_gzip(BUNDLED_INDEX))
+        .as_ref()
+        .map_err(|e| e.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "# sample\n\
+50-001\tWrocław\tKuźnicza\t1-19(n)\tdolnośląskie\n\
+50-001\tWrocław\tLegnicka\t2-DK(p)\tdolnośląskie\n\
+50-002\tWrocław\tKuźnicza\t21-DK(n)\tdolnośląskie\n\
+55-001\tKąty Wrocławskie\t\t\tdolnośląskie\n\
+55-001\tSadków\t\t\tdolnośląskie\n\
+00-001\tWarszawa\tMarszałkowska\t\tmazowieckie\n";
+
+    fn geo(gaid: u64, name: &str) -> GeoItem {
+        GeoItem {
+            GAID: gaid,
+            Name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_normalize_postal_code() {
+        assert_eq!(normalize_postal_code("50-141"), Some("50-141".to_string()));
+        assert_eq!(normalize_postal_code("50141"), Some("50-141".to_string()));
+        assert_eq!(normalize_postal_code("5014"), None);
+        assert_eq!(normalize_postal_code("Wrocław"), None);
+    }
+
+    #[test]
+    fn test_numbers_match() {
+        assert!(numbers_match("1-19(n)", "7"));
+        assert!(!numbers_match("1-19(n)", "8"));
+        assert!(!numbers_match("1-19(n)", "21"));
+        assert!(numbers_match("2-DK(p)", "1024"));
+        assert!(numbers_match("1-7(n), 10", "10"));
+        assert!(numbers_match("1-7(n)", "5A"));
+        assert!(numbers_match("", "5"));
+    }
+
+    #[test]
+    fn test_area_with_streets_and_providers() {
+        let index = PostalIndex::parse(SAMPLE).unwrap();
+        let area = index.area("50001", None).unwrap();
+        assert_eq!(area.postal_code, "50-001");
+        assert_eq!(area.localities.len(), 1);
+        assert_eq!(area.localities[0].streets, vec!["Kuźnicza", "Legnicka"]);
+        assert_eq!(area.providers, vec!["tauron", "water", "fortum"]);
+
+        let area = index.area("50-001", Some("24")).unwrap();
+        assert_eq!(area.localities[0].streets, vec!["Legnicka"]);
+        let area = index.area("50-001", Some("25")).unwrap();
+        assert!(area.localities.is_empty());
+
+        let area = index.area("55-001", None).unwrap();
+        assert_eq!(area.localities.len(), 2);
+        assert_eq!(area.providers, vec!["tauron"]);
+
+        let area = index.area("00-001", None).unwrap();
+        assert!(area.providers.is_empty());
+
+        assert!(index.area("99-999", None).is_none());
+    }
+
+    #[test]
+    fn test_narrow_candidates() {
+        let index = PostalIndex::parse(SAMPLE).unwrap();
+        let area = index.area("50-001", None).unwrap();
+
+        let cities = area.narrow_cities(vec![geo(1, "Wrocław"), geo(2, "Wrocławice")]);
+        assert_eq!(cities, vec![geo(1, "Wrocław")]);
+
+        let streets = area.narrow_streets(vec![geo(10, "ul. Kuźnicza"), geo(11, "ul. Ruska")]);
+        assert_eq!(streets, vec![geo(10, "ul. Kuźnicza")]);
+
+        // A code covering whole villages doesn't narrow streets
+        let area = index.area("55-001", None).unwrap();
+        assert_eq!(area.narrow_streets(vec![geo(20, "Polna")]).len(), 1);
+    }
+
+    #[test]
+    fn test_bundled_index_loads() {
+        let index = bundled().expect("bundled index should decompress");
+        assert!(index.area("50-141", None).is_some());
+    }
+
+    #[test]
+    #[cfg_attr(
+        not(postal_full),
+        ignore = "needs the index generated from the PNA register"
+    )]
+    fn test_bundled_index_has_register() {
+        let index = bundled().unwrap();
+        let gliwice = index.area("44-100", None).expect("44-100");
+        assert!(gliwice
+            .localities
+            .iter()
+            .any(|l| l.name == "Gliwice" && l.voivodeship == "śląskie"));
+        assert_eq!(gliwice.providers, vec!["tauron"]);
+        let jelenia_gora = index.area("58500", None).expect("58-500");
+        assert!(jelenia_gora
+            .localities
+            .iter()
+            .any(|l| l.name == "Jelenia Góra"));
+    }
+}
@@ -0,0 +1,380 @@
+//! HTTP clients for the alert sources, converting everything to `UnifiedAlert`.
+
+use crate::api_logic::{
+    self, get_outages_query, AlertSource, SavedLocation, Settings, UnifiedAlert, BASE_URL,
+    FORTUM_CITY_GUID, FORTUM_REGION_ID, FORTUM_URL, MPWIK_URL,
+};
+use chrono::{SecondsFormat, Utc};
+use std::fmt;
+use std::time::Duration;
+
+pub const ALL_SOURCES: [AlertSource; 3] =
+    [AlertSource::Tauron, AlertSource::Water, AlertSource::Fortum];
+
+/// A source that doesn't answer must not hold up the other sources and the scheduler.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchErrorKind {
+    /// No address to ask Tauron about.
+    NotConfigured,
+    Timeout,
+    Connection,
+    /// The source answered with an error status.
+    HttpStatus,
+    /// The response wasn't what the source normally sends.
+    Decode,
+    Other,
+}
+
+/// Every `FetchErrorKind`, in the order metrics list them.
+pub const ERROR_KINDS: [FetchErrorKind; 6] = [
+    FetchErrorKind::NotConfigured,
+    FetchErrorKind::Timeout,
+    FetchErrorKind::Connection,
+    FetchErrorKind::HttpStatus,
+    FetchErrorKind::Decode,
+    FetchErrorKind::Other,
+];
+
+impl FetchErrorKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FetchErrorKind::NotConfigured => "not_configured",
+            FetchErrorKind::Timeout => "timeout",
+            FetchErrorKind::Connection => "connection",
+            FetchErrorKind::HttpStatus => "http_status",
+            FetchErrorKind::Decode => "decode",
+            FetchErrorKind::Other => "other",
+        }
+    }
+}
+
+/// A failed fetch. `kind` is what `/sources/status` and `/metrics` count it as.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchError {
+    pub kind: FetchErrorKind,
+    pub message: String,
+}
+
+impl FetchError {
+    pub fn new(kind: FetchErrorKind, message: impl Into<String>) -> Self {
+        FetchError {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(e: reqwest::Error) -> Self {
+        // A connect timeout is both; it counts as a timeout
+        let kind = if e.is_timeout() {
+            FetchErrorKind::Timeout
+        } else if e.is_connect() {
+            FetchErrorKind::Connection
+        } else if e.is_decode() {
+            FetchErrorKind::Decode
+        } else if e.status().is_some() {
+            FetchErrorKind::HttpStatus
+        } else if e.is_request() {
+            FetchErrorKind::Connection
+        } else {
+            FetchErrorKind::Other
+        };
+        FetchError::new(kind, e.to_string())
+    }
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+pub fn build_client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// The primary address and saved locations Tauron can be asked about: those with
+/// both GAIDs.
+pub fn tauron_locations(settings: &Settings) -> Vec<SavedLocation> {
+    settings
+        .identified_locations()
+        .into_iter()
+        .map(|(_, location)| location)
+        .filter(|l| l.cityGAID != 0 && l.streetGAID != 0)
+        .collect()
+}
+
+/// Tauron outages at one address.
+pub async fn fetch_tauron(location: &SavedLocation) -> Result<Vec<UnifiedAlert>, FetchError> {
+    let now = Utc::now();
+    let from_date = now.to_rfc3339_opts(SecondsFormat::Millis, true);
+    let cache_bust = now.timestamp_millis().to_string();
+
+    let query = get_outages_query(
+        location.cityGAID,
+        location.streetGAID,
+        &location.houseNo,
+        &from_date,
+        &cache_bust,
+    );
+
+    let client = build_client().map_err(|e| FetchError::new(FetchErrorKind::Other, e))?;
+    let res = client
+        .get(format!("{}/outages/address", BASE_URL))
+        .query(&query)
+        .header("accept", "application/json")
+        .header("x-requested-with", "XMLHttpRequest")
+        .header("Referer", "https://www.tauron-dystrybucja.pl/wylaczenia")
+        .send()
+        .await?;
+
+    if !res.status().is_success() {
+        return Err(FetchError::new(
+            FetchErrorKind::HttpStatus,
+            format!("HTTP error! status: {}", res.status()),
+        ));
+    }
+
+    let data = res.json::<api_logic::OutageResponse>().await?;
+
+    let alerts: Vec<UnifiedAlert> = data
+        .OutageItems
+        .unwrap_or_default()
+        .iter()
+        .map(|item| item.to_unified())
+        .collect();
+
+    Ok(alerts)
+}
+
+/// MPWiK water failures (all of Wrocław).
+pub async fn fetch_water() -> Result<Vec<UnifiedAlert>, FetchError> {
+    let client = build_client().map_err(|e| FetchError::new(FetchErrorKind::Other, e))?;
+    let res = client
+        .post(MPWIK_URL)
+        .header(
+            "content-type",
+            "application/x-www-form-urlencoded; charset=UTF-8",
+        )
+        .header("accept", "application/json")
+        .header("x-requested-with", "XMLHttpRequest")
+        .header("origin", "https://www.mpwik.wroc.pl")
+        .header("referer", "https://www.mpwik.wroc.pl/")
+        .body("action=all")
+        .send()
+        .await?;
+
+    if !res.status().is_success() {
+        return Err(FetchError::new(
+            FetchErrorKind::HttpStatus,
+            format!("MPWiK HTTP error: {}", res.status()),
+        ));
+    }
+
+    let data: api_logic::MpwikResponse = res.json().await?;
+    let alerts: Vec<UnifiedAlert> = data
+        .failures
+        .unwrap_or_default()
+        .iter()
+        .map(|f| f.to_unified())
+        .collect();
+
+    Ok(alerts)
+}
+
+/// Fortum planned and current switch-offs (Wrocław), without duplicates.
+pub async fn fetch_fortum() -> Result<Vec<UnifiedAlert>, FetchError> {
+    let client = build_client().map_err(|e| FetchError::new(FetchErrorKind::Other, e))?;
+
+    let planned_url = format!(
+        "{}?cityGuid={}&regionId={}&current=false",
+        FORTUM_URL, FORTUM_CITY_GUID, FORTUM_REGION_ID
+    );
+    let current_url = format!(
+        "{}?cityGuid={}&regionId={}&current=true",
+        FORTUM_URL, FORTUM_CITY_GUID, FORTUM_REGION_ID
+    );
+
+    let (planned_res, current_res) = tokio::join!(
+        client
+            .get(&planned_url)
+            .header("accept", "application/json")
+            .send(),
+        client
+            .get(&current_url)
+            .header("accept", "application/json")
+            .send()
+    );
+
+    let planned_data: api_logic::FortumResponse = planned_res?.error_for_status()?.json().await?;
+    let current_data: api_logic::FortumResponse = current_res?.error_for_status()?.json().await?;
+
+    let mut seen_ids = std::collections::HashSet::new();
+    let mut all_points = planned_data.points;
+    all_points.extend(current_data.points);
+
+    let alerts: Vec<UnifiedAlert> = all_points
+        .into_iter()
+        .filter(|p| seen_ids.insert(p.switch_off_id.clone()))
+        .map(|p| p.to_unified())
+        .collect();
+
+    Ok(alerts)
+}
+
+/// What a fetch of one source got, per address: Tauron is asked about each address
+/// on its own (keyed by `tauron_key`), the other sources once under "".
+#[derive(Debug, Clone)]
+pub struct Fetched(pub Vec<(String, Result<Vec<UnifiedAlert>, FetchError>)>);
+
+impl Fetched {
+    /// A source that isn't asked per address.
+    pub fn single(result: Result<Vec<UnifiedAlert>, FetchError>) -> Self {
+        Fetched(vec![(String::new(), result)])
+    }
+
+    /// What the addresses that answered listed; an outage listed for several of
+    /// them comes once.
+    pub fn alerts(&self) -> Vec<UnifiedAlert> {
+        let mut alerts: Vec<UnifiedAlert> = Vec::new();
+        for alert in self
+            .0
+            .iter()
+            .filter_map(|(_, result)| result.as_ref().ok())
+            .flatten()
+        {
+            if !alerts.iter().any(|a| a.id == alert.id) {
+                alerts.push(alert.clone());
+            }
+        }
+        alerts
+    }
+
+    /// The first failure, if any address failed.
+    pub fn error(&self) -> Option<&FetchError> {
+        self.0.iter().find_map(|(_, result)| result.as_ref().err())
+    }
+
+    /// Whether at least one address answered.
+    pub fn answered(&self) -> bool {
+        self.0.iter().any(|(_, result)| result.is_ok())
+    }
+}
+
+/// Identifies a Tauron address among the results of a fetch.
+pub fn tauron_key(location: &SavedLocation) -> String {
+    format!(
+        "{}/{}/{}",
+        location.cityGAID, location.streetGAID, location.houseNo
+    )
+}
+
+/// Tauron outages at every address, concurrently. Errors name the address.
+async fn fetch_tauron_locations(locations: Vec<SavedLocation>) -> Fetched {
+    if locations.is_empty() {
+        return Fetched::single(Err(FetchError::new(
+            FetchErrorKind::NotConfigured,
+            "No settings configured. Please set up your location first.",
+        )));
+    }
+    let mut fetches = tokio::task::JoinSet::new();
+    for (n, location) in locations.iter().cloned().enumerate() {
+        fetches.spawn(async move {
+            let result = fetch_tauron(&location)
+                .await
+                .map_err(|e| FetchError::new(e.kind, format!("{}: {}", location.label, e.message)));
+            (n, result)
+        });
+    }
+    let mut results: Vec<Option<Result<Vec<UnifiedAlert>, FetchError>>> =
+        vec![None; locations.len()];
+    while let Some(joined) = fetches.join_next().await {
+        match joined {
+            Ok((n, result)) => results[n] = Some(result),
+            Err(e) => log::warn!("Tauron fetch task failed: {}", e),
+        }
+    }
+    Fetched(
+        locations
+            .iter()
+            .zip(results)
+            .map(|(location, result)| {
+                let result = result.unwrap_or_else(|| {
+                    Err(FetchError::new(
+                        FetchErrorKind::Other,
+                        format!("{}: fetch task failed", location.label),
+                    ))
+                });
+                (tauron_key(location), result)
+            })
+            .collect(),
+    )
+}
+
+/// Fetch one source. Tauron is asked about every address that has GAIDs.
+pub async fn fetch_source(source: &AlertSource, settings: Option<&Settings>) -> Fetched {
+    match source {
+        AlertSource::Tauron => {
+            fetch_tauron_locations(settings.map(tauron_locations).unwrap_or_default()).await
+        }
+        AlertSource::Water => Fetched::single(fetch_water().await),
+        AlertSource::Fortum => Fetched::single(fetch_fortum().await),
+    }
+}
+
+/// Display name used to prefix errors from a source.
+pub fn source_label(source: &AlertSource) -> &'static str {
+    match source {
+        AlertSource::Tauron => "Tauron",
+        AlertSource::Water => "MPWiK",
+        AlertSource::Fortum => "Fortum",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::stand_in;
+    use serde_json::Value;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_error_kind() {
+        async fn kind(url: &str, timeout: Duration) -> FetchErrorKind {
+            let client = reqwest::Client::builder().timeout(timeout).build().unwrap();
+            let fetched = async {
+                client
+                    .get(url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json::<Value>()
+                    .await
+            };
+            FetchError::from(fetched.await.unwrap_err()).kind
+        }
+        let second = Duration::from_secs(1);
+
+        let (url, _) = stand_in(|_, _| (503, String::new())).await;
+        assert_eq!(kind(&url, second).await, FetchErrorKind::HttpStatus);
+        let (url, _) = stand_in(|_, _| (200, "<html>".to_string())).await;
+        assert_eq!(kind(&url, second).await, FetchErrorKind::Decode);
+
+        let silent = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", silent.local_addr().unwrap());
+        assert_eq!(
+            kind(&url, Duration::from_millis(100)).await,
+            FetchErrorKind::Timeout
+        );
+        drop(silent);
+        assert_eq!(kind(&url, second).await, FetchErrorKind::Connection);
+    }
+}
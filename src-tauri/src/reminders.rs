@@ -0,0 +1,416 @@
+//! Reminders ahead of outages at the configured address, at the lead times set per
+//! source in `Settings.reminderLeadMinutes`. They are planned from the current alerts
+//! on every scheduler tick, so a moved alert is reminded about at its new time and a
+//! vanished one not at all. The reminders already shown are kept in `reminders.json`.
+
+use crate::alert_diff::{AlertChange, ChangeKind};
+use crate::api_logic::{parse_alert_time, AlertSource, Settings, UnifiedAlert};
+use crate::notifier::{self, affects_address, outage_name, Lang, Outgoing};
+use crate::rules::{self, EventKind};
+use crate::scheduler::{source_enabled, AlertStore};
+use crate::settings_store::{read_json, store_json, SettingsCache};
+use chrono::{Duration, Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+pub const REMINDERS_FILE: &str = "reminders.json";
+
+/// A reminder that was shown, or skipped because it was already due when the alert
+/// appeared. Keyed by the start it was for, so a moved alert gets reminded again.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct FiredReminder {
+    alert_id: String,
+    start_date: String,
+    lead_minutes: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedReminder {
+    pub alert_id: String,
+    pub source: AlertSource,
+    pub start_date: String,
+    pub lead_minutes: u32,
+    /// Local time, like the alert dates.
+    pub due_at: String,
+}
+
+/// Lead times for `source`, longest first.
+pub fn lead_times(settings: &Settings, source: &AlertSource) -> Vec<u32> {
+    let mut leads: Vec<u32> = settings
+        .reminderLeadMinutes
+        .as_ref()
+        .and_then(|leads| leads.get(source.as_str()))
+        .map(|leads| leads.iter().copied().filter(|&m| m > 0).collect())
+        .unwrap_or_default();
+    leads.sort_unstable_by(|a, b| b.cmp(a));
+    leads.dedup();
+    leads
+}
+
+fn due_at(start: NaiveDateTime, lead_minutes: u32) -> NaiveDateTime {
+    start - Duration::minutes(lead_minutes as i64)
+}
+
+/// "in 2 h", "za 3 dni": how long until the start, rounded the way people say it.
+pub fn time_until(remaining: Duration, lang: Lang) -> String {
+    let minutes = ((remaining.num_seconds() + 59) / 60).max(1);
+    match lang {
+        Lang::Pl if minutes >= 2 * 1440 => format!("za {} dni", (minutes + 720) / 1440),
+        Lang::Pl if minutes >= 90 => format!("za {} godz.", (minutes + 30) / 60),
+        Lang::Pl => format!("za {} min", minutes),
+        Lang::En if minutes >= 2 * 1440 => format!("in {} days", (minutes + 720) / 1440),
+        Lang::En if minutes >= 90 => format!("in {} h", (minutes + 30) / 60),
+        Lang::En => format!("in {} min", minutes),
+    }
+}
+
+pub fn reminder_title(alert: &UnifiedAlert, now: NaiveDateTime, lang: Lang) -> String {
+    let remaining = alert
+        .start_time()
+        .map_or(Duration::zero(), |start| start - now);
+    format!(
+        "{} {}",
+        outage_name(&alert.source, lang),
+        time_until(remaining, lang)
+    )
+}
+
+/// Alerts at the address from enabled sources that haven't started yet, with their start.
+fn upcoming<'a>(
+    alerts: &'a [UnifiedAlert],
+    settings: &'a Settings,
+    now: NaiveDateTime,
+) -> impl Iterator<Item = (&'a UnifiedAlert, &'a str, NaiveDateTime)> {
+    alerts
+        .iter()
+        .filter(move |a| source_enabled(Some(settings), &a.source) && affects_address(a, settings))
+        .filter_map(move |a| {
+            let start = a.start_time().filter(|&start| start > now)?;
+            Some((a, a.startDate.as_deref()?, start))
+        })
+}
+
+#[derive(Default)]
+pub struct Reminders {
+    fired: Mutex<Vec<FiredReminder>>,
+    path: Option<PathBuf>,
+}
+
+impl Reminders {
+    pub fn open(path: PathBuf) -> Self {
+        Reminders {
+            fired: Mutex::new(read_json(&path)),
+            path: Some(path),
+        }
+    }
+
+    fn store(&self, fired: &[FiredReminder]) {
+        store_json(self.path.as_deref(), &fired, "reminders");
+    }
+
+    /// Reminders still to come for `alerts`, earliest first.
+    pub fn planned(
+        &self,
+        alerts: &[UnifiedAlert],
+        settings: &Settings,
+        now: NaiveDateTime,
+    ) -> Vec<PlannedReminder> {
+        let fired = self.fired.lock().map(|f| f.clone()).unwrap_or_default();
+        let mut planned: Vec<PlannedReminder> = upcoming(alerts, settings, now)
+            .flat_map(|(alert, start_date, start)| {
+                lead_times(settings, &alert.source)
+                    .into_iter()
+                    .map(move |lead| PlannedReminder {
+                        alert_id: alert.id.clone(),
+                        source: alert.source,
+                        start_date: start_date.to_string(),
+                        lead_minutes: lead,
+                        due_at: due_at(start, lead).format("%Y-%m-%dT%H:%M:%S").to_string(),
+                    })
+            })
+            .filter(|p| {
+                !fired.iter().any(|f| {
+                    f.alert_id == p.alert_id
+                        && f.start_date == p.start_date
+                        && f.lead_minutes == p.lead_minutes
+                })
+            })
+            .collect();
+        planned.sort_by(|a, b| a.due_at.cmp(&b.due_at));
+        planned
+    }
+
+    /// Alerts with a reminder due at `now`, marking their due reminders as fired. An
+    /// alert with several leads due (the app was closed) is reminded about once.
+    pub fn take_due<'a>(
+        &self,
+        alerts: &'a [UnifiedAlert],
+        settings: &Settings,
+        now: NaiveDateTime,
+    ) -> Vec<&'a UnifiedAlert> {
+        let due: Vec<PlannedReminder> = self
+            .planned(alerts, settings, now)
+            .into_iter()
+            .filter(|p| parse_alert_time(&p.due_at).is_some_and(|due| due <= now))
+            .collect();
+        let Ok(mut fired) = self.fired.lock() else {
+            return Vec::new();
+        };
+        // Forget reminders for outages that have started
+        let before = fired.len();
+        fired.retain(|f| parse_alert_time(&f.start_date).is_some_and(|start| start > now));
+        let pruned = fired.len() != before;
+
+        fired.extend(due.iter().map(|p| FiredReminder {
+            alert_id: p.alert_id.clone(),
+            start_date: p.start_date.clone(),
+            lead_minutes: p.lead_minutes,
+        }));
+        if pruned || !due.is_empty() {
+            self.store(&fired);
+        }
+        alerts
+            .iter()
+            .filter(|a| due.iter().any(|p| p.alert_id == a.id))
+            .collect()
+    }
+
+    /// Follow alert changes: reminders already due when an alert first shows up are
+    /// skipped (it was just announced), a reworded alert keeps the reminders it had,
+    /// and a removed one drops them.
+    pub fn record_changes(&self, changes: &[AlertChange], settings: &Settings, now: NaiveDateTime) {
+        let Ok(mut fired) = self.fired.lock() else {
+            return;
+        };
+        let before = fired.clone();
+        for change in changes {
+            let alert = &change.alert;
+            match change.kind {
+                ChangeKind::Added => {
+                    let (Some(start), Some(start_date)) =
+                        (alert.start_time(), alert.startDate.as_ref())
+                    else {
+                        continue;
+                    };
+                    fired.extend(
+                        lead_times(settings, &alert.source)
+                            .into_iter()
+                            .filter(|&lead| due_at(start, lead) <= now)
+                            .map(|lead| FiredReminder {
+                                alert_id: alert.id.clone(),
+                                start_date: start_date.clone(),
+                                lead_minutes: lead,
+                            }),
+                    );
+                }
+                ChangeKind::TextChanged => {
+                    let previous_id = change.previous.as_ref().map_or(&alert.id, |p| &p.id);
+                    for f in fired.iter_mut().filter(|f| &f.alert_id == previous_id) {
+                        f.alert_id = alert.id.clone();
+                    }
+                }
+                ChangeKind::Removed | ChangeKind::Cancelled => {
+                    fired.retain(|f| f.alert_id != alert.id)
+                }
+                // The new start has reminders of its own
+                ChangeKind::TimeChanged | ChangeKind::Overrunning => {}
+            }
+        }
+        if *fired != before {
+            self.store(&fired);
+        }
+    }
+}
+
+/// Let `Reminders` follow the changes a poll found.
+pub fn record_changes(app: &AppHandle, settings: Option<&Settings>, changes: &[AlertChange]) {
+    if let Some(settings) = settings {
+        app.state::<Reminders>()
+            .record_changes(changes, settings, Local::now().naive_local());
+    }
+}
+
+/// Show the reminders that are due, as the notification rules say. Needs `Reminders`,
+/// `Notifier`, `AlertStore` and `SettingsCache` managed.
+pub fn check(app: &AppHandle) {
+    let Ok(Some(settings)) = app.state::<SettingsCache>().get() else {
+        return;
+    };
+    let alerts = app.state::<AlertStore>().snapshot(Some(&settings)).alerts;
+    let now = Local::now().naive_local();
+    let due = app.state::<Reminders>().take_due(&alerts, &settings, now);
+    let lang = notifier::language(Some(&settings));
+    let outgoing = due
+        .into_iter()
+        .filter_map(|alert| {
+            let action = rules::evaluate(alert, EventKind::Reminder, &settings, now).action;
+            action.notifies().then(|| Outgoing {
+                event: Some(EventKind::Reminder),
+                ..Outgoing::new(reminder_title(alert, now, lang), alert, action, lang)
+            })
+        })
+        .collect();
+    notifier::deliver(app, &settings, outgoing);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert_diff::diff;
+    use crate::test_support::{self, temp_dir};
+    use std::collections::BTreeMap;
+    use std::slice;
+
+    fn settings() -> Settings {
+        Settings {
+            reminderLeadMinutes: Some(BTreeMap::from([("tauron".to_string(), vec![120, 1440, 0])])),
+            ..test_support::settings()
+        }
+    }
+
+    /// A power outage without a known end.
+    fn alert(id: &str, start: &str, message: &str) -> UnifiedAlert {
+        UnifiedAlert {
+            endDate: None,
+            ..test_support::alert(id, AlertSource::Tauron, start, start, message)
+        }
+    }
+
+    fn at(value: &str) -> NaiveDateTime {
+        parse_alert_time(value).unwrap()
+    }
+
+    #[test]
+    fn test_planned_reminders() {
+        let reminders = Reminders::default();
+        let here = alert("tauron-1", "2026-03-12T08:00:00", "Kuźnicza 1-30");
+        let elsewhere = alert("tauron-2", "2026-03-12T08:00:00", "Legnicka 5");
+        let planned = reminders.planned(&[here, elsewhere], &settings(), at("2026-03-10T12:00:00"));
+        let due: Vec<&str> = planned.iter().map(|p| p.due_at.as_str()).collect();
+        assert_eq!(due, vec!["2026-03-11T08:00:00", "2026-03-12T06:00:00"]);
+        assert_eq!(
+            lead_times(&settings(), &AlertSource::Water),
+            Vec::<u32>::new()
+        );
+
+        // Outages at a saved location get reminders as well
+        let plot = alert("tauron-3", "2026-03-12T08:00:00", "Ruska 5");
+        assert_eq!(
+            reminders
+                .planned(&[plot], &settings(), at("2026-03-10T12:00:00"))
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_take_due_once_and_after_restart() {
+        let dir = temp_dir("reminders");
+        let path = dir.join(REMINDERS_FILE);
+        let s = settings();
+        let alerts = vec![alert("tauron-1", "2026-03-12T08:00:00", "Kuźnicza 1-30")];
+
+        let reminders = Reminders::open(path.clone());
+        assert!(reminders
+            .take_due(&alerts, &s, at("2026-03-11T07:59:00"))
+            .is_empty());
+        assert_eq!(
+            reminders
+                .take_due(&alerts, &s, at("2026-03-11T08:00:30"))
+                .len(),
+            1
+        );
+        assert!(reminders
+            .take_due(&alerts, &s, at("2026-03-11T08:01:00"))
+            .is_empty());
+
+        // Closed through both lead times: one reminder, then nothing
+        let reminders = Reminders::open(path.clone());
+        assert_eq!(
+            reminders
+                .planned(&alerts, &s, at("2026-03-11T09:00:00"))
+                .len(),
+            1
+        );
+        std::fs::remove_file(&path).unwrap();
+        let reminders = Reminders::open(path.clone());
+        assert_eq!(
+            reminders
+                .take_due(&alerts, &s, at("2026-03-12T07:00:00"))
+                .len(),
+            1
+        );
+        assert!(Reminders::open(path)
+            .planned(&alerts, &s, at("2026-03-12T07:00:00"))
+            .is_empty());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_reminders_follow_changes() {
+        let s = settings();
+        let reminders = Reminders::default();
+        let now = at("2026-03-11T12:00:00");
+        let before = alert("tauron-1", "2026-03-12T08:00:00", "Kuźnicza 1-30");
+
+        // Appeared after the 24 h mark: only the 2 h reminder is left
+        reminders.record_changes(&diff(&[], slice::from_ref(&before)), &s, now);
+        let planned = reminders.planned(slice::from_ref(&before), &s, now);
+        assert_eq!(
+            planned.iter().map(|p| p.lead_minutes).collect::<Vec<_>>(),
+            vec![120]
+        );
+
+        // Reworded: still only the 2 h one
+        let reworded = alert("tauron-2", "2026-03-12T08:00:00", "Kuźnicza 1-32");
+        reminders.record_changes(
+            &diff(slice::from_ref(&before), slice::from_ref(&reworded)),
+            &s,
+            now,
+        );
+        assert_eq!(
+            reminders.planned(slice::from_ref(&reworded), &s, now).len(),
+            1
+        );
+
+        // Moved: planned again for the new start
+        let moved = alert("tauron-3", "2026-03-14T08:00:00", "Kuźnicza 1-32");
+        reminders.record_changes(
+            &diff(slice::from_ref(&reworded), slice::from_ref(&moved)),
+            &s,
+            now,
+        );
+        let planned = reminders.planned(slice::from_ref(&moved), &s, now);
+        assert_eq!(planned[0].due_at, "2026-03-13T08:00:00");
+
+        // Gone: nothing planned
+        reminders.record_changes(&diff(slice::from_ref(&moved), &[]), &s, now);
+        assert!(reminders.planned(&[], &s, now).is_empty());
+    }
+
+    #[test]
+    fn test_reminder_title() {
+        let a = alert("tauron-1", "2026-03-12T08:00:00", "Kuźnicza");
+        assert_eq!(
+            reminder_title(&a, at("2026-03-11T08:00:30"), Lang::En),
+            "Power outage in 24 h"
+        );
+        assert_eq!(
+            reminder_title(&a, at("2026-03-12T06:00:00"), Lang::Pl),
+            "Wyłączenie prądu za 2 godz."
+        );
+        assert_eq!(
+            reminder_title(&a, at("2026-03-12T07:30:00"), Lang::En),
+            "Power outage in 30 min"
+        );
+        assert_eq!(
+            reminder_title(&a, at("2026-03-09T08:00:00"), Lang::Pl),
+            "Wyłączenie prądu za 3 dni"
+        );
+    }
+}
@@ -0,0 +1,595 @@
+//! User-defined notification rules, stored in `Settings.notificationRules`. The first
+//! rule whose conditions all hold decides what happens to an alert event; without a
+//! match, new, rescheduled, cancelled and overrunning alerts and reminders at the
+//! address notify and everything else is ignored. Quiet hours hold back everything but `notify_now` until they end.
+
+use crate::api_logic::{AlertSource, Settings, UnifiedAlert};
+use crate::notifier::affects_address;
+use chrono::{NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+/// What happened to an alert.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Added,
+    #[serde(alias = "rescheduled")]
+    TimeChanged,
+    TextChanged,
+    /// Gone after it started.
+    Removed,
+    /// Gone before it started.
+    Cancelled,
+    /// Still listed after its end.
+    Overrunning,
+    Reminder,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Added => "added",
+            EventKind::TimeChanged => "time_changed",
+            EventKind::TextChanged => "text_changed",
+            EventKind::Removed => "removed",
+            EventKind::Cancelled => "cancelled",
+            EventKind::Overrunning => "overrunning",
+            EventKind::Reminder => "reminder",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleAction {
+    /// Notify with sound, also during quiet hours.
+    NotifyNow,
+    Notify,
+    /// Notify without sound.
+    NotifySilent,
+    /// Only in the daily digest.
+    DigestOnly,
+    Ignore,
+}
+
+impl RuleAction {
+    pub fn notifies(&self) -> bool {
+        matches!(
+            self,
+            RuleAction::NotifyNow | RuleAction::Notify | RuleAction::NotifySilent
+        )
+    }
+}
+
+/// Conditions of a rule; unset ones don't restrict, so an empty condition matches all.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[allow(non_snake_case)]
+pub struct RuleCondition {
+    /// Any of "tauron", "water", "fortum".
+    #[serde(default)]
+    pub sources: Option<Vec<String>>,
+    #[serde(default)]
+    pub kinds: Option<Vec<EventKind>>,
+    #[serde(default)]
+    pub affectsAddress: Option<bool>,
+    #[serde(default)]
+    pub minDurationMinutes: Option<u32>,
+    #[serde(default)]
+    pub maxDurationMinutes: Option<u32>,
+    /// Starts at most this many hours from now (or has started).
+    #[serde(default)]
+    pub startsWithinHours: Option<u32>,
+    /// Start time of day window, "HH:MM"; may wrap past midnight.
+    #[serde(default)]
+    pub startTimeFrom: Option<String>,
+    #[serde(default)]
+    pub startTimeTo: Option<String>,
+    /// Any of these in the message or description, ignoring case.
+    #[serde(default)]
+    pub keywords: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[allow(non_snake_case)]
+pub struct NotificationRule {
+    pub name: String,
+    #[serde(default)]
+    pub when: RuleCondition,
+    pub action: RuleAction,
+}
+
+/// Daily window, "HH:MM" to "HH:MM", possibly over midnight.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[allow(non_snake_case)]
+pub struct QuietHours {
+    pub start: String,
+    pub end: String,
+}
+
+/// The computed attributes rules look at.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertFacts {
+    pub kind: EventKind,
+    pub affects_address: bool,
+    pub duration_minutes: Option<i64>,
+    /// Negative once it has started.
+    pub starts_in_minutes: Option<i64>,
+    /// "HH:MM".
+    pub start_time: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Decision {
+    pub action: RuleAction,
+    /// Name of the deciding rule, `None` for the default.
+    pub rule: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleEvaluation {
+    pub alert: UnifiedAlert,
+    pub facts: AlertFacts,
+    pub decision: Decision,
+    /// Would wait for the end of quiet hours if it happened now.
+    pub held: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRunReport {
+    pub evaluations: Vec<RuleEvaluation>,
+    pub errors: Vec<String>,
+}
+
+pub fn parse_time_of_day(value: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M").ok()
+}
+
+/// `from <= time < to`, wrapping past midnight when `to` is earlier.
+fn in_window(from: NaiveTime, to: NaiveTime, time: NaiveTime) -> bool {
+    if from <= to {
+        from <= time && time < to
+    } else {
+        time >= from || time < to
+    }
+}
+
+pub fn facts(
+    alert: &UnifiedAlert,
+    kind: EventKind,
+    settings: &Settings,
+    now: NaiveDateTime,
+) -> AlertFacts {
+    let start = alert.start_time();
+    AlertFacts {
+        kind,
+        affects_address: affects_address(alert, settings),
+        duration_minutes: start
+            .zip(alert.end_time())
+            .map(|(start, end)| (end - start).num_minutes()),
+        starts_in_minutes: start.map(|start| (start - now).num_minutes()),
+        start_time: start.map(|start| start.format("%H:%M").to_string()),
+    }
+}
+
+fn source_matches(sources: &[String], source: &AlertSource) -> bool {
+    sources.iter().any(|s| s == source.as_str())
+}
+
+impl RuleCondition {
+    pub fn matches(&self, alert: &UnifiedAlert, facts: &AlertFacts) -> bool {
+        if self
+            .sources
+            .as_ref()
+            .is_some_and(|s| !source_matches(s, &alert.source))
+        {
+            return false;
+        }
+        if self
+            .kinds
+            .as_ref()
+            .is_some_and(|k| !k.contains(&facts.kind))
+        {
+            return false;
+        }
+        if self
+            .affectsAddress
+            .is_some_and(|a| a != facts.affects_address)
+        {
+            return false;
+        }
+        if let Some(min) = self.minDurationMinutes {
+            if !facts.duration_minutes.is_some_and(|d| d >= min as i64) {
+                return false;
+            }
+        }
+        if let Some(max) = self.maxDurationMinutes {
+            if !facts.duration_minutes.is_some_and(|d| d <= max as i64) {
+                return false;
+            }
+        }
+        if let Some(hours) = self.startsWithinHours {
+            if !facts
+                .starts_in_minutes
+                .is_some_and(|m| m <= hours as i64 * 60)
+            {
+                return false;
+            }
+        }
+        if self.startTimeFrom.is_some() || self.startTimeTo.is_some() {
+            let from = self
+                .startTimeFrom
+                .as_deref()
+                .map_or(Some(NaiveTime::MIN), parse_time_of_day);
+            let to = self
+                .startTimeTo
+                .as_deref()
+                .map_or(Some(NaiveTime::MIN), parse_time_of_day);
+            let start = facts.start_time.as_deref().and_then(parse_time_of_day);
+            let inside = match (from, to, start) {
+                (Some(from), Some(to), Some(start)) => from == to || in_window(from, to, start),
+                _ => false,
+            };
+            if !inside {
+                return false;
+            }
+        }
+        if let Some(keywords) = &self.keywords {
+            let text = [alert.message.as_deref(), alert.description.as_deref()]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .to_lowercase();
+            if !keywords
+                .iter()
+                .any(|k| !k.trim().is_empty() && text.contains(&k.trim().to_lowercase()))
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// What happens when nothing matches.
+pub fn default_action(facts: &AlertFacts) -> RuleAction {
+    match facts.kind {
+        EventKind::Added
+        | EventKind::TimeChanged
+        | EventKind::Cancelled
+        | EventKind::Overrunning
+            if facts.affects_address =>
+        {
+            RuleAction::Notify
+        }
+        EventKind::Reminder => RuleAction::Notify,
+        _ => RuleAction::Ignore,
+    }
+}
+
+pub fn decide(rules: &[NotificationRule], alert: &UnifiedAlert, facts: &AlertFacts) -> Decision {
+    match rules.iter().find(|rule| rule.when.matches(alert, facts)) {
+        Some(rule) => Decision {
+            action: rule.action,
+            rule: Some(rule.name.clone()),
+        },
+        None => Decision {
+            action: default_action(facts),
+            rule: None,
+        },
+    }
+}
+
+/// Decide with the rules in `settings`.
+pub fn evaluate(
+    alert: &UnifiedAlert,
+    kind: EventKind,
+    settings: &Settings,
+    now: NaiveDateTime,
+) -> Decision {
+    decide(
+        &settings.notificationRules,
+        alert,
+        &facts(alert, kind, settings, now),
+    )
+}
+
+pub fn in_quiet_hours(quiet: Option<&QuietHours>, now: NaiveDateTime) -> bool {
+    let Some(quiet) = quiet else {
+        return false;
+    };
+    match (
+        parse_time_of_day(&quiet.start),
+        parse_time_of_day(&quiet.end),
+    ) {
+        (Some(start), Some(end)) => start != end && in_window(start, end, now.time()),
+        _ => false,
+    }
+}
+
+/// Whether a notification with `action` waits for quiet hours to end.
+pub fn is_held(action: RuleAction, settings: &Settings, now: NaiveDateTime) -> bool {
+    action.notifies()
+        && action != RuleAction::NotifyNow
+        && in_quiet_hours(settings.quietHours.as_ref(), now)
+}
+
+/// Problems that make rules or quiet hours silently not apply.
+pub fn rule_errors(rules: &[NotificationRule], quiet: Option<&QuietHours>) -> Vec<String> {
+    let mut errors = Vec::new();
+    for rule in rules {
+        let when = &rule.when;
+        for (field, value) in [
+            ("startTimeFrom", &when.startTimeFrom),
+            ("startTimeTo", &when.startTimeTo),
+        ] {
+            if let Some(value) = value.as_deref().filter(|v| parse_time_of_day(v).is_none()) {
+                errors.push(format!(
+                    "Rule \"{}\": {} \"{}\" is not HH:MM",
+                    rule.name, field, value
+                ));
+            }
+        }
+        if let Some(sources) = &when.sources {
+            for source in sources
+                .iter()
+                .filter(|s| !["tauron", "water", "fortum"].contains(&s.as_str()))
+            {
+                errors.push(format!(
+                    "Rule \"{}\": unknown source \"{}\"",
+                    rule.name, source
+                ));
+            }
+        }
+        if let (Some(min), Some(max)) = (when.minDurationMinutes, when.maxDurationMinutes) {
+            if min > max {
+                errors.push(format!(
+                    "Rule \"{}\": minDurationMinutes is above maxDurationMinutes",
+                    rule.name
+                ));
+            }
+        }
+    }
+    if let Some(quiet) = quiet {
+        for value in [&quiet.start, &quiet.end] {
+            if parse_time_of_day(value).is_none() {
+                errors.push(format!("Quiet hours: \"{}\" is not HH:MM", value));
+            }
+        }
+    }
+    errors
+}
+
+/// How `rules` would treat `alerts` if they all appeared now.
+pub fn dry_run(
+    alerts: &[UnifiedAlert],
+    rules: &[NotificationRule],
+    settings: &Settings,
+    now: NaiveDateTime,
+) -> DryRunReport {
+    let evaluations = alerts
+        .iter()
+        .map(|alert| {
+            let facts = facts(alert, EventKind::Added, settings, now);
+            let decision = decide(rules, alert, &facts);
+            RuleEvaluation {
+                alert: alert.clone(),
+                held: is_held(decision.action, settings, now),
+                facts,
+                decision,
+            }
+        })
+        .collect();
+    DryRunReport {
+        evaluations,
+        errors: rule_errors(rules, settings.quietHours.as_ref()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_logic::parse_alert_time;
+    use crate::test_support::{alert, settings};
+
+    fn rule(name: &str, when: RuleCondition, action: RuleAction) -> NotificationRule {
+        NotificationRule {
+            name: name.to_string(),
+            when,
+            action,
+        }
+    }
+
+    fn at(value: &str) -> NaiveDateTime {
+        parse_alert_time(value).unwrap()
+    }
+
+    #[test]
+    fn test_defaults_without_rules() {
+        let s = settings();
+        let now = at("2026-03-11T12:00:00");
+        let here = alert(
+            "tauron-1",
+            AlertSource::Tauron,
+            "2026-03-12T08:00:00",
+            "2026-03-12T16:00:00",
+            "Kuźnicza 1-30",
+        );
+        let elsewhere = alert(
+            "tauron-1",
+            AlertSource::Tauron,
+            "2026-03-12T08:00:00",
+            "2026-03-12T16:00:00",
+            "Legnicka 5",
+        );
+        assert_eq!(
+            evaluate(&here, EventKind::Added, &s, now).action,
+            RuleAction::Notify
+        );
+        assert_eq!(
+            evaluate(&elsewhere, EventKind::Added, &s, now).action,
+            RuleAction::Ignore
+        );
+        assert_eq!(
+            evaluate(&here, EventKind::Removed, &s, now).action,
+            RuleAction::Ignore
+        );
+        assert_eq!(
+            evaluate(&here, EventKind::Cancelled, &s, now).action,
+            RuleAction::Notify
+        );
+        assert_eq!(
+            evaluate(&here, EventKind::Overrunning, &s, now).action,
+            RuleAction::Notify
+        );
+        assert_eq!(
+            evaluate(&here, EventKind::TextChanged, &s, now).action,
+            RuleAction::Ignore
+        );
+
+        let f = facts(&here, EventKind::Added, &s, now);
+        assert_eq!(f.duration_minutes, Some(480));
+        assert_eq!(f.starts_in_minutes, Some(20 * 60));
+        assert_eq!(f.start_time.as_deref(), Some("08:00"));
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let s = settings();
+        let now = at("2026-03-11T12:00:00");
+        let rules = vec![
+            rule(
+                "short water cuts",
+                RuleCondition {
+                    sources: Some(vec!["water".to_string()]),
+                    maxDurationMinutes: Some(60),
+                    ..Default::default()
+                },
+                RuleAction::DigestOnly,
+            ),
+            rule(
+                "night work",
+                RuleCondition {
+                    startTimeFrom: Some("22:00".to_string()),
+                    startTimeTo: Some("06:00".to_string()),
+                    ..Default::default()
+                },
+                RuleAction::NotifySilent,
+            ),
+            rule(
+                "failures anywhere",
+                RuleCondition {
+                    keywords: Some(vec!["AWARIA".to_string()]),
+                    startsWithinHours: Some(2),
+                    ..Default::default()
+                },
+                RuleAction::NotifyNow,
+            ),
+        ];
+
+        let short = alert(
+            "water-1",
+            AlertSource::Water,
+            "2026-03-12T23:00:00",
+            "2026-03-12T23:30:00",
+            "Kuźnicza",
+        );
+        let night = alert(
+            "water-1",
+            AlertSource::Water,
+            "2026-03-12T23:00:00",
+            "2026-03-13T03:00:00",
+            "Kuźnicza",
+        );
+        let failure = alert(
+            "water-1",
+            AlertSource::Water,
+            "2026-03-11T12:30:00",
+            "2026-03-11T18:00:00",
+            "Awaria sieci, Legnicka",
+        );
+        let later_failure = alert(
+            "water-1",
+            AlertSource::Water,
+            "2026-03-11T20:00:00",
+            "2026-03-12T02:00:00",
+            "Awaria, Legnicka",
+        );
+
+        let decide_now = |a: &UnifiedAlert| decide(&rules, a, &facts(a, EventKind::Added, &s, now));
+        assert_eq!(decide_now(&short).rule.as_deref(), Some("short water cuts"));
+        assert_eq!(decide_now(&night).action, RuleAction::NotifySilent);
+        assert_eq!(decide_now(&failure).action, RuleAction::NotifyNow);
+        // Too far off for the last rule, and not at the address
+        assert_eq!(
+            decide_now(&later_failure),
+            Decision {
+                action: RuleAction::Ignore,
+                rule: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_quiet_hours_hold_all_but_urgent() {
+        let s = Settings {
+            quietHours: Some(QuietHours {
+                start: "22:00".to_string(),
+                end: "07:00".to_string(),
+            }),
+            ..settings()
+        };
+        assert!(is_held(RuleAction::Notify, &s, at("2026-03-11T23:15:00")));
+        assert!(is_held(
+            RuleAction::NotifySilent,
+            &s,
+            at("2026-03-12T06:59:00")
+        ));
+        assert!(!is_held(RuleAction::Notify, &s, at("2026-03-12T07:00:00")));
+        assert!(!is_held(
+            RuleAction::NotifyNow,
+            &s,
+            at("2026-03-11T23:15:00")
+        ));
+        assert!(!is_held(
+            RuleAction::DigestOnly,
+            &s,
+            at("2026-03-11T23:15:00")
+        ));
+    }
+
+    #[test]
+    fn test_dry_run_reports_errors() {
+        let s = settings();
+        let rules = vec![rule(
+            "bad",
+            RuleCondition {
+                sources: Some(vec!["gas".to_string()]),
+                startTimeFrom: Some("25:00".to_string()),
+                ..Default::default()
+            },
+            RuleAction::Ignore,
+        )];
+        let here = alert(
+            "tauron-1",
+            AlertSource::Tauron,
+            "2026-03-12T08:00:00",
+            "2026-03-12T16:00:00",
+            "Kuźnicza",
+        );
+        let report = dry_run(&[here], &rules, &s, at("2026-03-11T12:00:00"));
+        assert_eq!(report.errors.len(), 2);
+        assert_eq!(report.evaluations[0].decision.action, RuleAction::Notify);
+        assert!(!report.evaluations[0].held);
+
+        let parsed: NotificationRule = serde_json::from_str(
+            r#"{"name":"x","when":{"kinds":["rescheduled"]},"action":"notify_now"}"#,
+        )
+        .unwrap();
+        assert_eq!(parsed.when.kinds, Some(vec![EventKind::TimeChanged]));
+    }
+}
@@ -0,0 +1,1079 @@
+//! Background polling: every enabled source is fetched on its own interval, results
+//! are kept in the managed `AlertStore` and announced with `alerts-updated`, and what
+//! changed since the previous fetch with `alerts-changed` (plus `alert-rescheduled`,
+//! `alert-cancelled` and `alert-overrunning` for those). Rescheduled alerts keep their
+//! previous window and cancelled ones stay listed until their start, both for display.
+
+use crate::alert_diff::{self, AlertChange, ChangeKind};
+use crate::api_logic::{AlertSource, Settings, UnifiedAlert};
+use crate::providers::{self, tauron_key, tauron_locations, Fetched, ALL_SOURCES};
+use crate::settings_store::{write_atomic, SettingsCache};
+use crate::{digest, local_api, mqtt, notifier, reminders};
+use chrono::{DateTime, Duration, Local, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Event sent to the frontend with an `AlertSnapshot` after each poll.
+pub const ALERTS_UPDATED_EVENT: &str = "alerts-updated";
+
+/// Event with the `AlertChange`s found by a poll, when there are any.
+pub const ALERTS_CHANGED_EVENT: &str = "alerts-changed";
+
+/// Event with the `AlertChange` of an alert whose window moved.
+pub const ALERT_RESCHEDULED_EVENT: &str = "alert-rescheduled";
+
+/// Event with the `AlertChange` of an alert that disappeared before its start.
+pub const ALERT_CANCELLED_EVENT: &str = "alert-cancelled";
+
+/// Event with the `AlertChange` of an alert still listed after its end.
+pub const ALERT_OVERRUNNING_EVENT: &str = "alert-overrunning";
+
+/// Last known alerts and unseen changes, next to `settings.json`, so a restart
+/// doesn't make every alert look new.
+pub const ALERT_STATE_FILE: &str = "alert-state.json";
+
+/// How often the scheduler checks whether a source is due.
+const TICK: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Lower bound for `Settings.pollIntervals`, to stay polite to the providers.
+const MIN_INTERVAL_MINUTES: u32 = 5;
+
+pub fn default_interval_minutes(source: &AlertSource) -> u32 {
+    match source {
+        AlertSource::Tauron => 30,
+        AlertSource::Water => 15,
+        AlertSource::Fortum => 15,
+    }
+}
+
+pub fn poll_interval(settings: Option<&Settings>, source: &AlertSource) -> Duration {
+    let minutes = settings
+        .and_then(|s| s.pollIntervals.as_ref())
+        .and_then(|intervals| intervals.get(source.as_str()).copied())
+        .unwrap_or_else(|| default_interval_minutes(source))
+        .max(MIN_INTERVAL_MINUTES);
+    Duration::minutes(minutes as i64)
+}
+
+/// Default for `Settings.overrunAfterHours`.
+pub const DEFAULT_OVERRUN_HOURS: u32 = 8;
+
+/// How long an alert without an end may run before it counts as overrunning.
+pub fn overrun_after(settings: Option<&Settings>) -> Duration {
+    let hours = settings
+        .and_then(|s| s.overrunAfterHours)
+        .unwrap_or(DEFAULT_OVERRUN_HOURS)
+        .max(1);
+    Duration::hours(hours as i64)
+}
+
+/// Sources the user has turned on; Tauron also needs an address with GAIDs.
+pub fn source_enabled(settings: Option<&Settings>, source: &AlertSource) -> bool {
+    let listed = settings
+        .and_then(|s| s.enabledSources.as_ref())
+        .map_or(true, |sources| sources.iter().any(|s| s == source.as_str()));
+    match source {
+        AlertSource::Tauron => listed && settings.is_some_and(|s| !tauron_locations(s).is_empty()),
+        _ => listed,
+    }
+}
+
+/// What a fetch was for: Tauron is fetched again as soon as an address changes.
+fn fetch_key(settings: Option<&Settings>, source: &AlertSource) -> String {
+    match (source, settings) {
+        (AlertSource::Tauron, Some(s)) => tauron_locations(s)
+            .iter()
+            .map(tauron_key)
+            .collect::<Vec<_>>()
+            .join(";"),
+        _ => String::new(),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceStatus {
+    pub source: AlertSource,
+    pub enabled: bool,
+    pub alert_count: usize,
+    pub last_fetch_at: Option<String>,
+    pub last_success_at: Option<String>,
+    pub last_error: Option<String>,
+    pub latency_ms: Option<u64>,
+    pub next_fetch_at: Option<String>,
+    /// Fetches since the app started, and the time they took together.
+    pub fetch_count: u64,
+    pub total_latency_ms: u64,
+    /// Failed fetches since the app started by `FetchErrorKind`.
+    pub error_counts: BTreeMap<String, u64>,
+}
+
+/// The window an alert had before it was rescheduled, the first one if it moved twice.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Reschedule {
+    pub alert_id: String,
+    pub previous_start_date: Option<String>,
+    pub previous_end_date: Option<String>,
+    pub rescheduled_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertSnapshot {
+    /// Alerts of the enabled sources; a failed fetch keeps the previous ones.
+    pub alerts: Vec<UnifiedAlert>,
+    pub sources: Vec<SourceStatus>,
+    /// Previous windows of rescheduled alerts among `alerts`.
+    pub rescheduled: Vec<Reschedule>,
+    /// Cancelled alerts whose start hasn't come yet.
+    pub cancelled: Vec<UnifiedAlert>,
+    /// Ids of alerts among `alerts` still listed after their end.
+    pub overrunning: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct SourceEntry {
+    alerts: Vec<UnifiedAlert>,
+    last_fetch_at: Option<DateTime<Utc>>,
+    last_success_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    latency_ms: Option<u64>,
+    fetch_count: u64,
+    total_latency_ms: u64,
+    error_counts: BTreeMap<String, u64>,
+    fetch_key: String,
+    /// Ids of the alerts each address listed last, by `Fetched` key.
+    listed_by: BTreeMap<String, Vec<String>>,
+    rescheduled: Vec<Reschedule>,
+    cancelled: Vec<UnifiedAlert>,
+    overrunning: Vec<String>,
+}
+
+impl SourceEntry {
+    /// Keep the previous windows and cancellations in `changes` for display.
+    fn remember(
+        &mut self,
+        changes: &[AlertChange],
+        current: &[UnifiedAlert],
+        fetched_at: DateTime<Utc>,
+        now: NaiveDateTime,
+    ) {
+        for change in changes {
+            let alert = &change.alert;
+            let previous_id = change.previous.as_ref().map_or(&alert.id, |p| &p.id);
+            // Still the same late alert under a new id
+            for id in self.overrunning.iter_mut().filter(|id| *id == previous_id) {
+                *id = alert.id.clone();
+            }
+            let earlier = self
+                .rescheduled
+                .iter()
+                .position(|r| &r.alert_id == previous_id)
+                .map(|idx| self.rescheduled.remove(idx));
+            match change.kind {
+                ChangeKind::TimeChanged => {
+                    let previous = change.previous.as_ref();
+                    self.rescheduled.push(match earlier {
+                        Some(earlier) => Reschedule {
+                            alert_id: alert.id.clone(),
+                            rescheduled_at: fetched_at.to_rfc3339(),
+                            ..earlier
+                        },
+                        None => Reschedule {
+                            alert_id: alert.id.clone(),
+                            previous_start_date: previous.and_then(|p| p.startDate.clone()),
+                            previous_end_date: previous.and_then(|p| p.endDate.clone()),
+                            rescheduled_at: fetched_at.to_rfc3339(),
+                        },
+                    });
+                }
+                ChangeKind::TextChanged => {
+                    self.rescheduled.extend(earlier.map(|earlier| Reschedule {
+                        alert_id: alert.id.clone(),
+                        ..earlier
+                    }));
+                }
+                ChangeKind::Cancelled => {
+                    self.cancelled.retain(|c| c.id != alert.id);
+                    self.cancelled.push(alert.clone());
+                }
+                ChangeKind::Added => self.cancelled.retain(|c| c.id != alert.id),
+                ChangeKind::Removed | ChangeKind::Overrunning => {}
+            }
+        }
+        self.rescheduled
+            .retain(|r| current.iter().any(|a| a.id == r.alert_id));
+        self.cancelled
+            .retain(|c| c.start_time().is_some_and(|start| start > now));
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct PersistedSource {
+    source: Option<AlertSource>,
+    fetch_key: String,
+    alerts: Vec<UnifiedAlert>,
+    #[serde(default)]
+    listed_by: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    rescheduled: Vec<Reschedule>,
+    #[serde(default)]
+    cancelled: Vec<UnifiedAlert>,
+    #[serde(default)]
+    overrunning: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct PersistedState {
+    sources: Vec<PersistedSource>,
+    unseen: Vec<AlertChange>,
+}
+
+/// Latest alerts and fetch status per source, shared by the scheduler and commands.
+#[derive(Default)]
+pub struct AlertStore {
+    entries: Mutex<HashMap<AlertSource, SourceEntry>>,
+    /// Changes the user hasn't looked at yet.
+    unseen: Mutex<Vec<AlertChange>>,
+    path: Option<PathBuf>,
+}
+
+impl AlertStore {
+    /// A store that remembers its alerts in `path` across restarts.
+    pub fn open(path: PathBuf) -> Self {
+        let state: PersistedState = fs::read_to_string(&path)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+        let entries = state
+            .sources
+            .into_iter()
+            .filter_map(|p| {
+                let entry = SourceEntry {
+                    alerts: p.alerts,
+                    fetch_key: p.fetch_key,
+                    listed_by: p.listed_by,
+                    rescheduled: p.rescheduled,
+                    cancelled: p.cancelled,
+                    overrunning: p.overrunning,
+                    ..Default::default()
+                };
+                Some((p.source?, entry))
+            })
+            .collect();
+        AlertStore {
+            entries: Mutex::new(entries),
+            unseen: Mutex::new(state.unseen),
+            path: Some(path),
+        }
+    }
+
+    fn persist(
+        &self,
+        path: &Path,
+        entries: &HashMap<AlertSource, SourceEntry>,
+    ) -> Result<(), String> {
+        let unseen = self.unseen.lock().map_err(|e| e.to_string())?.clone();
+        let state = PersistedState {
+            sources: ALL_SOURCES
+                .into_iter()
+                .filter_map(|source| {
+                    let entry = entries.get(&source)?;
+                    Some(PersistedSource {
+                        source: Some(source),
+                        fetch_key: entry.fetch_key.clone(),
+                        alerts: entry.alerts.clone(),
+                        listed_by: entry.listed_by.clone(),
+                        rescheduled: entry.rescheduled.clone(),
+                        cancelled: entry.cancelled.clone(),
+                        overrunning: entry.overrunning.clone(),
+                    })
+                })
+                .collect(),
+            unseen,
+        };
+        let json = serde_json::to_string(&state).map_err(|e| e.to_string())?;
+        write_atomic(path, json.as_bytes())
+    }
+
+    /// Changes since the user last looked, see `alert_diff::accumulate`.
+    pub fn unseen(&self) -> Vec<AlertChange> {
+        self.unseen.lock().map(|u| u.clone()).unwrap_or_default()
+    }
+
+    pub fn mark_seen(&self) -> Result<(), String> {
+        self.unseen.lock().map_err(|e| e.to_string())?.clear();
+        let entries = self.entries.lock().map_err(|e| e.to_string())?;
+        match &self.path {
+            Some(path) => self.persist(path, &entries),
+            None => Ok(()),
+        }
+    }
+
+    /// Store a fetch result, returning what changed since the previous successful fetch.
+    /// An address that failed keeps what it listed before; the alerts of an address
+    /// no longer asked about are dropped without counting as removed.
+    pub fn record(
+        &self,
+        source: AlertSource,
+        fetched: &Fetched,
+        fetched_at: DateTime<Utc>,
+        latency: std::time::Duration,
+        fetch_key: String,
+    ) -> Vec<AlertChange> {
+        let Ok(mut entries) = self.entries.lock() else {
+            return Vec::new();
+        };
+        let entry = entries.entry(source).or_default();
+        entry.last_fetch_at = Some(fetched_at);
+        entry.latency_ms = Some(latency.as_millis() as u64);
+        entry.fetch_count += 1;
+        entry.total_latency_ms += latency.as_millis() as u64;
+        entry.fetch_key = fetch_key;
+
+        let keys: Vec<&String> = fetched.0.iter().map(|(key, _)| key).collect();
+        let kept: Vec<&String> = entry
+            .listed_by
+            .iter()
+            .filter(|(key, _)| keys.contains(key))
+            .flat_map(|(_, ids)| ids)
+            .collect();
+        let gone: Vec<String> = entry
+            .listed_by
+            .iter()
+            .filter(|(key, _)| !keys.contains(key))
+            .flat_map(|(_, ids)| ids)
+            .filter(|id| !kept.contains(id))
+            .cloned()
+            .collect();
+        entry.alerts.retain(|a| !gone.contains(&a.id));
+        entry.rescheduled.retain(|r| !gone.contains(&r.alert_id));
+        entry.cancelled.retain(|c| !gone.contains(&c.id));
+        entry.overrunning.retain(|id| !gone.contains(id));
+
+        let mut listed_by = BTreeMap::new();
+        let mut alerts: Vec<UnifiedAlert> = Vec::new();
+        for (key, result) in &fetched.0 {
+            let listed = match result {
+                Ok(listed) => listed.clone(),
+                // State from before addresses were told apart belongs to all of them
+                Err(_) => match entry.listed_by.get(key) {
+                    Some(ids) => entry
+                        .alerts
+                        .iter()
+                        .filter(|a| ids.contains(&a.id))
+                        .cloned()
+                        .collect(),
+                    None if entry.listed_by.is_empty() => entry.alerts.clone(),
+                    None => Vec::new(),
+                },
+            };
+            listed_by.insert(
+                key.clone(),
+                listed.iter().map(|a| a.id.clone()).collect::<Vec<_>>(),
+            );
+            for alert in listed {
+                if !alerts.iter().any(|a| a.id == alert.id) {
+                    alerts.push(alert);
+                }
+            }
+        }
+
+        let changes = if fetched.answered() {
+            let now = fetched_at.with_timezone(&Local).naive_local();
+            let mut changes = alert_diff::diff(&entry.alerts, &alerts);
+            alert_diff::mark_cancelled(&mut changes, now);
+            entry.remember(&changes, &alerts, fetched_at, now);
+            entry.alerts = alerts;
+            entry.last_success_at = Some(fetched_at);
+            changes
+        } else {
+            Vec::new()
+        };
+        // Cancelled alerts stay with the address that listed them
+        for (key, ids) in listed_by.iter_mut() {
+            let before = entry.listed_by.get(key).into_iter().flatten();
+            let cancelled: Vec<String> = before
+                .filter(|id| entry.cancelled.iter().any(|c| &c.id == *id) && !ids.contains(id))
+                .cloned()
+                .collect();
+            ids.extend(cancelled);
+        }
+        entry.listed_by = listed_by;
+
+        match fetched.error() {
+            Some(e) => {
+                *entry
+                    .error_counts
+                    .entry(e.kind.as_str().to_string())
+                    .or_default() += 1;
+                entry.last_error = Some(e.to_string());
+            }
+            None => entry.last_error = None,
+        }
+
+        self.keep(&entries, &changes);
+        changes
+    }
+
+    /// Flag alerts of `source` that its last successful fetch still listed after
+    /// their end, each once. Returns the newly overrunning ones.
+    pub fn check_overruns(
+        &self,
+        source: AlertSource,
+        open_ended_after: Duration,
+    ) -> Vec<AlertChange> {
+        let Ok(mut entries) = self.entries.lock() else {
+            return Vec::new();
+        };
+        let Some(entry) = entries.get_mut(&source) else {
+            return Vec::new();
+        };
+        let Some(listed_at) = entry.last_success_at else {
+            return Vec::new();
+        };
+        let now = listed_at.with_timezone(&Local).naive_local();
+        let late = |a: &UnifiedAlert| {
+            alert_diff::due_end(a, open_ended_after).is_some_and(|end| end <= now)
+        };
+        let before = entry.overrunning.len();
+        // Gone, or moved to end later: may overrun again
+        entry
+            .overrunning
+            .retain(|id| entry.alerts.iter().any(|a| &a.id == id && late(a)));
+        let changes =
+            alert_diff::overrunning(&entry.alerts, &entry.overrunning, now, open_ended_after);
+        entry
+            .overrunning
+            .extend(changes.iter().map(|c| c.alert.id.clone()));
+        if entry.overrunning.len() != before || !changes.is_empty() {
+            self.keep(&entries, &changes);
+        }
+        changes
+    }
+
+    /// Add `changes` to the unseen ones and store the state.
+    fn keep(&self, entries: &HashMap<AlertSource, SourceEntry>, changes: &[AlertChange]) {
+        if let Ok(mut unseen) = self.unseen.lock() {
+            alert_diff::accumulate(&mut unseen, changes);
+        }
+        if let Some(path) = &self.path {
+            if let Err(e) = self.persist(path, entries) {
+                log::warn!("Could not store alert state: {}", e);
+            }
+        }
+    }
+
+    /// Enabled sources whose interval has passed or whose address changed.
+    pub fn due_sources(&self, settings: Option<&Settings>, now: DateTime<Utc>) -> Vec<AlertSource> {
+        let Ok(entries) = self.entries.lock() else {
+            return Vec::new();
+        };
+        ALL_SOURCES
+            .into_iter()
+            .filter(|source| source_enabled(settings, source))
+            .filter(|source| match entries.get(source) {
+                Some(entry) => {
+                    entry.fetch_key != fetch_key(settings, source)
+                        || entry
+                            .last_fetch_at
+                            .map_or(true, |at| now >= at + poll_interval(settings, source))
+                }
+                None => true,
+            })
+            .collect()
+    }
+
+    pub fn snapshot(&self, settings: Option<&Settings>) -> AlertSnapshot {
+        let Ok(entries) = self.entries.lock() else {
+            return AlertSnapshot {
+                alerts: Vec::new(),
+                sources: Vec::new(),
+                rescheduled: Vec::new(),
+                cancelled: Vec::new(),
+                overrunning: Vec::new(),
+            };
+        };
+        let mut alerts = Vec::new();
+        let mut sources = Vec::new();
+        let mut rescheduled = Vec::new();
+        let mut cancelled = Vec::new();
+        let mut overrunning = Vec::new();
+        for source in ALL_SOURCES {
+            let enabled = source_enabled(settings, &source);
+            let entry = entries.get(&source);
+            if let (true, Some(entry)) = (enabled, entry) {
+                alerts.extend(entry.alerts.iter().cloned());
+                rescheduled.extend(entry.rescheduled.iter().cloned());
+                cancelled.extend(entry.cancelled.iter().cloned());
+                overrunning.extend(entry.overrunning.iter().cloned());
+            }
+            sources.push(SourceStatus {
+                source,
+                enabled,
+                alert_count: entry.map_or(0, |e| e.alerts.len()),
+                last_fetch_at: entry
+                    .and_then(|e| e.last_fetch_at)
+                    .map(|at| at.to_rfc3339()),
+                last_success_at: entry
+                    .and_then(|e| e.last_success_at)
+                    .map(|at| at.to_rfc3339()),
+                last_error: entry.and_then(|e| e.last_error.clone()),
+                latency_ms: entry.and_then(|e| e.latency_ms),
+                next_fetch_at: entry
+                    .and_then(|e| e.last_fetch_at)
+                    .filter(|_| enabled)
+                    .map(|at| (at + poll_interval(settings, &source)).to_rfc3339()),
+                fetch_count: entry.map_or(0, |e| e.fetch_count),
+                total_latency_ms: entry.map_or(0, |e| e.total_latency_ms),
+                error_counts: entry.map(|e| e.error_counts.clone()).unwrap_or_default(),
+            });
+        }
+        AlertSnapshot {
+            alerts,
+            sources,
+            rescheduled,
+            cancelled,
+            overrunning,
+        }
+    }
+}
+
+/// Fetch `sources` concurrently and store the outcomes in that order, collecting what
+/// changed into `changes`.
+pub async fn fetch_and_record(
+    store: &AlertStore,
+    sources: &[AlertSource],
+    settings: Option<&Settings>,
+    changes: &mut Vec<AlertChange>,
+) -> Vec<(AlertSource, Fetched)> {
+    let mut fetches = tokio::task::JoinSet::new();
+    for (n, &source) in sources.iter().enumerate() {
+        let settings = settings.cloned();
+        fetches.spawn(async move {
+            let fetched_at = Utc::now();
+            let started = std::time::Instant::now();
+            let fetched = providers::fetch_source(&source, settings.as_ref()).await;
+            (n, source, fetched, fetched_at, started.elapsed())
+        });
+    }
+    let mut fetched = Vec::new();
+    while let Some(joined) = fetches.join_next().await {
+        match joined {
+            Ok(outcome) => fetched.push(outcome),
+            Err(e) => log::warn!("Fetch task failed: {}", e),
+        }
+    }
+    fetched.sort_by_key(|(n, ..)| *n);
+
+    let mut results = Vec::new();
+    for (_, source, fetched, fetched_at, latency) in fetched {
+        changes.extend(store.record(
+            source,
+            &fetched,
+            fetched_at,
+            latency,
+            fetch_key(settings, &source),
+        ));
+        if fetched.answered() {
+            changes.extend(store.check_overruns(source, overrun_after(settings)));
+        }
+        results.push((source, fetched));
+    }
+    results
+}
+
+/// Announce the current alerts, and the changes just found if there are any, also
+/// as desktop notifications and to the reminders.
+pub fn emit_snapshot(
+    app: &AppHandle,
+    store: &AlertStore,
+    settings: Option<&Settings>,
+    changes: &[AlertChange],
+) {
+    if let Err(e) = app.emit(ALERTS_UPDATED_EVENT, store.snapshot(settings)) {
+        log::warn!("Could not emit {}: {}", ALERTS_UPDATED_EVENT, e);
+    }
+    if !changes.is_empty() {
+        if let Err(e) = app.emit(ALERTS_CHANGED_EVENT, changes) {
+            log::warn!("Could not emit {}: {}", ALERTS_CHANGED_EVENT, e);
+        }
+        for change in changes {
+            let event = match change.kind {
+                ChangeKind::TimeChanged => ALERT_RESCHEDULED_EVENT,
+                ChangeKind::Cancelled => ALERT_CANCELLED_EVENT,
+                ChangeKind::Overrunning => ALERT_OVERRUNNING_EVENT,
+                _ => continue,
+            };
+            if let Err(e) = app.emit(event, change) {
+                log::warn!("Could not emit {}: {}", event, e);
+            }
+        }
+        notifier::notify_changes(app, settings, changes);
+        reminders::record_changes(app, settings, changes);
+        local_api::broadcast_changes(app, settings, changes);
+    }
+}
+
+async fn poll_due(app: &AppHandle) {
+    let settings = match app.state::<SettingsCache>().get() {
+        Ok(settings) => settings,
+        Err(e) => {
+            log::warn!("Polling without settings: {}", e);
+            None
+        }
+    };
+    let store = app.state::<AlertStore>();
+    let due = store.due_sources(settings.as_ref(), Utc::now());
+    if due.is_empty() {
+        return;
+    }
+    let mut changes = Vec::new();
+    for (source, fetched) in fetch_and_record(&store, &due, settings.as_ref(), &mut changes).await {
+        if let Some(e) = fetched.error() {
+            log::warn!("{} poll failed: {}", providers::source_label(&source), e);
+        }
+    }
+    emit_snapshot(app, &store, settings.as_ref(), &changes);
+}
+
+/// Start polling, reminders, the daily digest, the MQTT publisher and the local API.
+/// Needs `SettingsCache`, `AlertStore`, `Notifier`, `Reminders`, `Digests`, `Mqtt` and
+/// `LocalApi` to be managed.
+pub fn start(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            poll_due(&app).await;
+            reminders::check(&app);
+            digest::check(&app);
+            mqtt::update(&app);
+            local_api::update(&app);
+            notifier::release_held(&app);
+            tokio::time::sleep(TICK).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_logic::{parse_alert_time, SavedLocation};
+    use crate::providers::{FetchError, FetchErrorKind};
+    use crate::test_support::{alert, settings, temp_dir};
+
+    fn water() -> UnifiedAlert {
+        alert(
+            "water-1",
+            AlertSource::Water,
+            "2026-03-12T08:00:00",
+            "2026-03-12T16:00:00",
+            "Kuźnicza",
+        )
+    }
+
+    #[test]
+    fn test_poll_interval() {
+        let mut s = settings();
+        assert_eq!(
+            poll_interval(Some(&s), &AlertSource::Tauron),
+            Duration::minutes(30)
+        );
+        s.pollIntervals = Some(BTreeMap::from([
+            ("water".to_string(), 60),
+            ("tauron".to_string(), 1),
+        ]));
+        assert_eq!(
+            poll_interval(Some(&s), &AlertSource::Water),
+            Duration::minutes(60)
+        );
+        assert_eq!(
+            poll_interval(Some(&s), &AlertSource::Tauron),
+            Duration::minutes(5)
+        );
+        assert_eq!(
+            poll_interval(None, &AlertSource::Fortum),
+            Duration::minutes(15)
+        );
+    }
+
+    #[test]
+    fn test_source_enabled() {
+        let mut s = settings();
+        assert!(ALL_SOURCES.iter().all(|src| source_enabled(Some(&s), src)));
+        assert!(!source_enabled(None, &AlertSource::Tauron));
+        assert!(source_enabled(None, &AlertSource::Water));
+        s.enabledSources = Some(vec!["tauron".to_string()]);
+        assert!(source_enabled(Some(&s), &AlertSource::Tauron));
+        assert!(!source_enabled(Some(&s), &AlertSource::Fortum));
+
+        // A saved location with GAIDs is enough for Tauron
+        let only_saved = Settings {
+            locations: vec![SavedLocation {
+                label: "Działka".to_string(),
+                cityGAID: 2,
+                streetGAID: 20,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        assert!(source_enabled(Some(&only_saved), &AlertSource::Tauron));
+        assert!(!source_enabled(
+            Some(&Settings::default()),
+            &AlertSource::Tauron
+        ));
+    }
+
+    #[test]
+    fn test_due_sources() {
+        let store = AlertStore::default();
+        let s = settings();
+        let now = Utc::now();
+        assert_eq!(store.due_sources(Some(&s), now).len(), 3);
+
+        for source in ALL_SOURCES {
+            let key = fetch_key(Some(&s), &source);
+            store.record(
+                source,
+                &Fetched::single(Ok(Vec::new())),
+                now,
+                std::time::Duration::ZERO,
+                key,
+            );
+        }
+        assert!(store
+            .due_sources(Some(&s), now + Duration::minutes(1))
+            .is_empty());
+        assert_eq!(
+            store.due_sources(Some(&s), now + Duration::minutes(20)),
+            vec![AlertSource::Water, AlertSource::Fortum]
+        );
+
+        // A new address makes Tauron due right away
+        let moved = Settings {
+            houseNo: "27".to_string(),
+            ..settings()
+        };
+        assert_eq!(
+            store.due_sources(Some(&moved), now),
+            vec![AlertSource::Tauron]
+        );
+
+        // So does a new saved location, which Tauron is asked about as well
+        let mut more = settings();
+        more.locations.push(SavedLocation {
+            label: "Biuro".to_string(),
+            cityGAID: 2,
+            streetGAID: 20,
+            houseNo: "5".to_string(),
+            ..Default::default()
+        });
+        assert_eq!(
+            fetch_key(Some(&more), &AlertSource::Tauron),
+            "1/10/25;2/20/5"
+        );
+        assert_eq!(
+            store.due_sources(Some(&more), now),
+            vec![AlertSource::Tauron]
+        );
+    }
+
+    #[test]
+    fn test_record_diffs_and_persists() {
+        let dir = temp_dir("alert_state");
+        let path = dir.join(ALERT_STATE_FILE);
+        let now = Utc::now();
+        let zero = std::time::Duration::ZERO;
+
+        let store = AlertStore::open(path.clone());
+        let first = Fetched::single(Ok(vec![water()]));
+        let changes = store.record(AlertSource::Water, &first, now, zero, String::new());
+        assert_eq!(changes.len(), 1);
+        assert_eq!(store.unseen().len(), 1);
+
+        // After a restart the same alerts are not new again
+        let store = AlertStore::open(path.clone());
+        assert_eq!(store.unseen().len(), 1);
+        assert!(store
+            .record(AlertSource::Water, &first, now, zero, String::new())
+            .is_empty());
+        store.mark_seen().unwrap();
+        assert!(AlertStore::open(path).unseen().is_empty());
+
+        // A different address starts from scratch instead of reporting removals
+        let at = |key: &str, id: &str| {
+            Fetched(vec![(
+                key.to_string(),
+                Ok(vec![alert(
+                    id,
+                    AlertSource::Tauron,
+                    "2026-03-12T08:00:00",
+                    "2026-03-12T16:00:00",
+                    "Kuźnicza",
+                )]),
+            )])
+        };
+        store.record(
+            AlertSource::Tauron,
+            &at("1/10/25", "tauron-1"),
+            now,
+            zero,
+            "1/10/25".to_string(),
+        );
+        let changes = store.record(
+            AlertSource::Tauron,
+            &at("1/10/27", "tauron-2"),
+            now,
+            zero,
+            "1/10/27".to_string(),
+        );
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].kind, alert_diff::ChangeKind::Added);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_tauron_addresses_are_kept_apart() {
+        let tauron = |id: &str, start: &str| UnifiedAlert {
+            endDate: None,
+            ..alert(id, AlertSource::Tauron, start, start, id)
+        };
+        let home = tauron("tauron-home", "2026-03-12T08:00:00");
+        let plot = tauron("tauron-plot", "2026-03-13T08:00:00");
+        let office = tauron("tauron-office", "2026-03-14T08:00:00");
+        let fetched = |parts: Vec<(&str, Result<Vec<UnifiedAlert>, FetchError>)>| {
+            Fetched(
+                parts
+                    .into_iter()
+                    .map(|(key, result)| (key.to_string(), result))
+                    .collect(),
+            )
+        };
+        let store = AlertStore::default();
+        let s = settings();
+        let now = DateTime::parse_from_rfc3339("2026-03-01T12:00:00Z")
+            .unwrap()
+            .to_utc();
+        let zero = std::time::Duration::ZERO;
+        let ids = |store: &AlertStore| -> Vec<String> {
+            store
+                .snapshot(Some(&s))
+                .alerts
+                .into_iter()
+                .map(|a| a.id)
+                .collect()
+        };
+
+        let both = fetched(vec![
+            ("1/10/25", Ok(vec![home.clone()])),
+            ("2/20/5", Ok(vec![plot.clone()])),
+        ]);
+        assert_eq!(
+            store
+                .record(AlertSource::Tauron, &both, now, zero, String::new())
+                .len(),
+            2
+        );
+
+        // One address failing keeps what it listed and names it in the error
+        let timeout = FetchError::new(FetchErrorKind::Timeout, "Działka: operation timed out");
+        let partial = fetched(vec![
+            ("1/10/25", Ok(vec![home.clone()])),
+            ("2/20/5", Err(timeout)),
+        ]);
+        assert!(store
+            .record(AlertSource::Tauron, &partial, now, zero, String::new())
+            .is_empty());
+        assert_eq!(ids(&store), ["tauron-home", "tauron-plot"]);
+        let status = store.snapshot(Some(&s)).sources.remove(0);
+        assert_eq!(
+            status.last_error.as_deref(),
+            Some("Działka: operation timed out")
+        );
+        assert_eq!(
+            status.error_counts,
+            BTreeMap::from([("timeout".to_string(), 1)])
+        );
+        assert!(status.last_success_at.is_some());
+
+        // A new address only adds its own alerts
+        let three = fetched(vec![
+            ("1/10/25", Ok(vec![home.clone()])),
+            ("2/20/5", Ok(vec![plot.clone()])),
+            ("3/30/1", Ok(vec![office.clone()])),
+        ]);
+        let changes = store.record(AlertSource::Tauron, &three, now, zero, String::new());
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            (changes[0].kind, changes[0].alert.id.as_str()),
+            (ChangeKind::Added, "tauron-office")
+        );
+        assert!(store.snapshot(Some(&s)).sources[0].last_error.is_none());
+
+        // A removed one takes its alerts along without reporting them
+        let two = fetched(vec![
+            ("1/10/25", Ok(vec![home.clone()])),
+            ("3/30/1", Ok(vec![office.clone()])),
+        ]);
+        assert!(store
+            .record(AlertSource::Tauron, &two, now, zero, String::new())
+            .is_empty());
+        assert_eq!(ids(&store), ["tauron-home", "tauron-office"]);
+    }
+
+    #[test]
+    fn test_failed_fetch_keeps_previous_alerts() {
+        let store = AlertStore::default();
+        let s = settings();
+        let now = Utc::now();
+        let ok = Fetched::single(Ok(vec![water()]));
+        store.record(
+            AlertSource::Water,
+            &ok,
+            now,
+            std::time::Duration::from_millis(120),
+            String::new(),
+        );
+        let failed = Fetched::single(Err(FetchError::new(
+            FetchErrorKind::Timeout,
+            "operation timed out",
+        )));
+        store.record(
+            AlertSource::Water,
+            &failed,
+            now,
+            std::time::Duration::ZERO,
+            String::new(),
+        );
+
+        let snapshot = store.snapshot(Some(&s));
+        assert_eq!(snapshot.alerts, vec![water()]);
+        let water = snapshot
+            .sources
+            .iter()
+            .find(|st| st.source == AlertSource::Water)
+            .unwrap();
+        assert_eq!(water.last_error.as_deref(), Some("operation timed out"));
+        assert_eq!(water.alert_count, 1);
+        assert!(water.next_fetch_at.is_some());
+        assert_eq!(water.fetch_count, 2);
+        assert_eq!(water.total_latency_ms, 120);
+        assert_eq!(
+            water.error_counts,
+            BTreeMap::from([("timeout".to_string(), 1)])
+        );
+
+        // Disabled sources don't contribute alerts
+        let only_tauron = Settings {
+            enabledSources: Some(vec!["tauron".to_string()]),
+            ..settings()
+        };
+        assert!(store.snapshot(Some(&only_tauron)).alerts.is_empty());
+    }
+
+    #[test]
+    fn test_reschedules_and_cancellations_are_kept() {
+        let fortum = |id: &str, start: &str| UnifiedAlert {
+            endDate: None,
+            ..alert(id, AlertSource::Fortum, start, start, "Kuźnicza")
+        };
+        let store = AlertStore::default();
+        let s = settings();
+        // Well before the alerts start
+        let now = DateTime::parse_from_rfc3339("2026-03-01T12:00:00Z")
+            .unwrap()
+            .to_utc();
+        let zero = std::time::Duration::ZERO;
+        let first = Fetched::single(Ok(vec![
+            fortum("fortum-1", "2026-03-12T08:00:00"),
+            fortum("fortum-2", "2026-03-13T08:00:00"),
+        ]));
+        store.record(AlertSource::Fortum, &first, now, zero, String::new());
+
+        let moved = Fetched::single(Ok(vec![
+            fortum("fortum-1", "2026-03-12T10:00:00"),
+            fortum("fortum-2", "2026-03-13T08:00:00"),
+        ]));
+        store.record(AlertSource::Fortum, &moved, now, zero, String::new());
+        let moved_again = Fetched::single(Ok(vec![fortum("fortum-1", "2026-03-12T12:00:00")]));
+        let changes = store.record(AlertSource::Fortum, &moved_again, now, zero, String::new());
+        let kinds: Vec<ChangeKind> = changes.iter().map(|c| c.kind).collect();
+        assert_eq!(kinds, vec![ChangeKind::TimeChanged, ChangeKind::Cancelled]);
+
+        let snapshot = store.snapshot(Some(&s));
+        assert_eq!(snapshot.rescheduled.len(), 1);
+        // The window before the first move is the one to show
+        assert_eq!(
+            snapshot.rescheduled[0].previous_start_date.as_deref(),
+            Some("2026-03-12T08:00:00")
+        );
+        assert_eq!(snapshot.cancelled.len(), 1);
+        assert_eq!(snapshot.cancelled[0].id, "fortum-2");
+
+        // Back on the list: no longer cancelled
+        let back = Fetched::single(Ok(vec![
+            fortum("fortum-1", "2026-03-12T12:00:00"),
+            fortum("fortum-2", "2026-03-13T08:00:00"),
+        ]));
+        store.record(AlertSource::Fortum, &back, now, zero, String::new());
+        assert!(store.snapshot(Some(&s)).cancelled.is_empty());
+    }
+
+    #[test]
+    fn test_overruns_are_flagged_once() {
+        let water = |end: &str| {
+            alert(
+                "water-1",
+                AlertSource::Water,
+                "2026-03-12T08:00:00",
+                end,
+                "Kuźnicza",
+            )
+        };
+        let at = |time: &str| {
+            let local = parse_alert_time(time)
+                .unwrap()
+                .and_local_timezone(Local)
+                .unwrap();
+            local.with_timezone(&Utc)
+        };
+        let store = AlertStore::default();
+        let s = settings();
+        let zero = std::time::Duration::ZERO;
+        let listed = Fetched::single(Ok(vec![water("2026-03-12T16:00:00")]));
+        let record = |time: &str, fetched: &Fetched| {
+            store.record(AlertSource::Water, fetched, at(time), zero, String::new());
+            store.check_overruns(AlertSource::Water, overrun_after(Some(&s)))
+        };
+
+        assert!(record("2026-03-12T15:00:00", &listed).is_empty());
+        let late = record("2026-03-12T16:15:00", &listed);
+        assert_eq!(late.len(), 1);
+        assert_eq!(late[0].kind, ChangeKind::Overrunning);
+        assert_eq!(
+            store.snapshot(Some(&s)).overrunning,
+            vec!["water-1".to_string()]
+        );
+        assert!(record("2026-03-12T16:30:00", &listed).is_empty());
+
+        // A failed fetch doesn't prove the alert is still listed
+        assert!(record(
+            "2026-03-12T17:00:00",
+            &Fetched::single(Err(FetchError::new(FetchErrorKind::Timeout, "timeout")))
+        )
+        .is_empty());
+
+        // Extended, then late again
+        let extended = Fetched::single(Ok(vec![water("2026-03-12T18:00:00")]));
+        assert!(record("2026-03-12T17:30:00", &extended).is_empty());
+        assert!(store.snapshot(Some(&s)).overrunning.is_empty());
+        assert_eq!(record("2026-03-12T18:30:00", &extended).len(), 1);
+    }
+}
@@ -0,0 +1,631 @@
+use crate::api_logic::Settings;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Layout version written to `schemaVersion`. Bump it together with a new entry in
+/// `MIGRATIONS` whenever the stored layout changes.
+pub const CURRENT_SCHEMA_VERSION: u32 = 2;
+
+/// Rolling backups live in this directory next to `settings.json`.
+const BACKUP_DIR: &str = "settings-backups";
+
+/// Number of backups kept; older ones are deleted on save.
+const MAX_BACKUPS: usize = 10;
+
+/// Written when a corrupt settings file was replaced, until the user dismisses it.
+const RECOVERY_FILE: &str = "settings-recovery.json";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    /// File name inside the backup directory, used to restore it.
+    pub id: String,
+    pub created_at: String,
+    pub city_name: String,
+    pub street_name: String,
+    pub house_no: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryReport {
+    pub recovered_at: String,
+    /// Where the corrupt file was moved to.
+    pub quarantined_file: String,
+    /// The backup that was restored; `None` when no usable backup existed.
+    pub restored_backup: Option<String>,
+    pub error: String,
+}
+
+/// Why a settings file could not be read.
+enum ReadError {
+    /// Not valid settings JSON; recoverable from a backup.
+    Corrupt(String),
+    /// Readable, but unusable here (e.g. written by a newer app version).
+    Rejected(String),
+}
+
+type Migration = fn(&mut Value) -> Result<(), String>;
+
+/// `MIGRATIONS[n]` upgrades a settings object from schema `n` to `n + 1`.
+const MIGRATIONS: &[Migration] = &[migrate_v0_to_v1, migrate_v1_to_v2];
+
+/// v0: files written before versioning. Same fields, only the version is missing.
+fn migrate_v0_to_v1(_settings: &mut Value) -> Result<(), String> {
+    Ok(())
+}
+
+/// v2 adds `locations`, saved addresses besides the primary one.
+fn migrate_v1_to_v2(settings: &mut Value) -> Result<(), String> {
+    let obj = settings.as_object_mut().ok_or("not a JSON object")?;
+    obj.entry("locations")
+        .or_insert_with(|| Value::Array(Vec::new()));
+    Ok(())
+}
+
+/// A version too large for `u32` still counts as newer than this app.
+fn schema_version(value: &Value) -> u32 {
+    value
+        .get("schemaVersion")
+        .and_then(Value::as_u64)
+        .map(|v| u32::try_from(v).unwrap_or(u32::MAX))
+        .unwrap_or(0)
+}
+
+fn newer_schema_error(version: u32) -> String {
+    format!(
+        "Settings were saved by a newer version of the app (schema {}, this version supports {}). Please update the app.",
+        version, CURRENT_SCHEMA_VERSION
+    )
+}
+
+/// Run the migration chain on a raw settings object, returning whether anything changed.
+pub fn migrate(value: &mut Value) -> Result<bool, String> {
+    let version = schema_version(value);
+    if version > CURRENT_SCHEMA_VERSION {
+        return Err(newer_schema_error(version));
+    }
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(value)
+            .map_err(|e| format!("Settings migration from schema {} failed: {}", from, e))?;
+    }
+    if let Some(obj) = value.as_object_mut() {
+        obj.insert("schemaVersion".to_string(), CURRENT_SCHEMA_VERSION.into());
+    }
+    Ok(version != CURRENT_SCHEMA_VERSION)
+}
+
+/// Write `data` next to `path`, fsync it and rename it over `path`, so readers
+/// (e.g. the Android widgets) never see a half-written file.
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<(), String> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("Invalid settings path: {}", path.display()))?;
+    let tmp_path = path.with_file_name(format!("{}.tmp", file_name.to_string_lossy()));
+
+    let mut file = File::create(&tmp_path).map_err(|e| e.to_string())?;
+    file.write_all(data).map_err(|e| e.to_string())?;
+    file.sync_all().map_err(|e| e.to_string())?;
+    drop(file);
+
+    fs::rename(&tmp_path, path).map_err(|e| {
+        fs::remove_file(&tmp_path).ok();
+        e.to_string()
+    })?;
+
+    // Persist the rename itself; directories can't be opened for syncing on Windows.
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        File::open(dir).and_then(|d| d.sync_all()).ok();
+    }
+    Ok(())
+}
+
+/// State files the app can do without: a missing or unreadable file reads as empty.
+pub(crate) fn read_json<T: serde::de::DeserializeOwned + Default>(path: &Path) -> T {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+/// Write a state file with `write_atomic`, logging failures; `what` names it in the log.
+pub(crate) fn store_json<T: Serialize>(path: Option<&Path>, value: &T, what: &str) {
+    let Some(path) = path else {
+        return;
+    };
+    let stored = serde_json::to_string(value)
+        .map_err(|e| e.to_string())
+        .and_then(|json| write_atomic(path, json.as_bytes()));
+    if let Err(e) = stored {
+        log::warn!("Could not store {}: {}", what, e);
+    }
+}
+
+fn backup_dir(path: &Path) -> PathBuf {
+    path.with_file_name(BACKUP_DIR)
+}
+
+fn timestamp() -> String {
+    Utc::now().format("%Y%m%dT%H%M%S%3fZ").to_string()
+}
+
+/// Parse and migrate settings JSON, returning whether a migration ran.
+fn parse_settings(data: &str) -> Result<(Settings, bool), ReadError> {
+    let mut value: Value = serde_json::from_str(data).map_err(|e| {
+        ReadError::Corrupt(format!(
+            "Settings parse error (might be empty/corrupt): {}",
+            e
+        ))
+    })?;
+    if !value.is_object() {
+        return Err(ReadError::Corrupt(
+            "Settings parse error: not a JSON object".to_string(),
+        ));
+    }
+    let migrated = migrate(&mut value).map_err(ReadError::Rejected)?;
+    let settings = serde_json::from_value(value).map_err(|e| {
+        ReadError::Corrupt(format!(
+            "Settings parse error (might be empty/corrupt): {}",
+            e
+        ))
+    })?;
+    Ok((settings, migrated))
+}
+
+/// Keep a copy of freshly saved settings, skipping it when nothing changed since
+/// the newest backup, and drop the oldest copies beyond `MAX_BACKUPS`.
+fn take_backup(path: &Path, json: &str) -> Result<(), String> {
+    let dir = backup_dir(path);
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+    let names = backup_names(path);
+    if let Some(newest) = names.last() {
+        if fs::read_to_string(dir.join(newest)).ok().as_deref() == Some(json) {
+            return Ok(());
+        }
+    }
+    write_atomic(
+        &dir.join(format!("settings-{}.json", timestamp())),
+        json.as_bytes(),
+    )?;
+
+    let names = backup_names(path);
+    for old in names.iter().take(names.len().saturating_sub(MAX_BACKUPS)) {
+        fs::remove_file(dir.join(old)).ok();
+    }
+    Ok(())
+}
+
+/// Backup file names, oldest first (names sort by their timestamp).
+fn backup_names(path: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(backup_dir(path))
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .filter(|n| n.starts_with("settings-") && n.ends_with(".json"))
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+/// Saved backups, newest first.
+pub fn list_backups(path: &Path) -> Vec<BackupInfo> {
+    let dir = backup_dir(path);
+    backup_names(path)
+        .into_iter()
+        .rev()
+        .filter_map(|name| {
+            let data = fs::read_to_string(dir.join(&name)).ok()?;
+            let (settings, _) = parse_settings(&data).ok()?;
+            let created_at = name
+                .trim_start_matches("settings-")
+                .trim_end_matches(".json")
+                .to_string();
+            Some(BackupInfo {
+                id: name,
+                created_at,
+                city_name: settings.cityName,
+                street_name: settings.streetName,
+                house_no: settings.houseNo,
+            })
+        })
+        .collect()
+}
+
+/// Make the backup `id` the current settings.
+pub fn restore_backup(path: &Path, id: &str) -> Result<Settings, String> {
+    if !backup_names(path).iter().any(|name| name == id) {
+        return Err(format!("Unknown settings backup: {}", id));
+    }
+    let data = fs::read_to_string(backup_dir(path).join(id)).map_err(|e| e.to_string())?;
+    let (settings, _) = parse_settings(&data).map_err(|e| match e {
+        ReadError::Corrupt(e) | ReadError::Rejected(e) => e,
+    })?;
+    save_settings_to_path(path, &settings)?;
+    Ok(settings)
+}
+
+/// Move the corrupt file aside and restore the newest backup that still parses.
+fn recover(path: &Path, error: String) -> Result<Option<Settings>, String> {
+    let quarantined = path.with_file_name(format!("settings.corrupt-{}.json", timestamp()));
+    fs::rename(path, &quarantined).map_err(|e| e.to_string())?;
+    log::warn!(
+        "Corrupt settings moved to {}: {}",
+        quarantined.display(),
+        error
+    );
+
+    let dir = backup_dir(path);
+    let restored = backup_names(path).into_iter().rev().find_map(|name| {
+        let data = fs::read_to_string(dir.join(&name)).ok()?;
+        let (settings, _) = parse_settings(&data).ok()?;
+        Some((name, settings))
+    });
+
+    let report = RecoveryReport {
+        recovered_at: Utc::now().to_rfc3339(),
+        quarantined_file: quarantined.to_string_lossy().to_string(),
+        restored_backup: restored.as_ref().map(|(name, _)| name.clone()),
+        error,
+    };
+    let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+    write_atomic(&path.with_file_name(RECOVERY_FILE), json.as_bytes())?;
+
+    match restored {
+        Some((_, settings)) => {
+            save_settings_to_path(path, &settings)?;
+            Ok(Some(settings))
+        }
+        None => Ok(None),
+    }
+}
+
+/// The last automatic recovery, if the user hasn't dismissed it yet.
+pub fn recovery_report(path: &Path) -> Option<RecoveryReport> {
+    let data = fs::read_to_string(path.with_file_name(RECOVERY_FILE)).ok()?;
+    serde_json::from_str(&data).ok()
+}
+
+pub fn dismiss_recovery_report(path: &Path) -> Result<(), String> {
+    match fs::remove_file(path.with_file_name(RECOVERY_FILE)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+        _ => Ok(()),
+    }
+}
+
+pub fn save_settings_to_path(path: &Path, settings: &Settings) -> Result<(), String> {
+    // Never overwrite settings from a newer app version with an older layout
+    if let Ok(existing) = fs::read_to_string(path) {
+        if let Ok(value) = serde_json::from_str::<Value>(&existing) {
+            let version = schema_version(&value);
+            if version > CURRENT_SCHEMA_VERSION {
+                return Err(newer_schema_error(version));
+            }
+        }
+    }
+
+    let mut settings = settings.clone();
+    settings.schemaVersion = CURRENT_SCHEMA_VERSION;
+    let json = serde_json::to_string_pretty(&settings).map_err(|e| e.to_string())?;
+    write_atomic(path, json.as_bytes())?;
+
+    if let Err(e) = take_backup(path, &json) {
+        log::warn!("Could not back up settings: {}", e);
+    }
+    Ok(())
+}
+
+pub fn load_settings_from_path(path: &Path) -> Result<Option<Settings>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let data = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    if data.trim().is_empty() {
+        return Ok(None);
+    }
+
+    let (settings, migrated) = match parse_settings(&data) {
+        Ok(parsed) => parsed,
+        Err(ReadError::Rejected(e)) => return Err(e),
+        Err(ReadError::Corrupt(e)) => return recover(path, e),
+    };
+
+    if migrated {
+        if let Err(e) = save_settings_to_path(path, &settings) {
+            log::warn!("Could not store migrated settings: {}", e);
+        }
+    }
+    Ok(Some(settings))
+}
+
+/// Settings held in memory so commands don't re-read `settings.json` on every call.
+/// `current` is `None` until first loaded; the inner `Option` mirrors
+/// `load_settings_from_path` (no file yet).
+pub struct SettingsCache {
+    path: PathBuf,
+    current: Mutex<Option<Option<Settings>>>,
+}
+
+impl SettingsCache {
+    pub fn new(path: PathBuf) -> Self {
+        SettingsCache {
+            path,
+            current: Mutex::new(None),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get(&self) -> Result<Option<Settings>, String> {
+        let mut current = self.current.lock().map_err(|e| e.to_string())?;
+        if let Some(settings) = current.as_ref() {
+            return Ok(settings.clone());
+        }
+        let settings = load_settings_from_path(&self.path)?;
+        *current = Some(settings.clone());
+        Ok(settings)
+    }
+
+    pub fn save(&self, settings: &Settings) -> Result<(), String> {
+        let mut current = self.current.lock().map_err(|e| e.to_string())?;
+        save_settings_to_path(&self.path, settings)?;
+        let mut stored = settings.clone();
+        stored.schemaVersion = CURRENT_SCHEMA_VERSION;
+        *current = Some(Some(stored));
+        Ok(())
+    }
+
+    pub fn invalidate(&self) {
+        if let Ok(mut current) = self.current.lock() {
+            *current = None;
+        }
+    }
+
+    /// Re-read the file after an outside change. Returns the new settings when they
+    /// differ from what was cached, so our own saves don't count as changes.
+    pub fn reload(&self) -> Result<Option<Option<Settings>>, String> {
+        let mut current = self.current.lock().map_err(|e| e.to_string())?;
+        let settings = load_settings_from_path(&self.path)?;
+        if current.as_ref() == Some(&settings) {
+            return Ok(None);
+        }
+        *current = Some(settings.clone());
+        Ok(Some(settings))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    #[test]
+    fn test_settings_persistence() {
+        let dir = temp_dir("persistence");
+        let test_path = dir.join("settings.json");
+
+        let settings = Settings {
+            cityName: "TestCity".to_string(),
+            streetName: "TestStreet".to_string(),
+            houseNo: "10".to_string(),
+            cityGAID: 111,
+            streetGAID: 222,
+            theme: Some("light".to_string()),
+            language: Some("en".to_string()),
+            schemaVersion: CURRENT_SCHEMA_VERSION,
+            ..Default::default()
+        };
+
+        // Save
+        save_settings_to_path(&test_path, &settings).expect("Failed to save settings");
+
+        // Load
+        let loaded = load_settings_from_path(&test_path).expect("Failed to load settings");
+        assert_eq!(Some(settings), loaded);
+
+        // No temp file left behind
+        assert!(!dir.join("settings.json.tmp").exists());
+
+        // Cleanup
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_load_non_existent_settings() {
+        let test_path = std::path::Path::new("non_existent_settings.json");
+        let loaded = load_settings_from_path(test_path).expect("Failed to load settings");
+        assert_eq!(None, loaded);
+    }
+
+    #[test]
+    fn test_load_corrupt_settings_without_backup() {
+        let dir = temp_dir("corrupt_no_backup");
+        let test_path = dir.join("settings.json");
+        std::fs::write(&test_path, "{ invalid json }").unwrap();
+
+        // Quarantined instead of leaving the user stuck
+        let result = load_settings_from_path(&test_path).expect("corrupt file is recovered");
+        assert_eq!(result, None);
+        assert!(!test_path.exists());
+
+        let report = recovery_report(&test_path).expect("recovery is reported");
+        assert_eq!(report.restored_backup, None);
+        assert!(std::path::Path::new(&report.quarantined_file).exists());
+
+        dismiss_recovery_report(&test_path).unwrap();
+        assert!(recovery_report(&test_path).is_none());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_load_corrupt_settings_restores_backup() {
+        let dir = temp_dir("corrupt_with_backup");
+        let test_path = dir.join("settings.json");
+        let settings = Settings {
+            cityName: "Backup".to_string(),
+            ..Default::default()
+        };
+        save_settings_to_path(&test_path, &settings).unwrap();
+        std::fs::write(&test_path, "{\"cityName\": ").unwrap();
+
+        let loaded = load_settings_from_path(&test_path).unwrap().unwrap();
+        assert_eq!(loaded.cityName, "Backup");
+        assert!(recovery_report(&test_path)
+            .unwrap()
+            .restored_backup
+            .is_some());
+        // The restored file is good again
+        assert!(load_settings_from_path(&test_path).unwrap().is_some());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_rolling_backups() {
+        let dir = temp_dir("rolling_backups");
+        let test_path = dir.join("settings.json");
+
+        for i in 0..MAX_BACKUPS + 3 {
+            let settings = Settings {
+                houseNo: i.to_string(),
+                ..Default::default()
+            };
+            save_settings_to_path(&test_path, &settings).unwrap();
+            // Saving the same settings again doesn't add a backup
+            save_settings_to_path(&test_path, &settings).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+
+        let backups = list_backups(&test_path);
+        assert_eq!(backups.len(), MAX_BACKUPS);
+        assert_eq!(backups[0].house_no, (MAX_BACKUPS + 2).to_string());
+
+        let oldest = backups.last().unwrap();
+        let restored = restore_backup(&test_path, &oldest.id).unwrap();
+        assert_eq!(restored.houseNo, "3");
+        assert_eq!(
+            load_settings_from_path(&test_path)
+                .unwrap()
+                .unwrap()
+                .houseNo,
+            "3"
+        );
+
+        assert!(restore_backup(&test_path, "../settings.json").is_err());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_load_legacy_settings_missing_fields() {
+        let dir = temp_dir("legacy");
+        let test_path = dir.join("settings.json");
+        // Theme is optional, but let's see if we missing other fields how it behaves
+        let legacy_json = r#"{
+            "cityName": "Legacy",
+            "streetName": "Old St",
+            "houseNo": "1",
+            "cityGAID": 1,
+            "streetGAID": 2
+        }"#;
+        std::fs::write(&test_path, legacy_json).unwrap();
+
+        let loaded =
+            load_settings_from_path(&test_path).expect("Should handle missing optional fields");
+        assert!(loaded.is_some());
+        let s = loaded.unwrap();
+        assert_eq!(s.cityName, "Legacy");
+        assert_eq!(s.theme, None); // Should default to None
+        assert_eq!(s.schemaVersion, CURRENT_SCHEMA_VERSION);
+
+        // The migrated layout is written back
+        let stored: Value =
+            serde_json::from_str(&std::fs::read_to_string(&test_path).unwrap()).unwrap();
+        assert_eq!(schema_version(&stored), CURRENT_SCHEMA_VERSION);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_newer_schema_is_refused() {
+        let dir = temp_dir("future");
+        let test_path = dir.join("settings.json");
+        let future_json = format!(
+            r#"{{
+            "schemaVersion": {},
+            "cityName": "Future",
+            "streetName": "New St",
+            "houseNo": "1",
+            "cityGAID": 1,
+            "streetGAID": 2
+        }}"#,
+            CURRENT_SCHEMA_VERSION + 1
+        );
+        std::fs::write(&test_path, &future_json).unwrap();
+
+        assert!(load_settings_from_path(&test_path).is_err());
+        assert!(save_settings_to_path(&test_path, &Settings::default()).is_err());
+        // The newer file is left untouched
+        assert_eq!(std::fs::read_to_string(&test_path).unwrap(), future_json);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_settings_cache_reload() {
+        let dir = temp_dir("cache");
+        let test_path = dir.join("settings.json");
+        let cache = SettingsCache::new(test_path.clone());
+        assert_eq!(cache.get().unwrap(), None);
+
+        let settings = Settings {
+            cityName: "Cached".to_string(),
+            ..Default::default()
+        };
+        cache.save(&settings).unwrap();
+        // Our own save is not reported as an outside change
+        assert_eq!(cache.reload().unwrap(), None);
+
+        // Another process edits the file
+        let other = Settings {
+            cityName: "Edited".to_string(),
+            ..Default::default()
+        };
+        save_settings_to_path(&test_path, &other).unwrap();
+        assert_eq!(cache.get().unwrap().unwrap().cityName, "Cached");
+        let changed = cache.reload().unwrap().expect("change detected");
+        assert_eq!(changed.unwrap().cityName, "Edited");
+        assert_eq!(cache.get().unwrap().unwrap().cityName, "Edited");
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_migrate_stamps_version() {
+        let mut value = serde_json::json!({ "cityName": "X" });
+        assert!(migrate(&mut value).unwrap());
+        assert_eq!(schema_version(&value), CURRENT_SCHEMA_VERSION);
+        assert_eq!(value["locations"], serde_json::json!([]));
+        assert!(!migrate(&mut value).unwrap());
+    }
+
+    #[test]
+    fn test_oversized_schema_version_is_newer() {
+        let mut value = serde_json::json!({ "schemaVersion": u64::from(u32::MAX) + 1 });
+        assert_eq!(schema_version(&value), u32::MAX);
+        assert!(migrate(&mut value).is_err());
+        assert_eq!(value["schemaVersion"], u64::from(u32::MAX) + 1);
+    }
+}
@@ -0,0 +1,501 @@
+//! Settings sync through a folder the user picks (Syncthing, Nextcloud, a USB stick).
+//!
+//! Every device mirrors its settings into `awaria-sync.json` in that folder. Each field
+//! carries the time it was last changed and by which device, and on conflicts the later
+//! change wins per field, so a street changed on the laptop and a theme changed on the
+//! desktop both survive.
+
+use crate::api_logic::Settings;
+use crate::settings_store::{write_atomic, CURRENT_SCHEMA_VERSION};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Shared file inside the sync folder.
+pub const SYNC_FILE: &str = "awaria-sync.json";
+
+/// Per-device sync bookkeeping, next to `settings.json`.
+const SYNC_STATE_FILE: &str = "settings-sync-state.json";
+
+/// Fields that describe this device rather than the user's setup. Passwords and
+/// tokens inside the other fields stay here too, see `Settings::without_secrets`.
+const LOCAL_ONLY_FIELDS: &[&str] = &["schemaVersion", "syncFolder"];
+
+/// Stamp for local values that predate joining a sync folder: anything already in
+/// the folder wins over them.
+const NEVER: &str = "1970-01-01T00:00:00.000Z";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldStamp {
+    pub value: Value,
+    /// RFC 3339 UTC with milliseconds, so stamps compare as strings.
+    pub modified_at: String,
+    pub device: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncDocument {
+    pub schema_version: u32,
+    pub fields: BTreeMap<String, FieldStamp>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+struct SyncState {
+    device_id: String,
+    /// Fields as of the last sync; a local value differing from these was changed here.
+    fields: BTreeMap<String, FieldStamp>,
+    last_sync_at: Option<String>,
+    last_error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncStatus {
+    pub folder: Option<String>,
+    pub device_id: Option<String>,
+    pub last_sync_at: Option<String>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncOutcome {
+    pub settings: Settings,
+    /// Remote changes were applied; the caller has to save `settings`.
+    pub local_changed: bool,
+    pub remote_changed: bool,
+}
+
+pub fn now_stamp() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn new_device_id() -> String {
+    let nanos = Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64;
+    format!("{:016x}", nanos ^ ((std::process::id() as u64) << 32))
+}
+
+fn state_path(settings_path: &Path) -> std::path::PathBuf {
+    settings_path.with_file_name(SYNC_STATE_FILE)
+}
+
+fn load_state(settings_path: &Path) -> Option<SyncState> {
+    let data = fs::read_to_string(state_path(settings_path)).ok()?;
+    serde_json::from_str(&data).ok()
+}
+
+fn save_state(settings_path: &Path, state: &SyncState) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(state).map_err(|e| e.to_string())?;
+    write_atomic(&state_path(settings_path), json.as_bytes())
+}
+
+fn synced_values(settings: &Settings) -> Result<BTreeMap<String, Value>, String> {
+    match serde_json::to_value(settings.without_secrets()).map_err(|e| e.to_string())? {
+        Value::Object(obj) => Ok(obj
+            .into_iter()
+            .filter(|(field, _)| !LOCAL_ONLY_FIELDS.contains(&field.as_str()))
+            .collect()),
+        _ => Err("Settings did not serialize to an object".to_string()),
+    }
+}
+
+/// Stamp every field whose local value differs from the last synced one.
+fn stamp_local_changes(
+    fields: &mut BTreeMap<String, FieldStamp>,
+    settings: &Settings,
+    device: &str,
+    now: &str,
+) -> Result<(), String> {
+    for (field, value) in synced_values(settings)? {
+        if fields.get(&field).map(|stamp| &stamp.value) != Some(&value) {
+            fields.insert(
+                field,
+                FieldStamp {
+                    value,
+                    modified_at: now.to_string(),
+                    device: device.to_string(),
+                },
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Per field, the later stamp wins; equal stamps are settled by device id so every
+/// device picks the same value.
+pub fn merge_fields(
+    local: &BTreeMap<String, FieldStamp>,
+    remote: &BTreeMap<String, FieldStamp>,
+) -> BTreeMap<String, FieldStamp> {
+    let mut merged = local.clone();
+    for (field, theirs) in remote {
+        let take_theirs = match merged.get(field) {
+            Some(ours) => (&theirs.modified_at, &theirs.device) > (&ours.modified_at, &ours.device),
+            None => true,
+        };
+        if take_theirs {
+            merged.insert(field.clone(), theirs.clone());
+        }
+    }
+    merged
+}
+
+fn apply_fields(
+    settings: &Settings,
+    fields: &BTreeMap<String, FieldStamp>,
+) -> Result<Settings, String> {
+    let mut value = serde_json::to_value(settings).map_err(|e| e.to_string())?;
+    let obj = value
+        .as_object_mut()
+        .ok_or("Settings did not serialize to an object")?;
+    for (field, stamp) in fields {
+        if !LOCAL_ONLY_FIELDS.contains(&field.as_str()) {
+            obj.insert(field.clone(), stamp.value.clone());
+        }
+    }
+    let mut synced: Settings =
+        serde_json::from_value(value).map_err(|e| format!("Synced settings are invalid: {}", e))?;
+    synced.keep_secrets(settings);
+    Ok(synced)
+}
+
+fn read_document(path: &Path) -> Result<Option<SyncDocument>, String> {
+    match fs::read_to_string(path) {
+        Ok(data) => serde_json::from_str(&data)
+            .map(Some)
+            .map_err(|e| format!("{} is unreadable: {}", SYNC_FILE, e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Exchange changes between `settings` and the sync folder. Writes the shared file and
+/// the local bookkeeping; saving the returned settings is left to the caller.
+pub fn sync_once(
+    settings_path: &Path,
+    settings: &Settings,
+    folder: &Path,
+    now: &str,
+) -> Result<SyncOutcome, String> {
+    if !folder.is_dir() {
+        return Err(format!("Sync folder {} does not exist", folder.display()));
+    }
+    let doc_path = folder.join(SYNC_FILE);
+    // An unreadable file is usually one the sync tool is still writing; leave it alone
+    let remote = read_document(&doc_path)?.unwrap_or_default();
+    if remote.schema_version > CURRENT_SCHEMA_VERSION {
+        return Err(format!(
+            "The sync folder was written by a newer version of the app (schema {}). Please update the app.",
+            remote.schema_version
+        ));
+    }
+
+    let mut state = match load_state(settings_path) {
+        Some(state) => state,
+        None => SyncState {
+            device_id: new_device_id(),
+            ..Default::default()
+        },
+    };
+    // Joining a folder another device already uses
+    let joining = state.fields.is_empty() && !remote.fields.is_empty();
+    let stamp = if joining { NEVER } else { now };
+    let device_id = state.device_id.clone();
+    stamp_local_changes(&mut state.fields, settings, &device_id, stamp)?;
+
+    let merged = merge_fields(&state.fields, &remote.fields);
+    let new_settings = apply_fields(settings, &merged)?;
+
+    let remote_changed = merged != remote.fields || remote.schema_version != CURRENT_SCHEMA_VERSION;
+    if remote_changed {
+        let doc = SyncDocument {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            fields: merged.clone(),
+        };
+        let json = serde_json::to_string_pretty(&doc).map_err(|e| e.to_string())?;
+        write_atomic(&doc_path, json.as_bytes())?;
+    }
+
+    state.fields = merged;
+    state.last_sync_at = Some(now.to_string());
+    state.last_error = None;
+    save_state(settings_path, &state)?;
+
+    Ok(SyncOutcome {
+        local_changed: new_settings != *settings,
+        settings: new_settings,
+        remote_changed,
+    })
+}
+
+/// Remember a failed sync for `status`.
+pub fn record_error(settings_path: &Path, error: &str) {
+    let mut state = load_state(settings_path).unwrap_or_else(|| SyncState {
+        device_id: new_device_id(),
+        ..Default::default()
+    });
+    state.last_error = Some(error.to_string());
+    if let Err(e) = save_state(settings_path, &state) {
+        log::warn!("Could not store sync state: {}", e);
+    }
+}
+
+pub fn status(settings_path: &Path, folder: Option<&str>) -> SyncStatus {
+    let state = load_state(settings_path);
+    SyncStatus {
+        folder: folder.map(str::to_string),
+        device_id: state.as_ref().map(|s| s.device_id.clone()),
+        last_sync_at: state.as_ref().and_then(|s| s.last_sync_at.clone()),
+        last_error: state.and_then(|s| s.last_error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::email::EmailSettings;
+    use crate::mqtt::MqttSettings;
+    use crate::test_support::temp_dir;
+
+    fn settings(street: &str) -> Settings {
+        Settings {
+            cityName: "Wrocław".to_string(),
+            streetName: street.to_string(),
+            houseNo: "25".to_string(),
+            cityGAID: 1,
+            streetGAID: 10,
+            ..Default::default()
+        }
+    }
+
+    fn stamp(value: &str, at: &str, device: &str) -> FieldStamp {
+        FieldStamp {
+            value: Value::String(value.to_string()),
+            modified_at: at.to_string(),
+            device: device.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_merge_fields_last_writer_wins() {
+        let local = BTreeMap::from([
+            (
+                "theme".to_string(),
+                stamp("dark", "2026-03-02T10:00:00.000Z", "a"),
+            ),
+            (
+                "houseNo".to_string(),
+                stamp("25", "2026-03-01T10:00:00.000Z", "a"),
+            ),
+        ]);
+        let remote = BTreeMap::from([
+            (
+                "theme".to_string(),
+                stamp("light", "2026-03-01T10:00:00.000Z", "b"),
+            ),
+            (
+                "houseNo".to_string(),
+                stamp("27", "2026-03-03T10:00:00.000Z", "b"),
+            ),
+            (
+                "language".to_string(),
+                stamp("pl", "2026-03-01T10:00:00.000Z", "b"),
+            ),
+        ]);
+        let merged = merge_fields(&local, &remote);
+        assert_eq!(merged["theme"].value, "dark");
+        assert_eq!(merged["houseNo"].value, "27");
+        assert_eq!(merged["language"].value, "pl");
+
+        // Ties go the same way from either side
+        let a = BTreeMap::from([(
+            "theme".to_string(),
+            stamp("dark", "2026-03-01T10:00:00.000Z", "a"),
+        )]);
+        let b = BTreeMap::from([(
+            "theme".to_string(),
+            stamp("light", "2026-03-01T10:00:00.000Z", "b"),
+        )]);
+        assert_eq!(merge_fields(&a, &b), merge_fields(&b, &a));
+    }
+
+    #[test]
+    fn test_two_devices_sync_through_folder() {
+        let folder = temp_dir("sync_folder");
+        let desktop = temp_dir("sync_desktop").join("settings.json");
+        let laptop = temp_dir("sync_laptop").join("settings.json");
+
+        // The desktop publishes its setup
+        let mut desktop_settings = settings("Kuźnicza");
+        desktop_settings.syncFolder = Some(folder.to_string_lossy().to_string());
+        let out = sync_once(
+            &desktop,
+            &desktop_settings,
+            &folder,
+            "2026-03-01T10:00:00.000Z",
+        )
+        .unwrap();
+        assert!(out.remote_changed);
+        assert!(!out.local_changed);
+
+        // A laptop joining with another address takes the folder's values
+        let out = sync_once(
+            &laptop,
+            &settings("Ruska"),
+            &folder,
+            "2026-03-01T11:00:00.000Z",
+        )
+        .unwrap();
+        assert!(out.local_changed);
+        assert_eq!(out.settings.streetName, "Kuźnicza");
+        let mut laptop_settings = out.settings;
+
+        // Both change different fields; both changes survive
+        laptop_settings.theme = Some("dark".to_string());
+        sync_once(
+            &laptop,
+            &laptop_settings,
+            &folder,
+            "2026-03-02T10:00:00.000Z",
+        )
+        .unwrap();
+        desktop_settings.houseNo = "27".to_string();
+        let out = sync_once(
+            &desktop,
+            &desktop_settings,
+            &folder,
+            "2026-03-02T11:00:00.000Z",
+        )
+        .unwrap();
+        assert_eq!(out.settings.theme.as_deref(), Some("dark"));
+        assert_eq!(out.settings.houseNo, "27");
+        // The sync folder itself is device-local
+        assert_eq!(out.settings.syncFolder, desktop_settings.syncFolder);
+
+        let out = sync_once(
+            &laptop,
+            &laptop_settings,
+            &folder,
+            "2026-03-02T12:00:00.000Z",
+        )
+        .unwrap();
+        assert_eq!(out.settings.houseNo, "27");
+        assert_eq!(out.settings.syncFolder, None);
+
+        // Nothing new: nothing is written
+        let out = sync_once(&laptop, &out.settings, &folder, "2026-03-02T13:00:00.000Z").unwrap();
+        assert!(!out.local_changed);
+        assert!(!out.remote_changed);
+
+        for dir in [
+            folder,
+            desktop.parent().unwrap().to_path_buf(),
+            laptop.parent().unwrap().to_path_buf(),
+        ] {
+            std::fs::remove_dir_all(dir).ok();
+        }
+    }
+
+    #[test]
+    fn test_secrets_are_never_synced() {
+        let folder = temp_dir("sync_secrets_folder");
+        let desktop = temp_dir("sync_secrets_desktop").join("settings.json");
+        let laptop = temp_dir("sync_secrets_laptop").join("settings.json");
+
+        let mut desktop_settings = settings("Kuźnicza");
+        desktop_settings.email = Some(EmailSettings {
+            host: "smtp.example.com".to_string(),
+            password: Some("smtp-secret".to_string()),
+            ..Default::default()
+        });
+        desktop_settings.mqtt = Some(MqttSettings {
+            host: "broker.local".to_string(),
+            password: Some("mqtt-secret".to_string()),
+            ..Default::default()
+        });
+        sync_once(
+            &desktop,
+            &desktop_settings,
+            &folder,
+            "2026-03-01T10:00:00.000Z",
+        )
+        .unwrap();
+        let shared = std::fs::read_to_string(folder.join(SYNC_FILE)).unwrap();
+        assert!(shared.contains("smtp.example.com"));
+        assert!(!shared.contains("smtp-secret") && !shared.contains("mqtt-secret"));
+
+        // The laptop gets the channels and keeps its own password
+        let mut laptop_settings = settings("Kuźnicza");
+        laptop_settings.email = Some(EmailSettings {
+            password: Some("laptop-secret".to_string()),
+            ..Default::default()
+        });
+        let out = sync_once(
+            &laptop,
+            &laptop_settings,
+            &folder,
+            "2026-03-01T11:00:00.000Z",
+        )
+        .unwrap();
+        let email = out.settings.email.unwrap();
+        assert_eq!(email.host, "smtp.example.com");
+        assert_eq!(email.password.as_deref(), Some("laptop-secret"));
+        assert_eq!(out.settings.mqtt.unwrap().password, None);
+
+        // Changing only a password is not a change to sync
+        desktop_settings.email.as_mut().unwrap().password = Some("rotated-secret".to_string());
+        let out = sync_once(
+            &desktop,
+            &desktop_settings,
+            &folder,
+            "2026-03-02T10:00:00.000Z",
+        )
+        .unwrap();
+        assert!(!out.remote_changed);
+        assert_eq!(
+            out.settings.email.unwrap().password.as_deref(),
+            Some("rotated-secret")
+        );
+        assert!(!std::fs::read_to_string(folder.join(SYNC_FILE))
+            .unwrap()
+            .contains("rotated-secret"));
+
+        for dir in [
+            folder,
+            desktop.parent().unwrap().to_path_buf(),
+            laptop.parent().unwrap().to_path_buf(),
+        ] {
+            std::fs::remove_dir_all(dir).ok();
+        }
+    }
+
+    #[test]
+    fn test_unreadable_or_newer_sync_file_is_left_alone() {
+        let folder = temp_dir("sync_bad_folder");
+        let local = temp_dir("sync_bad_local").join("settings.json");
+        let doc_path = folder.join(SYNC_FILE);
+
+        std::fs::write(&doc_path, "{ half a fi").unwrap();
+        assert!(sync_once(&local, &settings("Kuźnicza"), &folder, &now_stamp()).is_err());
+        assert_eq!(std::fs::read_to_string(&doc_path).unwrap(), "{ half a fi");
+
+        let future = format!(
+            r#"{{"schemaVersion": {}, "fields": {{}}}}"#,
+            CURRENT_SCHEMA_VERSION + 1
+        );
+        std::fs::write(&doc_path, &future).unwrap();
+        assert!(sync_once(&local, &settings("Kuźnicza"), &folder, &now_stamp()).is_err());
+        assert_eq!(std::fs::read_to_string(&doc_path).unwrap(), future);
+
+        record_error(&local, "boom");
+        assert_eq!(status(&local, None).last_error.as_deref(), Some("boom"));
+
+        std::fs::remove_dir_all(folder).ok();
+        std::fs::remove_dir_all(local.parent().unwrap()).ok();
+    }
+}
@@ -0,0 +1,179 @@
+use crate::settings_store::SettingsCache;
+use crate::settings_sync;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Event sent to the frontend with the new `Settings` (or `null` if the file is gone).
+pub const SETTINGS_CHANGED_EVENT: &str = "settings-changed";
+
+/// Writers often touch the file several times per save; wait for them to settle.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Watch the settings directory and reload the cache when `settings.json` changes
+/// outside this process, e.g. from the Android widget config or a sync tool.
+pub fn start(app: AppHandle) -> Result<(), String> {
+    let path = app.state::<SettingsCache>().path().to_path_buf();
+    let dir = path
+        .parent()
+        .map(PathBuf::from)
+        .ok_or_else(|| "Settings path has no parent directory".to_string())?;
+    let file_name = path.file_name().map(|n| n.to_os_string());
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res {
+            if event
+                .paths
+                .iter()
+                .any(|p| p.file_name() == file_name.as_deref())
+            {
+                tx.send(()).ok();
+            }
+        }
+    })
+    .map_err(|e| e.to_string())?;
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .map_err(|e| e.to_string())?;
+
+    std::thread::spawn(move || {
+        // The watcher stops when dropped, so it lives as long as this thread
+        let _watcher = watcher;
+        while rx.recv().is_ok() {
+            while rx.recv_timeout(DEBOUNCE).is_ok() {}
+
+            let cache = app.state::<SettingsCache>();
+            match cache.reload() {
+                Ok(Some(settings)) => {
+                    log::info!("Settings changed on disk, reloaded");
+                    emit_settings_changed(&app, &settings);
+                    sync_settings(&app);
+                }
+                Ok(None) => {}
+                Err(e) => log::warn!("Could not reload settings: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+fn emit_settings_changed<S: serde::Serialize + Clone>(app: &AppHandle, settings: &S) {
+    if let Err(e) = app.emit(SETTINGS_CHANGED_EVENT, settings) {
+        log::warn!("Could not emit {}: {}", SETTINGS_CHANGED_EVENT, e);
+    }
+}
+
+/// Watches the sync folder chosen in `Settings.syncFolder`, if any.
+pub struct SyncWatcher {
+    /// Wakes the sync thread when the folder's sync file changes.
+    tx: mpsc::Sender<()>,
+    watched: Mutex<Option<(PathBuf, RecommendedWatcher)>>,
+    /// Serializes sync runs from commands and from the watcher thread.
+    running: Mutex<()>,
+}
+
+impl SyncWatcher {
+    /// Point the watcher at `folder`, or stop watching with `None`.
+    fn watch(&self, folder: Option<&Path>) {
+        let Ok(mut watched) = self.watched.lock() else {
+            return;
+        };
+        if watched.as_ref().map(|(path, _)| path.as_path()) == folder {
+            return;
+        }
+        *watched = None;
+        let Some(folder) = folder else {
+            return;
+        };
+
+        let tx = self.tx.clone();
+        let watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            if let Ok(event) = res {
+                let sync_file = Some(std::ffi::OsStr::new(settings_sync::SYNC_FILE));
+                if event.paths.iter().any(|p| p.file_name() == sync_file) {
+                    tx.send(()).ok();
+                }
+            }
+        })
+        .and_then(|mut watcher| {
+            watcher.watch(folder, RecursiveMode::NonRecursive)?;
+            Ok(watcher)
+        });
+        match watcher {
+            Ok(watcher) => *watched = Some((folder.to_path_buf(), watcher)),
+            Err(e) => log::warn!("Could not watch sync folder {}: {}", folder.display(), e),
+        }
+    }
+}
+
+/// Manage a `SyncWatcher` and run a first sync. Needs the `SettingsCache`.
+pub fn start_sync(app: &AppHandle) {
+    let (tx, rx) = mpsc::channel();
+    app.manage(SyncWatcher {
+        tx,
+        watched: Mutex::new(None),
+        running: Mutex::new(()),
+    });
+
+    let handle = app.clone();
+    std::thread::spawn(move || {
+        while rx.recv().is_ok() {
+            while rx.recv_timeout(DEBOUNCE).is_ok() {}
+            sync_settings(&handle);
+        }
+    });
+    sync_settings(app);
+}
+
+/// Exchange settings with the sync folder, if one is configured, and apply what
+/// other devices changed. Failures are logged and kept for `sync_status`.
+pub fn sync_settings(app: &AppHandle) {
+    let Some(sync) = app.try_state::<SyncWatcher>() else {
+        return;
+    };
+    let Ok(_running) = sync.running.lock() else {
+        return;
+    };
+    let cache = app.state::<SettingsCache>();
+    let settings = match cache.get() {
+        Ok(Some(settings)) => settings,
+        Ok(None) => return,
+        Err(e) => {
+            log::warn!("Sync skipped, settings unreadable: {}", e);
+            return;
+        }
+    };
+
+    let folder = settings
+        .syncFolder
+        .as_deref()
+        .filter(|f| !f.trim().is_empty())
+        .map(PathBuf::from);
+    sync.watch(folder.as_deref());
+    let Some(folder) = folder else {
+        return;
+    };
+
+    match settings_sync::sync_once(
+        cache.path(),
+        &settings,
+        &folder,
+        &settings_sync::now_stamp(),
+    ) {
+        Ok(outcome) if outcome.local_changed => {
+            log::info!("Applied settings from sync folder");
+            match cache.save(&outcome.settings) {
+                Ok(()) => emit_settings_changed(app, &Some(outcome.settings)),
+                Err(e) => settings_sync::record_error(cache.path(), &e),
+            }
+        }
+        Ok(_) => {}
+        Err(e) => {
+            log::warn!("Settings sync failed: {}", e);
+            settings_sync::record_error(cache.path(), &e);
+        }
+    }
+}
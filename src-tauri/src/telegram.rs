@@ -0,0 +1,140 @@
+//! Telegram Bot API calls for the chat channel (see `chat`): messages go to one chat
+//! as HTML, are changed with `editMessageText` and answered with a reply.
+
+use crate::email::escape_html;
+use crate::notifier::Outgoing;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+pub const DEFAULT_API_URL: &str = "https://api.telegram.org";
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest message Telegram accepts, in UTF-16 code units.
+pub const MAX_TEXT_LENGTH: usize = 4096;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[allow(non_snake_case)]
+pub struct TelegramSettings {
+    /// Bot API server; `DEFAULT_API_URL` when unset.
+    #[serde(default)]
+    pub apiUrl: Option<String>,
+    pub botToken: String,
+    /// Numeric chat id ("-1001234567890") or "@channelname".
+    pub chatId: String,
+}
+
+fn length(text: &str) -> usize {
+    text.encode_utf16().count()
+}
+
+/// `line` escaped, cut to at most `budget` code units.
+fn escape_within(line: &str, budget: usize) -> String {
+    let mut raw = line.to_string();
+    loop {
+        let escaped = escape_html(&raw);
+        let over = length(&escaped).saturating_sub(budget);
+        if over == 0 {
+            return escaped;
+        }
+        let keep = raw.chars().count().saturating_sub(over);
+        raw = raw.chars().take(keep).collect();
+    }
+}
+
+/// The message as Telegram HTML, with the title in bold. Lines past
+/// `MAX_TEXT_LENGTH` are cut off with "…".
+pub fn text(outgoing: &Outgoing) -> String {
+    let mut text = format!("<b>{}</b>", escape_html(&outgoing.title));
+    for line in outgoing.body.lines() {
+        let budget = MAX_TEXT_LENGTH.saturating_sub(length(&text) + 1);
+        let escaped = escape_html(line);
+        text.push('\n');
+        if length(&escaped) > budget {
+            text.push_str(&escape_within(line, budget.saturating_sub(1)));
+            text.push('…');
+            break;
+        }
+        text.push_str(&escaped);
+    }
+    text
+}
+
+fn message_id(id: &str) -> Result<i64, String> {
+    id.parse()
+        .map_err(|_| format!("Not a Telegram message id: {}", id))
+}
+
+/// Call `method`; returns its `result`. Errors leave out the URL, which holds the token.
+async fn call(
+    client: &reqwest::Client,
+    telegram: &TelegramSettings,
+    method: &str,
+    body: Value,
+) -> Result<Value, String> {
+    let url = format!(
+        "{}/bot{}/{}",
+        telegram
+            .apiUrl
+            .as_deref()
+            .unwrap_or(DEFAULT_API_URL)
+            .trim_end_matches('/'),
+        telegram.botToken,
+        method
+    );
+    let response = client
+        .post(url)
+        .timeout(TIMEOUT)
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| e.without_url().to_string())?;
+    let status = response.status();
+    let body: Value = response.json().await.unwrap_or_default();
+    if body["ok"].as_bool() != Some(true) {
+        return Err(match body["description"].as_str() {
+            Some(description) => format!("Telegram {}: {}", method, description),
+            None => format!("Telegram {} answered {}", method, status),
+        });
+    }
+    Ok(body["result"].clone())
+}
+
+/// Post `outgoing`, as a reply to `reply_to` when given; returns the message id.
+pub async fn send(
+    client: &reqwest::Client,
+    telegram: &TelegramSettings,
+    outgoing: &Outgoing,
+    reply_to: Option<&str>,
+) -> Result<String, String> {
+    let mut body =
+        json!({ "chat_id": telegram.chatId, "text": text(outgoing), "parse_mode": "HTML" });
+    if let Some(id) = reply_to {
+        body["reply_parameters"] =
+            json!({ "message_id": message_id(id)?, "allow_sending_without_reply": true });
+    }
+    let result = call(client, telegram, "sendMessage", body).await?;
+    result["message_id"]
+        .as_i64()
+        .map(|id| id.to_string())
+        .ok_or_else(|| "Telegram answered without a message id".to_string())
+}
+
+/// Replace the text of message `id` by `outgoing`.
+pub async fn edit(
+    client: &reqwest::Client,
+    telegram: &TelegramSettings,
+    id: &str,
+    outgoing: &Outgoing,
+) -> Result<(), String> {
+    let body = json!({
+        "chat_id": telegram.chatId,
+        "message_id": message_id(id)?,
+        "text": text(outgoing),
+        "parse_mode": "HTML",
+    });
+    call(client, telegram, "editMessageText", body)
+        .await
+        .map(|_| ())
+}
@@ -0,0 +1,282 @@
+//! Offline city and street index built from the TERYT registers (SIMC and ULIC).
+//!
+//! `build.rs` generates the index from the official GUS exports in `$TERYT_DIR` and
+//! bundles it gzip-compressed. Without them debug builds use the seed in
+//! `data/teryt.tsv.gz`. Lines are tab-separated:
+//!
+//! ```text
+//! C <simc> <name> <voivodeship> <district> <commune>
+//! S <simc> <sym_ul> <prefix> <name>
+//! ```
+
+use crate::api_logic::GeoItem;
+use crate::geo_search::{fold_diacritics, match_score, street_match_score};
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::sync::OnceLock;
+
+const BUNDLED_INDEX: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/teryt.tsv.gz"));
+
+/// Tauron GAIDs found for a TERYT entry. `ambiguous` is set when Tauron knows
+/// several localities or streets of that exact name.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[allow(non_snake_case)]
+pub struct TerytGaids {
+    pub cityGAID: u64,
+    pub streetGAID: Option<u64>,
+    pub ambiguous: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TerytCity {
+    pub simc: String,
+    pub name: String,
+    pub voivodeship: String,
+    pub district: String,
+    pub commune: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TerytStreet {
+    pub simc: String,
+    pub sym_ul: String,
+    /// Street type as registered, e.g. "ul.", "al.", "pl.".
+    pub prefix: String,
+    pub name: String,
+}
+
+impl TerytStreet {
+    /// Name as it is usually written, e.g. "ul. Kuźnicza".
+    pub fn full_name(&self) -> String {
+        if self.prefix.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{} {}", self.prefix, self.name)
+        }
+    }
+}
+
+/// Sorted by folded name so that a prefix search is a binary search plus a short scan.
+pub struct TerytIndex {
+    cities: Vec<(String, TerytCity)>,
+    streets: Vec<((String, String), TerytStreet)>,
+}
+
+impl TerytIndex {
+    pub fn parse(data: &str) -> Result<Self, String> {
+        let mut cities = Vec::new();
+        let mut streets = Vec::new();
+
+        for (line_no, line) in data.lines().enumerate() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            match fields.as_slice() {
+                ["C", simc, name, voivodeship, district, commune] => {
+                    let city = TerytCity {
+                        simc: simc.to_string(),
+                        name: name.to_string(),
+                        voivodeship: voivodeship.to_string(),
+                        district: district.to_string(),
+                        commune: commune.to_string(),
+                    };
+                    cities.push((fold_diacritics(name), city));
+                }
+                ["S", simc, sym_ul, prefix, name] => {
+                    let street = TerytStreet {
+                        simc: simc.to_string(),
+                        sym_ul: sym_ul.to_string(),
+                        prefix: prefix.to_string(),
+                        name: name.to_string(),
+                    };
+                    streets.push(((simc.to_string(), fold_diacritics(name)), street));
+                }
+                _ => return Err(format!("TERYT index: malformed line {}", line_no + 1)),
+            }
+        }
+
+        cities.sort_by(|a, b| a.0.cmp(&b.0));
+        streets.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(TerytIndex { cities, streets })
+    }
+
+    pub fn from_gzip(bytes: &[u8]) -> Result<Self, String> {
+        let mut data = String::new();
+        GzDecoder::new(bytes)
+            .read_to_string(&mut data)
+            .map_err(|e| format!("TERYT index: {}", e))?;
+        Self::parse(&data)
+    }
+
+    /// Localities whose name starts with `prefix`, ignoring case and diacritics.
+    pub fn search_cities(&self, prefix: &str, limit: usize) -> Vec<TerytCity> {
+        let key = fold_diacritics(prefix.trim());
+        let start = self
+            .cities
+            .partition_point(|(name, _)| name.as_str() < key.as_str());
+        self.cities[start..]
+            .iter()
+            .take_while(|(name, _)| name.starts_with(&key))
+            .take(limit)
+            .map(|(_, city)| city.clone())
+            .collect()
+    }
+
+    /// Streets of the locality `simc` whose name starts with `prefix`.
+    pub fn search_streets(&self, simc: &str, prefix: &str, limit: usize) -> Vec<TerytStreet> {
+        let key = fold_diacritics(prefix.trim());
+        let start = self
+            .streets
+            .partition_point(|((s, name), _)| (s.as_str(), name.as_str()) < (simc, key.as_str()));
+        self.streets[start..]
+            .iter()
+            .take_while(|((s, name), _)| s == simc && name.starts_with(&key))
+            .take(limit)
+            .map(|(_, street)| street.clone())
+            .collect()
+    }
+
+    pub fn city(&self, simc: &str) -> Option<&TerytCity> {
+        self.cities.iter().map(|(_, c)| c).find(|c| c.simc == simc)
+    }
+
+    pub fn street(&self, simc: &str, sym_ul: &str) -> Option<&TerytStreet> {
+        self.streets
+            .iter()
+            .map(|(_, s)| s)
+            .find(|s| s.simc == simc && s.sym_ul == sym_ul)
+    }
+}
+
+/// Pick the Tauron geo item that names the same place, with whether it was ambiguous.
+/// `items` are expected ranked, as `lookup_city`/`lookup_street` return them.
+pub fn exact_geo_match(items: &[GeoItem], name: &str, street: bool) -> Option<(u64, bool)> {
+    let score = |item: &GeoItem| {
+        if street {
+            street_match_score(&item.Name, name)
+        } else {
+            match_score(&item.Name, name)
+        }
+    };
+    let mut exact = items.iter().filter(|item| score(item) >= 0.95);
+    let first = exact.next()?;
+    Some((first.GAID, exact.next().is_some()))
+}
+
+/// The bundled index, decompressed on first use.
+pub fn bundled() -> Result<&'static TerytIndex, String> {
+    static INDEX: OnceLock<Result<TerytIndex, String>> = OnceLock::new();
+    INDEX
+        .get_or_init(|| TerytIndex::from_gzip(BUNDLED_INDEX))
+        .as_ref()
+        .map_err(|e| e.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "# sample\n\
+C\t0000001\tWrocław\tdolnośląskie\tWrocław\tWrocław\n\
+C\t0000002\tWrocanka\tpodkarpackie\tjasielski\tTarnowiec\n\
+C\t0000003\tOpole\topolskie\tOpole\tOpole\n\
+S\t0000001\t00010\tul.\tKuźnicza\n\
+S\t0000001\t00011\tul.\tKuźnicza Boczna\n\
+S\t0000001\t00012\tul.\tLegnicka\n\
+S\t0000003\t00010\tul.\tKuźnicza\n";
+
+    #[test]
+    fn test_search_cities_by_folded_prefix() {
+        let index = TerytIndex::parse(SAMPLE).unwrap();
+        let names: Vec<_> = index
+            .search_cities("wroc", 10)
+            .into_iter()
+            .map(|c| c.name)
+            .collect();
+        assert_eq!(names, vec!["Wrocanka", "Wrocław"]);
+        assert_eq!(index.search_cities("Wrocł", 10).len(), 1);
+        assert_eq!(index.search_cities("wroc", 1).len(), 1);
+        assert!(index.search_cities("Zzz", 10).is_empty());
+    }
+
+    #[test]
+    fn test_search_streets_within_city() {
+        let index = TerytIndex::parse(SAMPLE).unwrap();
+        let streets = index.search_streets("0000001", "kuzn", 10);
+        assert_eq!(streets.len(), 2);
+        assert_eq!(streets[0].full_name(), "ul. Kuźnicza");
+        assert_eq!(index.search_streets("0000003", "Kuź", 10).len(), 1);
+        assert!(index.search_streets("0000002", "Kuź", 10).is_empty());
+    }
+
+    #[test]
+    fn test_lookup_by_code() {
+        let index = TerytIndex::parse(SAMPLE).unwrap();
+        assert_eq!(index.city("0000003").unwrap().name, "Opole");
+        assert_eq!(index.street("0000001", "00012").unwrap().name, "Legnicka");
+        assert!(index.street("0000003", "00012").is_none());
+    }
+
+    #[test]
+    fn test_exact_geo_match() {
+        let geo = |gaid, name: &str| GeoItem {
+            GAID: gaid,
+            Name: name.to_string(),
+        };
+        let streets = vec![geo(1, "ul. Kuźnicza Boczna"), geo(2, "ul. Kuźnicza")];
+        assert_eq!(
+            exact_geo_match(&streets, "ul. Kuźnicza", true),
+            Some((2, false))
+        );
+        assert_eq!(
+            exact_geo_match(&streets, "Kuźnicza", true),
+            Some((2, false))
+        );
+
+        let cities = vec![
+            geo(5, "Nowa Wieś"),
+            geo(6, "Nowa Wieś"),
+            geo(7, "Nowa Wieś Wielka"),
+        ];
+        assert_eq!(
+            exact_geo_match(&cities, "Nowa Wieś", false),
+            Some((5, true))
+        );
+        assert_eq!(exact_geo_match(&cities, "Stara Wieś", false), None);
+    }
+
+    #[test]
+    fn test_malformed_line() {
+        assert!(TerytIndex::parse("C\t123\tOnly name\n").is_err());
+    }
+
+    #[test]
+    fn test_bundled_index_loads() {
+        let index = bundled().expect("bundled index should decompress");
+        assert!(!index.search_cities("Wrocław", 1).is_empty());
+    }
+
+    #[test]
+    #[cfg_attr(
+        not(teryt_full),
+        ignore = "needs the index generated from the TERYT registers"
+    )]
+    fn test_bundled_index_has_registers() {
+        let index = bundled().unwrap();
+        let opole = index
+            .search_cities("Opole", 10)
+            .into_iter()
+            .find(|c| c.name == "Opole" && c.voivodeship == "opolskie")
+            .expect("Opole");
+        let streets = index.search_streets(&opole.simc, "Ozimska", 10);
+        assert!(streets.iter().any(|s| s.full_name() == "ul. Ozimska"));
+        let zory = index.search_cities("Zory", 10);
+        assert!(zory
+            .iter()
+            .any(|c| c.name == "Żory" && c.district == "Żory"));
+    }
+}
@@ -0,0 +1,117 @@
+//! Fixtures shared by the unit tests: sample alerts and settings, scratch
+//! directories, and a local HTTP server standing in for the services the app
+//! talks to.
+
+use crate::api_logic::{AlertSource, SavedLocation, Settings, UnifiedAlert};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+pub fn alert(id: &str, source: AlertSource, start: &str, end: &str, message: &str) -> UnifiedAlert {
+    UnifiedAlert {
+        id: id.to_string(),
+        source,
+        startDate: Some(start.to_string()),
+        endDate: Some(end.to_string()),
+        message: Some(message.to_string()),
+        description: None,
+    }
+}
+
+/// ul. Kuźnicza 25 as the primary address, with "Działka" at Ruska 5 saved.
+pub fn settings() -> Settings {
+    Settings {
+        cityName: "Wrocław".to_string(),
+        streetName: "ul. Kuźnicza".to_string(),
+        houseNo: "25".to_string(),
+        cityGAID: 1,
+        streetGAID: 10,
+        locations: vec![SavedLocation {
+            label: "Działka".to_string(),
+            cityName: "Wrocław".to_string(),
+            streetName: "Ruska".to_string(),
+            houseNo: "5".to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+/// An empty `awaria_<name>` directory under the system temp dir.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("awaria_{}", name));
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A request the stand-in received.
+#[derive(Clone, Debug)]
+pub struct Request {
+    /// Request line and headers.
+    pub head: String,
+    pub body: String,
+}
+
+impl Request {
+    pub fn path(&self) -> &str {
+        self.head.split(' ').nth(1).unwrap_or_default()
+    }
+
+    pub fn json(&self) -> Value {
+        serde_json::from_str(&self.body).unwrap()
+    }
+}
+
+/// A local HTTP server answering the `n`th request (counting from 1) with the
+/// status and JSON body `respond` gives. Returns its URL and the requests it got.
+pub async fn stand_in<F>(respond: F) -> (String, Arc<Mutex<Vec<Request>>>)
+where
+    F: Fn(usize, &Request) -> (u16, String) + Send + 'static,
+{
+    let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", server.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let seen = requests.clone();
+    tokio::spawn(async move {
+        for n in 1.. {
+            let (mut stream, _) = server.accept().await.unwrap();
+            let mut data = Vec::new();
+            let mut buf = [0u8; 4096];
+            let request = loop {
+                let read = stream.read(&mut buf).await.unwrap();
+                data.extend_from_slice(&buf[..read]);
+                let text = String::from_utf8_lossy(&data).to_string();
+                let Some(end) = text.find("\r\n\r\n") else {
+                    continue;
+                };
+                let length = text[..end]
+                    .lines()
+                    .find_map(|l| {
+                        l.to_lowercase()
+                            .strip_prefix("content-length:")
+                            .map(|v| v.trim().parse().unwrap())
+                    })
+                    .unwrap_or(0usize);
+                if read == 0 || data.len() >= end + 4 + length {
+                    break Request {
+                        head: text[..end].to_string(),
+                        body: text[end + 4..].to_string(),
+                    };
+                }
+            };
+            let (status, reply) = respond(n, &request);
+            seen.lock().unwrap().push(request);
+            let response = format!(
+                "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                reply.len(),
+                reply
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+    (url, requests)
+}
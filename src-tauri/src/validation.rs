@@ -0,0 +1,337 @@
+use crate::address::normalize_house_no;
+use crate::api_logic::{GeoItem, Settings};
+use crate::geo_search::{match_score, street_match_score};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The settings can't work as saved.
+    Error,
+    /// Probably wrong, or could not be checked.
+    Warning,
+    /// Something was adjusted or confirmed.
+    Info,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueCode {
+    MissingField,
+    HouseNoNormalized,
+    FlatNumberRemoved,
+    InvalidHouseNo,
+    UnknownCity,
+    CityNameMismatch,
+    StreetNotInCity,
+    StreetNameMismatch,
+    LookupFailed,
+    OutageQueryFailed,
+    OutageQueryOk,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationIssue {
+    pub code: IssueCode,
+    pub severity: Severity,
+    /// The `Settings` field concerned, if any.
+    pub field: Option<String>,
+    pub message: String,
+    /// Value the field should probably have, e.g. the name Tauron uses for a GAID.
+    pub suggestion: Option<String>,
+}
+
+impl ValidationIssue {
+    fn new(code: IssueCode, severity: Severity, field: Option<&str>, message: String) -> Self {
+        ValidationIssue {
+            code,
+            severity,
+            field: field.map(str::to_string),
+            message,
+            suggestion: None,
+        }
+    }
+
+    fn with_suggestion(mut self, suggestion: &str) -> Self {
+        self.suggestion = Some(suggestion.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationReport {
+    /// No issue of `Severity::Error`.
+    pub valid: bool,
+    /// The settings with normalizations applied; this is what a validated save stores.
+    pub settings: Settings,
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn new(settings: Settings, issues: Vec<ValidationIssue>) -> Self {
+        let valid = !issues.iter().any(|i| i.severity == Severity::Error);
+        ValidationReport {
+            valid,
+            settings,
+            issues,
+        }
+    }
+}
+
+/// Checks that need no network: required fields and the house number form.
+pub fn normalize(settings: &Settings) -> (Settings, Vec<ValidationIssue>) {
+    let mut normalized = settings.clone();
+    let mut issues = Vec::new();
+
+    for (field, value) in [
+        ("cityName", &settings.cityName),
+        ("streetName", &settings.streetName),
+        ("houseNo", &settings.houseNo),
+    ] {
+        if value.trim().is_empty() {
+            issues.push(ValidationIssue::new(
+                IssueCode::MissingField,
+                Severity::Error,
+                Some(field),
+                format!("{} is required", field),
+            ));
+        }
+    }
+    for (field, value) in [
+        ("cityGAID", settings.cityGAID),
+        ("streetGAID", settings.streetGAID),
+    ] {
+        if value == 0 {
+            issues.push(ValidationIssue::new(
+                IssueCode::MissingField,
+                Severity::Error,
+                Some(field),
+                format!("{} is not set; look the location up first", field),
+            ));
+        }
+    }
+    normalized.cityName = settings.cityName.trim().to_string();
+    normalized.streetName = settings.streetName.trim().to_string();
+
+    if settings.houseNo.trim().is_empty() {
+        return (normalized, issues);
+    }
+    match normalize_house_no(&settings.houseNo) {
+        Some((house_no, flat)) => {
+            if let Some(flat) = flat {
+                issues.push(ValidationIssue::new(
+                    IssueCode::FlatNumberRemoved,
+                    Severity::Info,
+                    Some("houseNo"),
+                    format!(
+                        "Flat number {} removed; outages are reported per building",
+                        flat
+                    ),
+                ));
+            }
+            if house_no != settings.houseNo {
+                issues.push(
+                    ValidationIssue::new(
+                        IssueCode::HouseNoNormalized,
+                        Severity::Info,
+                        Some("houseNo"),
+                        format!(
+                            "House number \"{}\" normalized to \"{}\"",
+                            settings.houseNo, house_no
+                        ),
+                    )
+                    .with_suggestion(&house_no),
+                );
+            }
+            normalized.houseNo = house_no;
+        }
+        None => issues.push(ValidationIssue::new(
+            IssueCode::InvalidHouseNo,
+            Severity::Error,
+            Some("houseNo"),
+            format!("\"{}\" doesn't look like a house number", settings.houseNo),
+        )),
+    }
+    (normalized, issues)
+}
+
+/// Check `cityGAID` against the cities Tauron returns for `cityName`.
+pub fn check_city(
+    settings: &Settings,
+    cities: &Result<Vec<GeoItem>, String>,
+) -> Vec<ValidationIssue> {
+    let cities = match cities {
+        Ok(cities) => cities,
+        Err(e) => return vec![lookup_failed("cityGAID", e)],
+    };
+    match cities.iter().find(|c| c.GAID == settings.cityGAID) {
+        Some(city) if match_score(&city.Name, &settings.cityName) >= 0.95 => Vec::new(),
+        Some(city) => vec![ValidationIssue::new(
+            IssueCode::CityNameMismatch,
+            Severity::Warning,
+            Some("cityName"),
+            format!("Tauron calls city {} \"{}\"", settings.cityGAID, city.Name),
+        )
+        .with_suggestion(&city.Name)],
+        None => vec![ValidationIssue::new(
+            IssueCode::UnknownCity,
+            Severity::Error,
+            Some("cityGAID"),
+            format!(
+                "City {} is not among Tauron's results for \"{}\"",
+                settings.cityGAID, settings.cityName
+            ),
+        )],
+    }
+}
+
+/// Check that `streetGAID` is one of the streets Tauron lists under `cityGAID`.
+pub fn check_street(
+    settings: &Settings,
+    streets: &Result<Vec<GeoItem>, String>,
+) -> Vec<ValidationIssue> {
+    let streets = match streets {
+        Ok(streets) => streets,
+        Err(e) => return vec![lookup_failed("streetGAID", e)],
+    };
+    match streets.iter().find(|s| s.GAID == settings.streetGAID) {
+        Some(street) if street_match_score(&street.Name, &settings.streetName) >= 0.95 => {
+            Vec::new()
+        }
+        Some(street) => vec![ValidationIssue::new(
+            IssueCode::StreetNameMismatch,
+            Severity::Warning,
+            Some("streetName"),
+            format!(
+                "Tauron calls street {} \"{}\"",
+                settings.streetGAID, street.Name
+            ),
+        )
+        .with_suggestion(&street.Name)],
+        None => vec![ValidationIssue::new(
+            IssueCode::StreetNotInCity,
+            Severity::Error,
+            Some("streetGAID"),
+            format!(
+                "Street {} (\"{}\") does not belong to city {}",
+                settings.streetGAID, settings.streetName, settings.cityGAID
+            ),
+        )],
+    }
+}
+
+/// Turn the result of a test outage query (number of alerts) into an issue.
+pub fn check_outage_query(result: &Result<usize, String>) -> ValidationIssue {
+    match result {
+        Ok(count) => ValidationIssue::new(
+            IssueCode::OutageQueryOk,
+            Severity::Info,
+            None,
+            format!("Test outage query succeeded ({} current alerts)", count),
+        ),
+        Err(e) => ValidationIssue::new(
+            IssueCode::OutageQueryFailed,
+            Severity::Warning,
+            None,
+            format!("Test outage query failed: {}", e),
+        ),
+    }
+}
+
+fn lookup_failed(field: &str, error: &str) -> ValidationIssue {
+    ValidationIssue::new(
+        IssueCode::LookupFailed,
+        Severity::Warning,
+        Some(field),
+        format!("Could not verify {}: {}", field, error),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::settings;
+
+    fn geo(gaid: u64, name: &str) -> GeoItem {
+        GeoItem {
+            GAID: gaid,
+            Name: name.to_string(),
+        }
+    }
+
+    fn codes(issues: &[ValidationIssue]) -> Vec<IssueCode> {
+        issues.iter().map(|i| i.code).collect()
+    }
+
+    #[test]
+    fn test_normalize_house_no_forms() {
+        let (normalized, issues) = normalize(&settings());
+        assert_eq!(normalized.houseNo, "25");
+        assert!(issues.is_empty());
+
+        let (normalized, issues) = normalize(&Settings {
+            houseNo: "25 m. 4".to_string(),
+            ..settings()
+        });
+        assert_eq!(normalized.houseNo, "25");
+        assert_eq!(
+            codes(&issues),
+            vec![IssueCode::FlatNumberRemoved, IssueCode::HouseNoNormalized]
+        );
+        assert!(ValidationReport::new(normalized, issues).valid);
+
+        let (_, issues) = normalize(&Settings {
+            houseNo: "brak".to_string(),
+            ..settings()
+        });
+        assert_eq!(codes(&issues), vec![IssueCode::InvalidHouseNo]);
+        assert!(!ValidationReport::new(settings(), issues).valid);
+    }
+
+    #[test]
+    fn test_missing_fields() {
+        let (_, issues) = normalize(&Settings::default());
+        assert_eq!(issues.len(), 5);
+        assert!(issues.iter().all(|i| i.code == IssueCode::MissingField));
+    }
+
+    #[test]
+    fn test_check_city() {
+        let s = settings();
+        assert!(check_city(&s, &Ok(vec![geo(1, "Wrocław")])).is_empty());
+
+        let issues = check_city(&s, &Ok(vec![geo(1, "Wrocław-Psie Pole")]));
+        assert_eq!(codes(&issues), vec![IssueCode::CityNameMismatch]);
+        assert_eq!(issues[0].suggestion.as_deref(), Some("Wrocław-Psie Pole"));
+
+        let issues = check_city(&s, &Ok(vec![geo(2, "Wrocław")]));
+        assert_eq!(issues[0].severity, Severity::Error);
+
+        let issues = check_city(&s, &Err("timeout".to_string()));
+        assert_eq!(codes(&issues), vec![IssueCode::LookupFailed]);
+        assert_eq!(issues[0].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_check_street() {
+        let s = settings();
+        assert!(check_street(&s, &Ok(vec![geo(10, "ul. Kuźnicza")])).is_empty());
+
+        let issues = check_street(&s, &Ok(vec![geo(11, "ul. Kuźnicza")]));
+        assert_eq!(codes(&issues), vec![IssueCode::StreetNotInCity]);
+
+        let issues = check_street(&s, &Ok(vec![geo(10, "ul. Ruska")]));
+        assert_eq!(codes(&issues), vec![IssueCode::StreetNameMismatch]);
+    }
+
+    #[test]
+    fn test_check_outage_query() {
+        assert_eq!(check_outage_query(&Ok(2)).severity, Severity::Info);
+        assert_eq!(
+            check_outage_query(&Err("HTTP error! status: 500".to_string())).code,
+            IssueCode::OutageQueryFailed
+        );
+    }
+}
@@ -0,0 +1,496 @@
+//! Webhook notification channel: every notification the app shows (see
+//! `notifier::Outgoing`) is also sent to the webhooks in `Settings.webhooks`, with
+//! presets for ntfy and Gotify. Bodies are templates with `{{placeholder}}`s filled
+//! from `WebhookPayload`. With a secret, the body is signed with HMAC-SHA256 in
+//! `X-Awaria-Signature`. Failed deliveries are retried with a growing delay.
+
+use crate::api_logic::{AlertSource, Settings};
+use crate::notifier::Outgoing;
+use crate::rules::{EventKind, RuleAction};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+pub const SIGNATURE_HEADER: &str = "X-Awaria-Signature";
+
+const DEFAULT_RETRIES: u32 = 2;
+
+/// Delay before the first retry, doubled for every further one.
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookPreset {
+    /// `url` is the topic URL, e.g. "https://ntfy.sh/my-topic".
+    Ntfy,
+    /// `url` is the server URL, `token` the application token.
+    Gotify,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[allow(non_snake_case)]
+pub struct Webhook {
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub preset: Option<WebhookPreset>,
+    /// "POST" when unset.
+    #[serde(default)]
+    pub method: Option<String>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Body template; the `WebhookPayload` as JSON (or the preset's body) when unset.
+    #[serde(default)]
+    pub body: Option<String>,
+    /// "application/json" when unset. Placeholders in JSON bodies are escaped.
+    #[serde(default)]
+    pub contentType: Option<String>,
+    /// ntfy access token or Gotify application token.
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub hmacSecret: Option<String>,
+    /// Retries after a failed attempt; 2 when unset.
+    #[serde(default)]
+    pub retries: Option<u32>,
+}
+
+/// What a webhook gets to know about a notification; the field names are the
+/// template placeholders.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookPayload {
+    pub title: String,
+    pub body: String,
+    pub alert_id: String,
+    pub action: RuleAction,
+    pub event: Option<EventKind>,
+    pub source: Option<AlertSource>,
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+    pub message: Option<String>,
+    /// 1 (lowest) to 5 (urgent), as ntfy counts.
+    pub priority: u8,
+}
+
+impl WebhookPayload {
+    pub fn new(outgoing: &Outgoing) -> Self {
+        let alert = outgoing.alert.as_ref();
+        WebhookPayload {
+            title: outgoing.title.clone(),
+            body: outgoing.body.clone(),
+            alert_id: outgoing.alert_id.clone(),
+            action: outgoing.action,
+            event: outgoing.event,
+            source: alert.map(|a| a.source),
+            start_date: alert.and_then(|a| a.startDate.clone()),
+            end_date: alert.and_then(|a| a.endDate.clone()),
+            message: alert.and_then(|a| a.message.clone()),
+            priority: match outgoing.action {
+                RuleAction::NotifyNow => 5,
+                RuleAction::NotifySilent => 2,
+                _ => 3,
+            },
+        }
+    }
+}
+
+/// Fill the `{{name}}` placeholders of `template` from `payload`; unknown ones stay.
+/// In `json` templates the values are escaped to go inside a JSON string.
+pub fn render_template(template: &str, payload: &WebhookPayload, json: bool) -> String {
+    let fields = serde_json::to_value(payload).unwrap_or_default();
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find("{{") {
+        let Some(close) = rest[open..].find("}}") else {
+            break;
+        };
+        out.push_str(&rest[..open]);
+        let placeholder = &rest[open..open + close + 2];
+        let value = match fields.get(placeholder[2..placeholder.len() - 2].trim()) {
+            Some(serde_json::Value::String(s)) => s.clone(),
+            Some(serde_json::Value::Null) => String::new(),
+            Some(other) => other.to_string(),
+            None => placeholder.to_string(),
+        };
+        if json {
+            let quoted = serde_json::Value::String(value).to_string();
+            out.push_str(&quoted[1..quoted.len() - 1]);
+        } else {
+            out.push_str(&value);
+        }
+        rest = &rest[open + close + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+pub fn sign(secret: &str, body: &str) -> Result<String, String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).map_err(|e| e.to_string())?;
+    mac.update(body.as_bytes());
+    Ok(format!(
+        "sha256={}",
+        hex::encode(mac.finalize().into_bytes())
+    ))
+}
+
+/// A request ready to send.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreparedRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+/// The ntfy topic URL split into the server and the topic.
+fn ntfy_topic(url: &str) -> Result<(&str, &str), String> {
+    let trimmed = url.trim_end_matches('/');
+    match trimmed.rsplit_once('/') {
+        Some((server, topic)) if !topic.is_empty() && server.contains("://") => Ok((server, topic)),
+        _ => Err(format!("Not an ntfy topic URL: {}", url)),
+    }
+}
+
+/// The request `webhook` makes for `payload`.
+pub fn prepare(webhook: &Webhook, payload: &WebhookPayload) -> Result<PreparedRequest, String> {
+    let content_type = webhook
+        .contentType
+        .clone()
+        .unwrap_or_else(|| "application/json".to_string());
+    let json = content_type.contains("json");
+    let mut headers = vec![("Content-Type".to_string(), content_type)];
+
+    let (url, body) = match webhook.preset {
+        Some(WebhookPreset::Ntfy) => {
+            // Published as JSON to the server root, so titles may use any characters
+            let (server, topic) = ntfy_topic(&webhook.url)?;
+            if let Some(token) = &webhook.token {
+                headers.push(("Authorization".to_string(), format!("Bearer {}", token)));
+            }
+            let body = serde_json::json!({
+                "topic": topic,
+                "title": payload.title,
+                "message": payload.body,
+                "priority": payload.priority,
+                "tags": ["warning"],
+            });
+            (server.to_string(), body.to_string())
+        }
+        Some(WebhookPreset::Gotify) => {
+            if let Some(token) = &webhook.token {
+                headers.push(("X-Gotify-Key".to_string(), token.clone()));
+            }
+            let body = serde_json::json!({
+                "title": payload.title,
+                "message": payload.body,
+                // Gotify counts 0-10
+                "priority": payload.priority * 2,
+            });
+            (
+                format!("{}/message", webhook.url.trim_end_matches('/')),
+                body.to_string(),
+            )
+        }
+        None => {
+            let body = serde_json::to_string(payload).map_err(|e| e.to_string())?;
+            (webhook.url.clone(), body)
+        }
+    };
+    let body = match &webhook.body {
+        Some(template) => render_template(template, payload, json),
+        None => body,
+    };
+
+    for (name, value) in &webhook.headers {
+        headers.retain(|(existing, _)| !existing.eq_ignore_ascii_case(name));
+        headers.push((name.clone(), render_template(value, payload, false)));
+    }
+    if let Some(secret) = webhook.hmacSecret.as_deref().filter(|s| !s.is_empty()) {
+        headers.push((SIGNATURE_HEADER.to_string(), sign(secret, &body)?));
+    }
+
+    Ok(PreparedRequest {
+        method: webhook
+            .method
+            .clone()
+            .unwrap_or_else(|| "POST".to_string())
+            .to_uppercase(),
+        url,
+        headers,
+        body,
+    })
+}
+
+/// How a delivery went.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookDelivery {
+    pub status: u16,
+    pub attempts: u32,
+}
+
+fn retryable(status: reqwest::StatusCode) -> bool {
+    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Send `request`, retrying network errors, server errors and 429 up to `retries`
+/// times, waiting `delay` before the first retry and twice as long for each next.
+pub async fn send(
+    client: &reqwest::Client,
+    request: &PreparedRequest,
+    retries: u32,
+    delay: Duration,
+) -> Result<WebhookDelivery, String> {
+    let method =
+        reqwest::Method::from_bytes(request.method.as_bytes()).map_err(|e| e.to_string())?;
+    let mut attempts = 0;
+    let mut wait = delay;
+    loop {
+        attempts += 1;
+        let mut builder = client
+            .request(method.clone(), &request.url)
+            .timeout(TIMEOUT);
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        let error = match builder.body(request.body.clone()).send().await {
+            Ok(res) if res.status().is_success() => {
+                return Ok(WebhookDelivery {
+                    status: res.status().as_u16(),
+                    attempts,
+                })
+            }
+            Ok(res) if !retryable(res.status()) => return Err(format!("HTTP {}", res.status())),
+            Ok(res) => format!("HTTP {}", res.status()),
+            Err(e) => e.to_string(),
+        };
+        if attempts > retries {
+            return Err(format!("{} (after {} attempts)", error, attempts));
+        }
+        tokio::time::sleep(wait).await;
+        wait *= 2;
+    }
+}
+
+/// Send `outgoing` through `webhook`.
+pub async fn deliver(
+    client: &reqwest::Client,
+    webhook: &Webhook,
+    outgoing: &Outgoing,
+    delay: Duration,
+) -> Result<WebhookDelivery, String> {
+    let request = prepare(webhook, &WebhookPayload::new(outgoing))?;
+    send(
+        client,
+        &request,
+        webhook.retries.unwrap_or(DEFAULT_RETRIES),
+        delay,
+    )
+    .await
+}
+
+/// Send `outgoing` to every configured webhook in the background.
+pub fn dispatch(settings: &Settings, outgoing: &[Outgoing]) {
+    if settings.webhooks.is_empty() || outgoing.is_empty() {
+        return;
+    }
+    let webhooks = settings.webhooks.clone();
+    let outgoing = outgoing.to_vec();
+    tauri::async_runtime::spawn(async move {
+        let client = reqwest::Client::new();
+        for webhook in &webhooks {
+            for o in &outgoing {
+                if let Err(e) = deliver(&client, webhook, o, RETRY_DELAY).await {
+                    log::warn!("Webhook {} failed: {}", webhook.name, e);
+                }
+            }
+        }
+    });
+}
+
+/// A sample notification, for trying a webhook out.
+pub async fn send_test(webhook: &Webhook) -> Result<WebhookDelivery, String> {
+    let outgoing = Outgoing {
+        title: "AWARIA".to_string(),
+        body: "Test notification".to_string(),
+        line: "AWARIA".to_string(),
+        alert_id: String::new(),
+        action: RuleAction::Notify,
+        alert: None,
+        event: None,
+        previous_alert_id: None,
+    };
+    deliver(&reqwest::Client::new(), webhook, &outgoing, RETRY_DELAY).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_logic::UnifiedAlert;
+    use crate::notifier::Lang;
+    use crate::test_support::{stand_in, Request};
+    use std::sync::{Arc, Mutex};
+
+    fn outgoing() -> Outgoing {
+        let alert = UnifiedAlert {
+            id: "tauron-a".to_string(),
+            source: AlertSource::Tauron,
+            startDate: Some("2026-03-12T08:00:00".to_string()),
+            endDate: Some("2026-03-12T16:00:00".to_string()),
+            message: Some("Kuźnicza \"1-30\"".to_string()),
+            description: None,
+        };
+        Outgoing::new(
+            "Power outage at your address".to_string(),
+            &alert,
+            RuleAction::NotifyNow,
+            Lang::En,
+        )
+    }
+
+    /// A local HTTP server answering with `statuses` in turn (200 when they run
+    /// out), recording the requests it gets.
+    async fn listener(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<Request>>>) {
+        stand_in(move |n, _| (statuses.get(n - 1).copied().unwrap_or(200), String::new())).await
+    }
+
+    #[test]
+    fn test_render_template() {
+        let payload = WebhookPayload::new(&outgoing());
+        let template =
+            r#"{"text": "{{title}}: {{ message }}", "p": {{priority}}, "x": "{{nope}}{{endDate}}"#;
+        assert_eq!(
+            render_template(template, &payload, true),
+            r#"{"text": "Power outage at your address: Kuźnicza \"1-30\"", "p": 5, "x": "{{nope}}2026-03-12T16:00:00"#
+        );
+        assert_eq!(
+            render_template("{{source}} {{event}}|", &payload, false),
+            "tauron |"
+        );
+    }
+
+    #[test]
+    fn test_presets() {
+        let payload = WebhookPayload::new(&outgoing());
+        let ntfy = Webhook {
+            name: "phone".to_string(),
+            url: "https://ntfy.example.com/awaria/".to_string(),
+            preset: Some(WebhookPreset::Ntfy),
+            token: Some("tk".to_string()),
+            ..Default::default()
+        };
+        let request = prepare(&ntfy, &payload).unwrap();
+        assert_eq!(request.url, "https://ntfy.example.com");
+        assert!(request
+            .headers
+            .contains(&("Authorization".to_string(), "Bearer tk".to_string())));
+        let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+        assert_eq!(body["topic"], "awaria");
+        assert_eq!(body["priority"], 5);
+        assert!(prepare(
+            &Webhook {
+                url: "ntfy.sh".to_string(),
+                ..ntfy
+            },
+            &payload
+        )
+        .is_err());
+
+        let gotify = Webhook {
+            name: "gotify".to_string(),
+            url: "http://gotify.lan".to_string(),
+            preset: Some(WebhookPreset::Gotify),
+            token: Some("app".to_string()),
+            headers: BTreeMap::from([("X-Source".to_string(), "{{source}}".to_string())]),
+            ..Default::default()
+        };
+        let request = prepare(&gotify, &payload).unwrap();
+        assert_eq!(request.url, "http://gotify.lan/message");
+        assert!(request
+            .headers
+            .contains(&("X-Gotify-Key".to_string(), "app".to_string())));
+        assert!(request
+            .headers
+            .contains(&("X-Source".to_string(), "tauron".to_string())));
+        let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+        assert_eq!(body["priority"], 10);
+    }
+
+    #[tokio::test]
+    async fn test_send_signs_and_retries() {
+        let (url, requests) = listener(vec![503, 500]).await;
+        let webhook = Webhook {
+            name: "local".to_string(),
+            url: format!("{}/hook", url),
+            method: Some("put".to_string()),
+            body: Some("{{title}}".to_string()),
+            contentType: Some("text/plain".to_string()),
+            hmacSecret: Some("secret".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            sign("key", "The quick brown fox jumps over the lazy dog").unwrap(),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+        let delivery = deliver(
+            &reqwest::Client::new(),
+            &webhook,
+            &outgoing(),
+            Duration::ZERO,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            delivery,
+            WebhookDelivery {
+                status: 200,
+                attempts: 3
+            }
+        );
+
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[2].head.starts_with("PUT /hook "));
+        assert_eq!(requests[2].body, "Power outage at your address");
+        let signature = sign("secret", "Power outage at your address").unwrap();
+        assert!(requests[2]
+            .head
+            .to_lowercase()
+            .contains(&format!("x-awaria-signature: {}", signature)));
+
+        // Out of retries, and client errors aren't retried
+        let (url, requests) = listener(vec![500, 500, 500]).await;
+        let failing = Webhook {
+            url,
+            retries: Some(1),
+            ..webhook.clone()
+        };
+        let error = deliver(
+            &reqwest::Client::new(),
+            &failing,
+            &outgoing(),
+            Duration::ZERO,
+        )
+        .await
+        .unwrap_err();
+        assert!(error.contains("after 2 attempts"), "{}", error);
+        assert_eq!(requests.lock().unwrap().len(), 2);
+
+        let (url, requests) = listener(vec![404]).await;
+        let missing = Webhook { url, ..webhook };
+        assert!(deliver(
+            &reqwest::Client::new(),
+            &missing,
+            &outgoing(),
+            Duration::ZERO
+        )
+        .await
+        .is_err());
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+}